@@ -18,7 +18,7 @@ use crate::{
 use core::fmt;
 use core::marker::PhantomData;
 
-use self::config::ExternalTrigger12;
+use self::config::{ExternalTrigger12, InjectedTrigger12};
 
 #[cfg(any(
     feature = "stm32g471",
@@ -29,7 +29,7 @@ use self::config::ExternalTrigger12;
     feature = "stm32g491",
     feature = "stm32g4a1",
 ))]
-use self::config::ExternalTrigger345;
+use self::config::{ExternalTrigger345, InjectedTrigger345};
 
 /// Vref internal signal, used for calibration
 pub struct Vref;
@@ -45,6 +45,12 @@ impl Vref {
     pub fn sample_to_millivolts(sample: u16) -> u16 {
         Self::sample_to_millivolts_ext(sample, VDDA_CALIB, config::Resolution::Twelve)
     }
+    /// Converts millivolts to a sample value using calibrated VDDA and configured resolution
+    #[inline(always)]
+    pub fn millivolts_to_sample_ext(millivolts: u16, vdda: u32, resolution: config::Resolution) -> u16 {
+        let mx_s = resolution.to_max_sample();
+        ((u32::from(millivolts) * mx_s) / vdda).min(mx_s) as u16
+    }
 }
 
 /// Vbat internal signal, used for monitoring the battery (if used)
@@ -137,25 +143,28 @@ macro_rules! adc_pins {
     };
 }
 
-macro_rules! adc_op_pga {
-    ($($opamp:ty => ($adc:ident, $chan:expr)),+ $(,)*) => {
+macro_rules! adc_opamp {
+    ($($opamp:ident => ($adc:ident, $chan:expr)),+ $(,)*) => {
         $(
-            impl<A, B> crate::hal_api_custom::adc::Channel<stm32::$adc> for $opamp {
-                type ID = u8;
-                fn channel() -> u8 { $chan }
-            }
+            adc_opamp!(@channel $opamp, $adc, $chan, Follower<A>);
+            adc_opamp!(@channel $opamp, $adc, $chan, OpenLoop<A, B>);
+            adc_opamp!(@channel $opamp, $adc, $chan, Pga<A, B>);
         )+
     };
-}
+    (@channel $opamp:ident, $adc:ident, $chan:expr, $state:ident<$($gen:ident),+>) => {
+        impl<$($gen),+> crate::hal_api_custom::adc::Channel<stm32::$adc>
+            for opamp::$opamp::$state<$($gen,)+ opamp::InternalOutput>
+        {
+            type ID = u8;
+            fn channel() -> u8 { $chan }
+        }
 
-macro_rules! adc_op_follower {
-    ($($opamp:ty => ($adc:ident, $chan:expr)),+ $(,)*) => {
-        $(
-            impl<A> crate::hal_api_custom::adc::Channel<stm32::$adc> for $opamp {
-                type ID = u8;
-                fn channel() -> u8 { $chan }
-            }
-        )+
+        impl<$($gen),+> crate::hal_api_custom::adc::Channel<stm32::$adc>
+            for opamp::$opamp::Locked<opamp::$opamp::$state<$($gen,)+ opamp::InternalOutput>>
+        {
+            type ID = u8;
+            fn channel() -> u8 { $chan }
+        }
     };
 }
 
@@ -685,6 +694,278 @@ pub mod config {
         }
     }
 
+    /// The place in the injected sequence a given channel should be captured
+    #[derive(Debug, PartialEq, PartialOrd, Copy, Clone)]
+    pub enum InjectedSequence {
+        /// 1
+        One,
+        /// 2
+        Two,
+        /// 3
+        Three,
+        /// 4
+        Four,
+    }
+
+    impl From<InjectedSequence> for u8 {
+        fn from(s: InjectedSequence) -> u8 {
+            match s {
+                InjectedSequence::One => 0,
+                InjectedSequence::Two => 1,
+                InjectedSequence::Three => 2,
+                InjectedSequence::Four => 3,
+            }
+        }
+    }
+
+    /// Possible external triggers for injected conversions
+    ///
+    /// This applies to ADC1 and ADC2
+    #[derive(Debug, Clone, Copy, Default)]
+    pub enum InjectedTrigger12 {
+        /// TIM1 trigger out
+        #[default]
+        Tim_1_trgo,
+        /// TIM1 compare channel 4
+        Tim_1_cc_4,
+        /// TIM2 trigger out
+        Tim_2_trgo,
+        /// TIM2 compare channel 1
+        Tim_2_cc_1,
+        /// TIM3 compare channel 4
+        Tim_3_cc_4,
+        /// TIM4 trigger out
+        Tim_4_trgo,
+        /// External interupt line 15
+        Exti_15,
+        /// TIM8 compare channel 4
+        Tim_8_cc_4,
+        /// TIM1 trigger out 2
+        Tim_1_trgo_2,
+        /// TIM8 trigger out
+        Tim_8_trgo,
+        /// TIM8 trigger out 2
+        Tim_8_trgo_2,
+        /// TIM3 compare channel 3
+        Tim_3_cc_3,
+        /// TIM3 trigger out
+        Tim_3_trgo,
+        /// TIM3 compare channel 1
+        Tim_3_cc_1,
+        /// TIM6 trigger out
+        Tim_6_trgo,
+        /// TIM15 trigger out
+        Tim_15_trgo,
+        /// TIM20 trigger out
+        Tim_20_trgo,
+        /// TIM20 trigger out 2
+        Tim_20_trgo_2,
+        /// TIM20 compare channel 4
+        Tim_20_cc_4,
+        /// hrtim_adc_trg2
+        Hrtim_adc_trg_2,
+        /// hrtim_adc_trg4
+        Hrtim_adc_trg_4,
+        /// hrtim_adc_trg5
+        Hrtim_adc_trg_5,
+        /// hrtim_adc_trg6
+        Hrtim_adc_trg_6,
+        /// hrtim_adc_trg7
+        Hrtim_adc_trg_7,
+        /// hrtim_adc_trg8
+        Hrtim_adc_trg_8,
+        /// hrtim_adc_trg9
+        Hrtim_adc_trg_9,
+        /// hrtim_adc_trg10
+        Hrtim_adc_trg_10,
+        /// TIM16 compare channel 1
+        Tim_16_cc_1,
+        /// LP_timeout
+        Lp_timeout,
+        /// TIM7 trigger out
+        Tim_7_trgo,
+    }
+
+    /// Possible external triggers for injected conversions
+    ///
+    /// This applies to ADC3, ADC4 and ADC5
+    #[cfg(any(
+        feature = "stm32g471",
+        feature = "stm32g473",
+        feature = "stm32g474",
+        feature = "stm32g483",
+        feature = "stm32g484",
+        feature = "stm32g491",
+        feature = "stm32g4a1",
+    ))]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub enum InjectedTrigger345 {
+        /// TIM1 trigger out
+        #[default]
+        Tim_1_trgo,
+        /// TIM1 compare channel 4
+        Tim_1_cc_4,
+        /// TIM2 trigger out
+        Tim_2_trgo,
+        /// TIM8 compare channel 2
+        Tim_8_cc_2,
+        /// TIM4 compare channel 3
+        Tim_4_cc_3,
+        /// TIM4 trigger out
+        Tim_4_trgo,
+        /// TIM4 compare channel 4
+        Tim_4_cc_4,
+        /// TIM8 compare channel 4
+        Tim_8_cc_4,
+        /// TIM1 trigger out 2
+        Tim_1_trgo_2,
+        /// TIM8 trigger out
+        Tim_8_trgo,
+        /// TIM8 trigger out 2
+        Tim_8_trgo_2,
+        /// TIM1 compare channel 3
+        Tim_1_cc_3,
+        /// TIM3 trigger out
+        Tim_3_trgo,
+        /// External interupt line 3
+        Exti_3,
+        /// TIM6 trigger out
+        Tim_6_trgo,
+        /// TIM15 trigger out
+        Tim_15_trgo,
+        /// TIM20 trigger out
+        Tim_20_trgo,
+        /// TIM20 trigger out 2
+        Tim_20_trgo_2,
+        /// TIM20 compare channel 2
+        Tim_20_cc_2,
+        /// hrtim_adc_trg2
+        Hrtim_adc_trg_2,
+        /// hrtim_adc_trg4
+        Hrtim_adc_trg_4,
+        /// hrtim_adc_trg5
+        Hrtim_adc_trg_5,
+        /// hrtim_adc_trg6
+        Hrtim_adc_trg_6,
+        /// hrtim_adc_trg7
+        Hrtim_adc_trg_7,
+        /// hrtim_adc_trg8
+        Hrtim_adc_trg_8,
+        /// hrtim_adc_trg9
+        Hrtim_adc_trg_9,
+        /// hrtim_adc_trg10
+        Hrtim_adc_trg_10,
+        /// hrtim_adc_trg1
+        Hrtim_adc_trg_1,
+        /// hrtim_adc_trg3
+        Hrtim_adc_trg_3,
+        /// LP_timeout
+        Lp_timeout,
+        /// TIM7 trigger out
+        Tim_7_trgo,
+    }
+
+    impl From<InjectedTrigger12> for u8 {
+        fn from(et: InjectedTrigger12) -> u8 {
+            match et {
+                InjectedTrigger12::Tim_1_trgo => 0b00000,
+                InjectedTrigger12::Tim_1_cc_4 => 0b00001,
+                InjectedTrigger12::Tim_2_trgo => 0b00010,
+                InjectedTrigger12::Tim_2_cc_1 => 0b00011,
+                InjectedTrigger12::Tim_3_cc_4 => 0b00100,
+                InjectedTrigger12::Tim_4_trgo => 0b00101,
+                InjectedTrigger12::Exti_15 => 0b00110,
+                InjectedTrigger12::Tim_8_cc_4 => 0b00111,
+                InjectedTrigger12::Tim_1_trgo_2 => 0b01000,
+                InjectedTrigger12::Tim_8_trgo => 0b01001,
+                InjectedTrigger12::Tim_8_trgo_2 => 0b01010,
+                InjectedTrigger12::Tim_3_cc_3 => 0b01011,
+                InjectedTrigger12::Tim_3_trgo => 0b01100,
+                InjectedTrigger12::Tim_3_cc_1 => 0b01101,
+                InjectedTrigger12::Tim_6_trgo => 0b01110,
+                InjectedTrigger12::Tim_15_trgo => 0b01111,
+                InjectedTrigger12::Tim_20_trgo => 0b10000,
+                InjectedTrigger12::Tim_20_trgo_2 => 0b10001,
+                InjectedTrigger12::Tim_20_cc_4 => 0b10010,
+                InjectedTrigger12::Hrtim_adc_trg_2 => 0b10011,
+                InjectedTrigger12::Hrtim_adc_trg_4 => 0b10100,
+                InjectedTrigger12::Hrtim_adc_trg_5 => 0b10101,
+                InjectedTrigger12::Hrtim_adc_trg_6 => 0b10110,
+                InjectedTrigger12::Hrtim_adc_trg_7 => 0b10111,
+                InjectedTrigger12::Hrtim_adc_trg_8 => 0b11000,
+                InjectedTrigger12::Hrtim_adc_trg_9 => 0b11001,
+                InjectedTrigger12::Hrtim_adc_trg_10 => 0b11010,
+                InjectedTrigger12::Tim_16_cc_1 => 0b11011,
+                // Reserved => 0b11100
+                InjectedTrigger12::Lp_timeout => 0b11101,
+                InjectedTrigger12::Tim_7_trgo => 0b11110,
+                // Reserved => 0b11111
+            }
+        }
+    }
+
+    #[cfg(any(
+        feature = "stm32g471",
+        feature = "stm32g473",
+        feature = "stm32g474",
+        feature = "stm32g483",
+        feature = "stm32g484",
+        feature = "stm32g491",
+        feature = "stm32g4a1",
+    ))]
+    impl From<InjectedTrigger345> for u8 {
+        fn from(et: InjectedTrigger345) -> u8 {
+            match et {
+                InjectedTrigger345::Tim_1_trgo => 0b00000,
+                InjectedTrigger345::Tim_1_cc_4 => 0b00001,
+                InjectedTrigger345::Tim_2_trgo => 0b00010,
+                InjectedTrigger345::Tim_8_cc_2 => 0b00011,
+                InjectedTrigger345::Tim_4_cc_3 => 0b00100,
+                InjectedTrigger345::Tim_4_trgo => 0b00101,
+                InjectedTrigger345::Tim_4_cc_4 => 0b00110,
+                InjectedTrigger345::Tim_8_cc_4 => 0b00111,
+                InjectedTrigger345::Tim_1_trgo_2 => 0b01000,
+                InjectedTrigger345::Tim_8_trgo => 0b01001,
+                InjectedTrigger345::Tim_8_trgo_2 => 0b01010,
+                InjectedTrigger345::Tim_1_cc_3 => 0b01011,
+                InjectedTrigger345::Tim_3_trgo => 0b01100,
+                InjectedTrigger345::Exti_3 => 0b01101,
+                InjectedTrigger345::Tim_6_trgo => 0b01110,
+                InjectedTrigger345::Tim_15_trgo => 0b01111,
+                InjectedTrigger345::Tim_20_trgo => 0b10000,
+                InjectedTrigger345::Tim_20_trgo_2 => 0b10001,
+                InjectedTrigger345::Tim_20_cc_2 => 0b10010,
+                InjectedTrigger345::Hrtim_adc_trg_2 => 0b10011,
+                InjectedTrigger345::Hrtim_adc_trg_4 => 0b10100,
+                InjectedTrigger345::Hrtim_adc_trg_5 => 0b10101,
+                InjectedTrigger345::Hrtim_adc_trg_6 => 0b10110,
+                InjectedTrigger345::Hrtim_adc_trg_7 => 0b10111,
+                InjectedTrigger345::Hrtim_adc_trg_8 => 0b11000,
+                InjectedTrigger345::Hrtim_adc_trg_9 => 0b11001,
+                InjectedTrigger345::Hrtim_adc_trg_10 => 0b11010,
+                InjectedTrigger345::Hrtim_adc_trg_1 => 0b11011,
+                InjectedTrigger345::Hrtim_adc_trg_3 => 0b11100,
+                InjectedTrigger345::Lp_timeout => 0b11101,
+                InjectedTrigger345::Tim_7_trgo => 0b11110,
+                // Reserved => 0b11111
+            }
+        }
+    }
+
+    /// Injected context queue mode
+    ///
+    /// Every write of the injected sequence and trigger (JSQR) is a context. With the queue
+    /// enabled up to two contexts are queued, each context is used for one injected sequence.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum InjectedQueue {
+        /// Queue disabled, the injected sequence is changed directly
+        Disabled,
+        /// Queue enabled, the last context is kept when the queue runs empty
+        KeepLastContext,
+        /// Queue enabled, injected triggers are ignored when the queue runs empty
+        EmptyQueue,
+    }
+
     /// Possible oversampling shift
     #[derive(Debug, Clone, Copy)]
     pub enum OverSamplingShift {
@@ -925,6 +1206,121 @@ pub mod config {
         }
     }
 
+    /// Analog watchdog selection
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum AnalogWatchdog {
+        /// Analog watchdog 1, guards one or all channels with 12-bit thresholds
+        One = 0,
+        /// Analog watchdog 2, guards a set of channels with 8-bit thresholds
+        Two = 1,
+        /// Analog watchdog 3, guards a set of channels with 8-bit thresholds
+        Three = 2,
+    }
+
+    /// Channels guarded by analog watchdog 2 or 3
+    #[derive(Debug, Clone, Copy, Default, PartialEq)]
+    pub struct WatchdogChannels(pub(crate) u32);
+    impl WatchdogChannels {
+        /// All channels
+        pub fn all() -> Self {
+            WatchdogChannels(0x7_ffff)
+        }
+
+        /// Adds a channel
+        pub fn add<PIN, ADC>(&mut self)
+        where
+            PIN: crate::hal_api_custom::adc::Channel<ADC, ID = u8>,
+        {
+            self.0 |= 1 << PIN::channel();
+        }
+
+        /// Removes a channel
+        pub fn remove<PIN, ADC>(&mut self)
+        where
+            PIN: crate::hal_api_custom::adc::Channel<ADC, ID = u8>,
+        {
+            self.0 &= !(1 << PIN::channel());
+        }
+    }
+
+    /// Offset register (OFRy), each can correct the conversions of one channel
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Offset {
+        /// OFR1
+        One = 0,
+        /// OFR2
+        Two = 1,
+        /// OFR3
+        Three = 2,
+        /// OFR4
+        Four = 3,
+    }
+
+    impl From<InjectedSequence> for Offset {
+        fn from(s: InjectedSequence) -> Offset {
+            match s {
+                InjectedSequence::One => Offset::One,
+                InjectedSequence::Two => Offset::Two,
+                InjectedSequence::Three => Offset::Three,
+                InjectedSequence::Four => Offset::Four,
+            }
+        }
+    }
+
+    /// Whether the offset is subtracted from or added to the conversion result (OFFSETPOS)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum OffsetSign {
+        /// The offset is subtracted
+        Negative,
+        /// The offset is added
+        Positive,
+    }
+
+    /// Offset correction of a channel
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct OffsetConfig {
+        pub(crate) channel: u8,
+        pub(crate) offset: u16,
+        pub(crate) sign: OffsetSign,
+        pub(crate) saturation: bool,
+    }
+
+    impl OffsetConfig {
+        /// Subtracts `offset` from the conversions of the channel. The offset is given at 12-bit
+        /// resolution, for lower resolutions the lower bits are ignored.
+        pub fn new<PIN, ADC>(offset: u16) -> Self
+        where
+            PIN: crate::hal_api_custom::adc::Channel<ADC, ID = u8>,
+        {
+            Self::for_channel(PIN::channel(), offset)
+        }
+
+        pub(crate) fn for_channel(channel: u8, offset: u16) -> Self {
+            assert!(offset < 0x1000);
+            OffsetConfig {
+                channel,
+                offset,
+                sign: OffsetSign::Negative,
+                saturation: false,
+            }
+        }
+
+        /// change the sign field
+        #[inline(always)]
+        pub fn sign(mut self, sign: OffsetSign) -> Self {
+            self.sign = sign;
+            self
+        }
+
+        /// Enable/disable saturation (SATEN). When enabled the result is clamped to the unsigned
+        /// range of the resolution, otherwise a negative offset gives signed results.
+        #[inline(always)]
+        pub fn saturation(mut self, saturation: bool) -> Self {
+            self.saturation = saturation;
+            self
+        }
+    }
+
     /// Configuration for the adc.
     /// There are some additional parameters on the adc peripheral that can be
     /// added here when needed but this covers several basic usecases.
@@ -943,6 +1339,8 @@ pub mod config {
         pub(crate) default_sample_time: SampleTime,
         pub(crate) vdda: Option<u32>,
         pub(crate) auto_delay: bool,
+        pub(crate) offsets: [Option<OffsetConfig>; 4],
+        pub(crate) gain_compensation: Option<u16>,
 
         /// Sets the differential input type of the Adc
         pub difsel: DifferentialSelection,
@@ -1037,6 +1435,24 @@ pub mod config {
             self.auto_delay = delay;
             self
         }
+
+        /// Assigns an offset correction to an offset register, `None` disables it
+        #[inline(always)]
+        pub fn offset(mut self, offset: Offset, config: Option<OffsetConfig>) -> Self {
+            self.offsets[offset as usize] = config;
+            self
+        }
+
+        /// Enable/disable gain compensation (GCOMP), applied to all channels.
+        /// The conversions are multiplied by `coefficient / 4096`, the coefficient is 14 bits.
+        #[inline(always)]
+        pub fn gain_compensation(mut self, coefficient: Option<u16>) -> Self {
+            if let Some(coefficient) = coefficient {
+                assert!(coefficient < 0x4000);
+            }
+            self.gain_compensation = coefficient;
+            self
+        }
     }
 
     impl AdcConfig<ExternalTrigger12> {
@@ -1074,30 +1490,149 @@ pub mod config {
         }
     }
 
-    impl<ET: Default> Default for AdcConfig<ET> {
-        fn default() -> Self {
-            Self {
-                clock_mode: ClockMode::Synchronous_Div_1,
-                clock: Clock::Div_2,
-                resolution: Resolution::Twelve,
-                align: Align::Right,
-                external_trigger: (TriggerMode::Disabled, ET::default()),
-                continuous: Continuous::Single,
-                subgroup_len: SubGroupLength::One,
-                dma: Dma::Disabled,
-                end_of_conversion_interrupt: Eoc::Disabled,
-                overrun_interrupt: false,
-                default_sample_time: SampleTime::Cycles_640_5,
-                vdda: None,
-                difsel: DifferentialSelection::default(),
-                auto_delay: false,
-            }
-        }
+    /// Configuration for the injected group of the adc
+    #[derive(Debug, Clone, Copy)]
+    pub struct InjectedConfig<JT> {
+        pub(crate) external_trigger: (TriggerMode, JT),
+        pub(crate) queue: InjectedQueue,
+        pub(crate) auto_injection: bool,
+        pub(crate) discontinuous: bool,
+        pub(crate) end_of_conversion_interrupt: Eoc,
     }
-}
 
-/// Type-State for Adc, indicating a deep-powered-down-pheripheral
-#[derive(Debug)]
+    impl<JT> InjectedConfig<JT> {
+        /// change the external_trigger field
+        #[inline(always)]
+        pub fn external_trigger(mut self, trigger_mode: TriggerMode, trigger: JT) -> Self {
+            self.external_trigger = (trigger_mode, trigger);
+            self
+        }
+
+        /// change the queue field
+        #[inline(always)]
+        pub fn queue(mut self, queue: InjectedQueue) -> Self {
+            self.queue = queue;
+            self
+        }
+
+        /// Enable/disable auto injection, the injected sequence is converted after every regular
+        /// sequence. The injected external trigger must be disabled for this.
+        #[inline(always)]
+        pub fn auto_injection(mut self, auto_injection: bool) -> Self {
+            self.auto_injection = auto_injection;
+            self
+        }
+
+        /// Enable/disable discontinuous mode, each trigger converts one rank of the injected
+        /// sequence
+        #[inline(always)]
+        pub fn discontinuous(mut self, discontinuous: bool) -> Self {
+            self.discontinuous = discontinuous;
+            self
+        }
+
+        /// change the end_of_conversion_interrupt field (JEOC/JEOS)
+        #[inline(always)]
+        pub fn end_of_conversion_interrupt(mut self, end_of_conversion_interrupt: Eoc) -> Self {
+            self.end_of_conversion_interrupt = end_of_conversion_interrupt;
+            self
+        }
+    }
+
+    impl<JT: Default> Default for InjectedConfig<JT> {
+        fn default() -> Self {
+            Self {
+                external_trigger: (TriggerMode::Disabled, JT::default()),
+                queue: InjectedQueue::Disabled,
+                auto_injection: false,
+                discontinuous: false,
+                end_of_conversion_interrupt: Eoc::Disabled,
+            }
+        }
+    }
+
+    /// Dual mode of a master and slave adc (DUAL)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DualMode {
+        /// Both adcs convert independently
+        Independent = 0b00000,
+        /// Combined regular simultaneous and injected simultaneous mode
+        RegularSimultaneousInjectedSimultaneous = 0b00001,
+        /// Combined regular simultaneous and alternate trigger mode
+        RegularSimultaneousAlternateTrigger = 0b00010,
+        /// Combined interleaved and injected simultaneous mode
+        InterleavedInjectedSimultaneous = 0b00011,
+        /// Injected simultaneous mode only
+        InjectedSimultaneous = 0b00101,
+        /// Regular simultaneous mode only, both adcs sample their regular sequence at the same time
+        RegularSimultaneous = 0b00110,
+        /// Interleaved mode only, the slave samples `delay` cycles after the master
+        Interleaved = 0b00111,
+        /// Alternate trigger mode only, injected triggers alternate between master and slave
+        AlternateTrigger = 0b01001,
+    }
+
+    /// Configuration for the common part of an adc pair in dual mode
+    #[derive(Debug, Clone, Copy)]
+    pub struct DualConfig {
+        pub(crate) mode: DualMode,
+        pub(crate) delay: u8,
+    }
+
+    impl DualConfig {
+        /// change the mode field
+        #[inline(always)]
+        pub fn mode(mut self, mode: DualMode) -> Self {
+            self.mode = mode;
+            self
+        }
+
+        /// Delay between the sampling phases of the master and the slave in interleaved mode,
+        /// in adc clock cycles minus one (DELAY). The maximum depends on the resolution, see the
+        /// reference manual.
+        #[inline(always)]
+        pub fn delay(mut self, delay: u8) -> Self {
+            assert!(delay < 16);
+            self.delay = delay;
+            self
+        }
+    }
+
+    impl Default for DualConfig {
+        fn default() -> Self {
+            Self {
+                mode: DualMode::RegularSimultaneous,
+                delay: 0,
+            }
+        }
+    }
+
+    impl<ET: Default> Default for AdcConfig<ET> {
+        fn default() -> Self {
+            Self {
+                clock_mode: ClockMode::Synchronous_Div_1,
+                clock: Clock::Div_2,
+                resolution: Resolution::Twelve,
+                align: Align::Right,
+                external_trigger: (TriggerMode::Disabled, ET::default()),
+                continuous: Continuous::Single,
+                subgroup_len: SubGroupLength::One,
+                dma: Dma::Disabled,
+                end_of_conversion_interrupt: Eoc::Disabled,
+                overrun_interrupt: false,
+                default_sample_time: SampleTime::Cycles_640_5,
+                vdda: None,
+                difsel: DifferentialSelection::default(),
+                auto_delay: false,
+                offsets: [None; 4],
+                gain_compensation: None,
+            }
+        }
+    }
+}
+
+/// Type-State for Adc, indicating a deep-powered-down-pheripheral
+#[derive(Debug)]
 pub struct PoweredDown;
 /// Type-State for Adc, indicating a non-configured peripheral
 #[derive(Debug)]
@@ -1193,8 +1728,6 @@ impl<ADC: TriggerType> Conversion<ADC> {
 /// to show which pins are available on certain device variants but currently the library doesn't enforce this.
 /// To fully support the right pins would require 10+ more features for the various variants.
 /// ## Todo
-/// * Injected conversions
-/// * Analog watchdog config
 /// * Discontinuous mode
 /// # Examples
 /// ## One-shot conversion
@@ -1310,6 +1843,37 @@ impl<ADC: TriggerType> Conversion<ADC> {
 ///     tim.bdtr.modify(|_, w| w.moe().set_bit());
 /// }
 /// ```
+///
+/// ## Injected conversions
+///
+/// The injected group has up to four ranks with their own data registers and trigger. Injected
+/// conversions interrupt a running regular sequence, which continues afterwards.
+/// ```
+/// let injected = InjectedConfig::default()
+///     .external_trigger(TriggerMode::RisingEdge, InjectedTrigger12::Tim_1_trgo_2)
+///     .end_of_conversion_interrupt(Eoc::Sequence);
+/// adc.set_injected_config(injected);
+/// adc.configure_injected_channel(&pa0, InjectedSequence::One, SampleTime::Cycles_6_5);
+/// adc.configure_injected_channel(&pa1, InjectedSequence::Two, SampleTime::Cycles_6_5);
+/// adc.start_injected_conversion();
+///
+/// // In the ADC1_2 interrupt
+/// let phase_a = adc.injected_sample(InjectedSequence::One);
+/// let phase_b = adc.injected_sample(InjectedSequence::Two);
+/// adc.clear_injected_end_of_conversion_flag();
+/// ```
+///
+/// ## Offset and gain compensation
+///
+/// Up to four channels can have an offset removed in hardware, gain compensation applies to all
+/// channels. Both are applied before the sample is stored in the data register.
+/// ```
+/// let config = AdcConfig::default()
+///     // Remove the 1.65V bias of the current sense amplifier, with signed results
+///     .offset(Offset::One, Some(OffsetConfig::new::<PA0<Analog>, ADC1>(2048)))
+///     // Multiply by 1.25
+///     .gain_compensation(Some(5120));
+/// ```
 #[derive(Clone, Copy)]
 pub struct DynamicAdc<ADC: TriggerType> {
     /// Current config of the ADC, kept up to date by the various set methods
@@ -1318,6 +1882,10 @@ pub struct DynamicAdc<ADC: TriggerType> {
     adc_reg: ADC,
     /// VDDA in millivolts calculated from the factory calibration and vrefint
     calibrated_vdda: u32,
+    /// Current config of the injected group
+    injected_config: config::InjectedConfig<ADC::InjectedTrigger>,
+    /// Channels of the injected ranks and the number of ranks, written to JSQR together with the trigger
+    injected_sequence: ([u8; 4], u8),
 }
 impl<ADC: TriggerType> fmt::Debug for DynamicAdc<ADC> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -1347,6 +1915,58 @@ where
     }
 }
 
+/// Typestate wrapper around a master and slave adc pair running in dual mode.
+///
+/// Only ADC1/ADC2 and ADC3/ADC4 can be combined. Both adcs are configured separately before being
+/// combined, conversions are started through the master. The triggers of the slave are not used.
+///
+/// In DMA mode the samples of both adcs are transferred together from the common data register
+/// (CDR), see [`split_dual_sample`].
+/// ```
+/// let adc1 = adc1.claim(ClockSource::SystemClock, &rcc, &mut delay, true);
+/// let adc2 = adc2.claim(ClockSource::SystemClock, &rcc, &mut delay, true);
+/// // configure the sequence and trigger of adc1, the sequence of adc2 ...
+/// let dual = DualAdc::new(adc1, adc2, DualConfig::default().mode(DualMode::RegularSimultaneous));
+/// let dual = dual.enable_dma(Dma::Continuous);
+///
+/// let mut transfer = stream.into_circ_peripheral_to_memory_transfer(dual, buffer, dma_config);
+/// transfer.start(|dual| dual.start_conversion());
+///
+/// let mut data = [0u32; 4];
+/// transfer.read_exact(&mut data);
+/// let (voltage, current) = split_dual_sample(data[0], Resolution::Twelve);
+/// ```
+pub struct DualAdc<MASTER: TriggerType, SLAVE: TriggerType, STATUS> {
+    master: DynamicAdc<MASTER>,
+    slave: DynamicAdc<SLAVE>,
+    _status: PhantomData<STATUS>,
+}
+impl<MASTER: TriggerType, SLAVE: TriggerType, STATUS> fmt::Debug for DualAdc<MASTER, SLAVE, STATUS>
+where
+    STATUS: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DualAdc<{:?}>: {{ master: {:?}, slave: {:?} }}",
+            self._status, self.master, self.slave
+        )
+    }
+}
+
+/// Splits a word of the common data register, as transferred by the DMA of a [`DualAdc`], into the
+/// master and slave samples.
+///
+/// With 12 and 10-bit resolution the slave sample is in the upper halfword, with 8 and 6-bit
+/// resolution both samples are packed into the lower halfword, the slave sample in the upper byte.
+#[inline(always)]
+pub fn split_dual_sample(data: u32, resolution: config::Resolution) -> (u16, u16) {
+    match resolution {
+        config::Resolution::Twelve | config::Resolution::Ten => (data as u16, (data >> 16) as u16),
+        config::Resolution::Eight | config::Resolution::Six => ((data & 0xff) as u16, ((data >> 8) & 0xff) as u16),
+    }
+}
+
 /// ADC Clock Source selection
 #[derive(Debug, Clone, Copy)]
 pub enum ClockSource {
@@ -1396,6 +2016,31 @@ trait AdcConfig {
 pub trait TriggerType {
     /// Specifies what External trigger type the ADC uses
     type ExternalTrigger: fmt::Debug;
+    /// Specifies what External trigger type the ADC uses for injected conversions
+    type InjectedTrigger: fmt::Debug;
+}
+
+/// Advanced control timers whose external trigger input (ETR) can be connected to the analog watchdogs of `ADC`
+pub trait WatchdogEtr<ADC> {
+    /// Selects the analog watchdog of `ADC` as ETR input of the timer
+    fn select_watchdog(watchdog: config::AnalogWatchdog);
+}
+
+macro_rules! watchdog_etr {
+    ($($TIMX:ident => ($adc_type:ident, $etrsel:expr)),+ $(,)*) => {
+        $(
+            impl WatchdogEtr<stm32::$adc_type> for stm32::$TIMX {
+                #[inline(always)]
+                fn select_watchdog(watchdog: config::AnalogWatchdog) {
+                    // The timer may be owned by another driver, only the ETR selection is touched
+                    unsafe {
+                        let tim = &(*stm32::$TIMX::ptr());
+                        tim.af1.modify(|_, w| w.etrsel().bits($etrsel + watchdog as u8));
+                    }
+                }
+            }
+        )+
+    };
 }
 
 #[inline(always)]
@@ -1545,10 +2190,212 @@ macro_rules! adc {
     (additionals: $adc_type:ident => ($common_type:ident)) => {
     };
 
-    ($($adc_type:ident => ($trigger_type:ident, $configure_clocks_fn_name:ident, $mux:expr, ($common_type:ident) )),+ $(,)*) => {
+    (injected_config => ($adc_type:ident, $injected_trigger_type:ident)) => {
+        /// Applies the configuration of the injected group.
+        #[inline(always)]
+        pub fn set_injected_config(&mut self, config: config::InjectedConfig<$injected_trigger_type>) {
+            self.adc.set_injected_config(config)
+        }
+
+        /// Sets which external trigger to use for injected conversions and if it is disabled, rising, falling or both
+        #[inline(always)]
+        pub fn set_injected_external_trigger(&mut self, (edge, jextsel): (config::TriggerMode, $injected_trigger_type)) {
+            self.adc.set_injected_external_trigger((edge, jextsel))
+        }
+
+        /// Resets the injected sequence to one rank
+        #[inline(always)]
+        pub fn reset_injected_sequence(&mut self) {
+            self.adc.reset_injected_sequence()
+        }
+
+        /// Configure a channel for injected sampling.
+        /// It will make sure the injected sequence is at least as long as the `sequence` provided.
+        /// # Arguments
+        /// * `channel` - channel to configure
+        /// * `sequence` - where in the injected sequence to sample the channel
+        /// * `sample_time` - how long to sample for. This is shared with regular conversions of the channel
+        #[inline(always)]
+        pub fn configure_injected_channel<CHANNEL>(&mut self, channel: &CHANNEL, sequence: config::InjectedSequence, sample_time: config::SampleTime)
+        where
+            CHANNEL: crate::hal_api_custom::adc::Channel<stm32::$adc_type, ID=u8>
+        {
+            self.adc.configure_injected_channel(channel, sequence, sample_time)
+        }
+
+        /// Sets an offset that is subtracted from the conversions of the channel in the injected rank.
+        /// See [DynamicAdc::set_injected_offset].
+        #[inline(always)]
+        pub fn set_injected_offset(&mut self, sequence: config::InjectedSequence, offset: u16) {
+            self.adc.set_injected_offset(sequence, offset)
+        }
+
+        /// Removes the offset of the injected rank
+        #[inline(always)]
+        pub fn clear_injected_offset(&mut self, sequence: config::InjectedSequence) {
+            self.adc.clear_injected_offset(sequence)
+        }
+    };
+
+    (offset_config => ($adc_type:ident)) => {
+        /// Sets the offset correction of an offset register, `None` disables it.
+        /// See [DynamicAdc::set_offset].
+        #[inline(always)]
+        pub fn set_offset(&mut self, offset: config::Offset, config: Option<config::OffsetConfig>) {
+            self.adc.set_offset(offset, config)
+        }
+
+        /// Sets the gain compensation coefficient, `None` disables gain compensation.
+        /// The conversions are multiplied by `coefficient / 4096`.
+        #[inline(always)]
+        pub fn set_gain_compensation(&mut self, coefficient: Option<u16>) {
+            self.adc.set_gain_compensation(coefficient)
+        }
+    };
+
+    (watchdog_config => ($adc_type:ident)) => {
+        /// Lets the analog watchdog guard a channel, for regular and injected conversions.
+        /// Analog watchdog 1 guards only this channel, for 2 and 3 the channel is added to the guarded channels.
+        #[inline(always)]
+        pub fn enable_watchdog_channel<CHANNEL>(&mut self, watchdog: config::AnalogWatchdog, channel: &CHANNEL)
+        where
+            CHANNEL: crate::hal_api_custom::adc::Channel<stm32::$adc_type, ID=u8>
+        {
+            self.adc.enable_watchdog_channel(watchdog, channel)
+        }
+
+        /// Lets the analog watchdog guard all channels, for regular and injected conversions.
+        #[inline(always)]
+        pub fn enable_watchdog_all_channels(&mut self, watchdog: config::AnalogWatchdog) {
+            self.adc.enable_watchdog_all_channels(watchdog)
+        }
+
+        /// Sets the channels guarded by analog watchdog 2 or 3.
+        /// Panics for analog watchdog 1, which guards either one or all channels.
+        #[inline(always)]
+        pub fn set_watchdog_channels(&mut self, watchdog: config::AnalogWatchdog, channels: config::WatchdogChannels) {
+            self.adc.set_watchdog_channels(watchdog, channels)
+        }
+
+        /// Stops the analog watchdog from guarding any channel
+        #[inline(always)]
+        pub fn disable_watchdog(&mut self, watchdog: config::AnalogWatchdog) {
+            self.adc.disable_watchdog(watchdog)
+        }
+
+        /// Sets the thresholds of the analog watchdog in samples at the configured resolution.
+        /// See [DynamicAdc::set_watchdog_thresholds].
+        #[inline(always)]
+        pub fn set_watchdog_thresholds(&mut self, watchdog: config::AnalogWatchdog, low: u16, high: u16) {
+            self.adc.set_watchdog_thresholds(watchdog, low, high)
+        }
+
+        /// Sets the thresholds of the analog watchdog in millivolts, using the calibrated VDDA.
+        #[inline(always)]
+        pub fn set_watchdog_thresholds_millivolts(&mut self, watchdog: config::AnalogWatchdog, low: u16, high: u16) {
+            self.adc.set_watchdog_thresholds_millivolts(watchdog, low, high)
+        }
+
+        /// Enable/disable the analog watchdog interrupt
+        #[inline(always)]
+        pub fn set_watchdog_interrupt(&mut self, watchdog: config::AnalogWatchdog, enable: bool) {
+            self.adc.set_watchdog_interrupt(watchdog, enable)
+        }
+
+        /// Connects the output of the analog watchdog to the external trigger input (ETR) of an advanced
+        /// control timer. See [DynamicAdc::connect_watchdog_to_etr].
+        #[inline(always)]
+        pub fn connect_watchdog_to_etr<TIM>(&mut self, watchdog: config::AnalogWatchdog)
+        where
+            TIM: WatchdogEtr<stm32::$adc_type>
+        {
+            self.adc.connect_watchdog_to_etr::<TIM>(watchdog)
+        }
+    };
+
+    (watchdog_flags => ($adc_type:ident)) => {
+        /// Read the analog watchdog flag
+        #[inline(always)]
+        pub fn get_watchdog_flag(&self, watchdog: config::AnalogWatchdog) -> bool {
+            self.adc.get_watchdog_flag(watchdog)
+        }
+
+        /// Resets the analog watchdog flag
+        #[inline(always)]
+        pub fn clear_watchdog_flag(&mut self, watchdog: config::AnalogWatchdog) {
+            self.adc.clear_watchdog_flag(watchdog)
+        }
+    };
+
+    (injected_conversion => ($adc_type:ident)) => {
+        /// Writes the injected sequence and external trigger to the ADC (JSQR).
+        /// With the queue enabled this queues a new context.
+        #[inline(always)]
+        pub fn load_injected_sequence(&mut self) {
+            self.adc.load_injected_sequence()
+        }
+
+        /// Starts the injected conversion sequence, or enables the injected external trigger
+        #[inline(always)]
+        pub fn start_injected_conversion(&mut self) {
+            self.adc.clear_injected_end_of_conversion_flag();
+            self.adc.start_injected_conversion()
+        }
+
+        /// Cancels an ongoing injected conversion and disables the injected external trigger
+        #[inline(always)]
+        pub fn cancel_injected_conversion(&mut self) {
+            self.adc.cancel_injected_conversion()
+        }
+
+        /// Block until the injected sequence is converted
+        #[inline(always)]
+        pub fn wait_for_injected_sequence(&mut self) {
+            self.adc.wait_for_injected_sequence()
+        }
+
+        /// Returns true when the injected sequence has been completed (JEOS)
+        #[inline(always)]
+        pub fn is_injected_sequence_done(&self) -> bool {
+            self.adc.is_injected_sequence_done()
+        }
+
+        /// Returns true when an injected conversion has been completed (JEOC)
+        #[inline(always)]
+        pub fn is_injected_conversion_done(&self) -> bool {
+            self.adc.is_injected_conversion_done()
+        }
+
+        /// Resets the injected end-of-conversion and end-of-sequence flags
+        #[inline(always)]
+        pub fn clear_injected_end_of_conversion_flag(&mut self) {
+            self.adc.clear_injected_end_of_conversion_flag()
+        }
+
+        /// Returns the sample of the injected rank
+        #[inline(always)]
+        pub fn injected_sample(&self, sequence: config::InjectedSequence) -> u16 {
+            self.adc.injected_sample(sequence)
+        }
+
+        /// Read the injected queue overflow flag
+        #[inline(always)]
+        pub fn get_injected_queue_overflow_flag(&self) -> bool {
+            self.adc.get_injected_queue_overflow_flag()
+        }
+
+        /// Resets the injected queue overflow flag
+        #[inline(always)]
+        pub fn clear_injected_queue_overflow_flag(&mut self) {
+            self.adc.clear_injected_queue_overflow_flag()
+        }
+    };
+
+    ($($adc_type:ident => ($trigger_type:ident, $injected_trigger_type:ident, $configure_clocks_fn_name:ident, $mux:expr, ($common_type:ident) )),+ $(,)*) => {
         $(
             impl TriggerType for stm32::$adc_type {
                 type ExternalTrigger = $trigger_type;
+                type InjectedTrigger = $injected_trigger_type;
             }
 
             impl AdcConfig for stm32::$adc_type {
@@ -1565,6 +2412,12 @@ macro_rules! adc {
                     Vref::sample_to_millivolts_ext(sample, self.calibrated_vdda, self.config.resolution)
                 }
 
+                /// Converts millivolts to a sample value using calibrated VDDA and configured resolution
+                #[inline(always)]
+                pub fn millivolts_to_sample(&self, millivolts: u16) -> u16 {
+                    Vref::millivolts_to_sample_ext(millivolts, self.calibrated_vdda, self.config.resolution)
+                }
+
                 /// Disables the Voltage Regulator and release the ADC
                 #[inline(always)]
                 pub fn release(mut self) -> stm32::$adc_type {
@@ -1685,6 +2538,10 @@ macro_rules! adc {
                     self.set_default_sample_time(config.default_sample_time);
                     self.set_channel_input_type(config.difsel);
                     self.set_auto_delay(config.auto_delay);
+                    for (i, offset) in [config::Offset::One, config::Offset::Two, config::Offset::Three, config::Offset::Four].into_iter().enumerate() {
+                        self.set_offset(offset, config.offsets[i]);
+                    }
+                    self.set_gain_compensation(config.gain_compensation);
 
                     if let Some(vdda) = config.vdda {
                         self.calibrated_vdda = vdda;
@@ -1751,6 +2608,38 @@ macro_rules! adc {
                     self.adc_reg.cfgr.modify(|_, w| w.autdly().bit(delay) );
                 }
 
+                /// Sets the offset correction of an offset register, `None` disables it.
+                /// The offset applies to regular and injected conversions of the channel.
+                ///
+                /// NOTE: The software is allowed to write these bits only when ADSTART = 0 and JADSTART = 0
+                pub fn set_offset(&mut self, offset: config::Offset, config: Option<config::OffsetConfig>) {
+                    self.config.offsets[offset as usize] = config;
+                    let (en, channel, value, positive, saturation) = match config {
+                        Some(c) => (true, c.channel, c.offset, c.sign == config::OffsetSign::Positive, c.saturation),
+                        None => (false, 0, 0, false, false),
+                    };
+                    match offset {
+                        config::Offset::One => self.adc_reg.ofr1.write(|w| w.offset1_en().bit(en).offset1_ch().bits(channel).offset1().bits(value).offsetpos().bit(positive).saten().bit(saturation)),
+                        config::Offset::Two => self.adc_reg.ofr2.write(|w| w.offset2_en().bit(en).offset2_ch().bits(channel).offset2().bits(value).offsetpos().bit(positive).saten().bit(saturation)),
+                        config::Offset::Three => self.adc_reg.ofr3.write(|w| w.offset3_en().bit(en).offset3_ch().bits(channel).offset3().bits(value).offsetpos().bit(positive).saten().bit(saturation)),
+                        config::Offset::Four => self.adc_reg.ofr4.write(|w| w.offset4_en().bit(en).offset4_ch().bits(channel).offset4().bits(value).offsetpos().bit(positive).saten().bit(saturation)),
+                    }
+                }
+
+                /// Sets the gain compensation coefficient, `None` disables gain compensation.
+                /// The conversions are multiplied by `coefficient / 4096`.
+                ///
+                /// NOTE: The software is allowed to write these bits only when ADSTART = 0 and JADSTART = 0
+                pub fn set_gain_compensation(&mut self, coefficient: Option<u16>) {
+                    self.config.gain_compensation = coefficient;
+                    if let Some(coefficient) = coefficient {
+                        assert!(coefficient < 0x4000);
+                        // This is sound, as the coefficient is checked to fit the 14 bit field
+                        self.adc_reg.gcomp.write(|w| unsafe { w.gcompcoeff().bits(coefficient) });
+                    }
+                    self.adc_reg.cfgr2.modify(|_, w| w.gcomp().bit(coefficient.is_some()));
+                }
+
                 /// Enables and disables dis-/continuous mode
                 #[inline(always)]
                 pub fn set_continuous(&mut self, continuous: config::Continuous) {
@@ -1932,6 +2821,12 @@ macro_rules! adc {
                     }
 
                     //Set the sample time for the channel
+                    self.set_sample_time(channel, sample_time);
+                }
+
+                /// Sets the sample time of a channel
+                #[inline(always)]
+                fn set_sample_time(&mut self, channel: u8, sample_time: config::SampleTime) {
                     let st = u8::from(sample_time);
                     match channel {
                         0 => self.adc_reg.smpr1.modify(|_, w| w.smp0().bits(st) ),
@@ -2104,31 +2999,328 @@ macro_rules! adc {
                 pub fn clear_overrun_flag(&mut self) {
                     self.adc_reg.isr.modify(|_, w| w.ovr().set_bit());
                 }
-            }
 
-            //TODO: claim now configures the clock for all ADCs in the group (12 and 345).
-            //Ideally we should make this a function of the common group and claim all adc in that group at once.
-            //The situation now is that the clock source setting can change between claims, changing the existing
-            //setting of the allready claimed ADC.
-            impl AdcClaim<stm32::$adc_type> for stm32::$adc_type {
-                /// Enables the ADC clock, resets the peripheral (optionally), runs calibration and applies the supplied config
-                /// # Arguments
-                /// * `reset` - should a reset be performed. This is provided because on some devices multiple ADCs share the same common reset
-                /// TODO: fix needing SYST
-                #[inline(always)]
-                fn claim(self, cs: ClockSource, rcc: &Rcc, delay: &mut impl hal_api_old::blocking::delay::DelayUs<u8>, reset: bool) -> Adc<stm32::$adc_type, Disabled> {
-                    unsafe {
-                        let rcc_ptr = &(*stm32::RCC::ptr());
-                        stm32::$adc_type::enable(rcc_ptr);
-                        if reset {stm32::$adc_type::reset(rcc_ptr);}
-                    }
-                    Self::configure_clock_source(cs, rcc);
+                /// Applies the configuration of the injected group.
+                ///
+                /// NOTE: The software is allowed to write these bits only when ADSTART = 0 and JADSTART = 0
+                pub fn set_injected_config(&mut self, config: config::InjectedConfig<$injected_trigger_type>) {
+                    self.injected_config = config;
 
-                    let dynadc = DynamicAdc {
-                        config: config::AdcConfig::default(),
-                        adc_reg: self,
-                        calibrated_vdda: VDDA_CALIB,
-                    };
+                    self.adc_reg.cfgr.modify(|_, w| w
+                        .jqdis().bit(config.queue == config::InjectedQueue::Disabled)
+                        .jqm().bit(config.queue == config::InjectedQueue::EmptyQueue)
+                        .jauto().bit(config.auto_injection)
+                        .jdiscen().bit(config.discontinuous)
+                    );
+
+                    let (en, eocs) = match config.end_of_conversion_interrupt {
+                        config::Eoc::Disabled => (false, false),
+                        config::Eoc::Conversion => (true, true),
+                        config::Eoc::Sequence => (true, false),
+                    };
+                    self.adc_reg.ier.modify(|_, w| w
+                        .jeosie().bit(en && !eocs)
+                        .jeocie().bit(eocs)
+                    );
+
+                    if config.queue == config::InjectedQueue::Disabled {
+                        self.load_injected_sequence();
+                    }
+                }
+
+                /// Sets which external trigger to use for injected conversions and if it is disabled, rising, falling or both
+                #[inline(always)]
+                pub fn set_injected_external_trigger(&mut self, (edge, jextsel): (config::TriggerMode, $injected_trigger_type)) {
+                    self.injected_config.external_trigger = (edge, jextsel);
+                    if self.injected_config.queue == config::InjectedQueue::Disabled {
+                        self.load_injected_sequence();
+                    }
+                }
+
+                /// Resets the injected sequence to one rank
+                #[inline(always)]
+                pub fn reset_injected_sequence(&mut self) {
+                    self.injected_sequence = ([0; 4], 1);
+                    if self.injected_config.queue == config::InjectedQueue::Disabled {
+                        self.load_injected_sequence();
+                    }
+                }
+
+                /// Configure a channel for injected sampling.
+                /// It will make sure the injected sequence is at least as long as the `sequence` provided.
+                ///
+                /// With the queue disabled the injected sequence is updated immediately, which is only allowed
+                /// while no injected conversion is ongoing. With the queue enabled the new sequence is queued by
+                /// [load_injected_sequence](#method.load_injected_sequence).
+                /// # Arguments
+                /// * `channel` - channel to configure
+                /// * `sequence` - where in the injected sequence to sample the channel
+                /// * `sample_time` - how long to sample for. This is shared with regular conversions of the channel
+                pub fn configure_injected_channel<CHANNEL>(&mut self, _channel: &CHANNEL, sequence: config::InjectedSequence, sample_time: config::SampleTime)
+                where
+                    CHANNEL: crate::hal_api_custom::adc::Channel<stm32::$adc_type, ID=u8>
+                {
+                    let channel = CHANNEL::channel();
+                    let rank = u8::from(sequence);
+
+                    let (ref mut channels, ref mut length) = self.injected_sequence;
+                    channels[rank as usize] = channel;
+                    *length = (*length).max(rank + 1);
+
+                    self.set_sample_time(channel, sample_time);
+
+                    if self.injected_config.queue == config::InjectedQueue::Disabled {
+                        self.load_injected_sequence();
+                    }
+                }
+
+                /// Writes the injected sequence and external trigger to the ADC (JSQR).
+                /// With the queue enabled this queues a new context, otherwise this is done by the configure methods.
+                pub fn load_injected_sequence(&mut self) {
+                    let (channels, length) = self.injected_sequence;
+                    let (edge, jextsel) = self.injected_config.external_trigger;
+                    self.adc_reg.jsqr.write(|w| unsafe { w
+                        .jl().bits(length - 1)
+                        .jextsel().bits(jextsel.into())
+                        .jexten().bits(edge.into())
+                        .jsq1().bits(channels[0])
+                        .jsq2().bits(channels[1])
+                        .jsq3().bits(channels[2])
+                        .jsq4().bits(channels[3])
+                    });
+                }
+
+                /// Returns the current injected sequence length.
+                #[inline(always)]
+                pub fn injected_sequence_length(&self) -> u8 {
+                    self.adc_reg.jsqr.read().jl().bits() + 1
+                }
+
+                /// Sets an offset that is subtracted from the conversions of the channel in the injected rank,
+                /// the result in the data register is signed.
+                ///
+                /// Offset register y is used for rank y, the offset also applies to regular conversions of that channel.
+                /// Configure the channel of the rank before setting the offset.
+                pub fn set_injected_offset(&mut self, sequence: config::InjectedSequence, offset: u16) {
+                    let channel = self.injected_sequence.0[u8::from(sequence) as usize];
+                    self.set_offset(sequence.into(), Some(config::OffsetConfig::for_channel(channel, offset)));
+                }
+
+                /// Removes the offset of the injected rank
+                pub fn clear_injected_offset(&mut self, sequence: config::InjectedSequence) {
+                    self.set_offset(sequence.into(), None);
+                }
+
+                /// Starts the injected conversion sequence, or enables the injected external trigger
+                #[inline(always)]
+                pub fn start_injected_conversion(&mut self) {
+                    self.adc_reg.cr.modify(|_, w| w.jadstart().set_bit());
+                }
+
+                /// Cancels an ongoing injected conversion and disables the injected external trigger
+                #[inline(always)]
+                pub fn cancel_injected_conversion(&mut self) {
+                    self.adc_reg.cr.modify(|_, w| w.jadstp().set_bit());
+                    while self.adc_reg.cr.read().jadstart().bit_is_set() {}
+                }
+
+                /// Returns if an injected conversion is active
+                #[inline(always)]
+                pub fn is_injected_conversion_active(&self) -> bool {
+                    self.adc_reg.cr.read().jadstart().bit_is_set()
+                }
+
+                /// Block until the injected sequence is converted
+                pub fn wait_for_injected_sequence(&mut self) {
+                    while !self.adc_reg.isr.read().jeos().bit_is_set() {}
+                }
+
+                /// Returns true when an injected conversion has been completed (JEOC)
+                #[inline(always)]
+                pub fn is_injected_conversion_done(&self) -> bool {
+                    self.adc_reg.isr.read().jeoc().bit_is_set()
+                }
+
+                /// Returns true when the injected sequence has been completed (JEOS)
+                #[inline(always)]
+                pub fn is_injected_sequence_done(&self) -> bool {
+                    self.adc_reg.isr.read().jeos().bit_is_set()
+                }
+
+                /// Resets the injected end-of-conversion and end-of-sequence flags
+                #[inline(always)]
+                pub fn clear_injected_end_of_conversion_flag(&mut self) {
+                    self.adc_reg.isr.write(|w| w.jeoc().set_bit().jeos().set_bit());
+                }
+
+                /// Returns the sample of the injected rank
+                #[inline(always)]
+                pub fn injected_sample(&self, sequence: config::InjectedSequence) -> u16 {
+                    match sequence {
+                        config::InjectedSequence::One => self.adc_reg.jdr1.read().jdata().bits(),
+                        config::InjectedSequence::Two => self.adc_reg.jdr2.read().jdata().bits(),
+                        config::InjectedSequence::Three => self.adc_reg.jdr3.read().jdata().bits(),
+                        config::InjectedSequence::Four => self.adc_reg.jdr4.read().jdata().bits(),
+                    }
+                }
+
+                /// Read the injected queue overflow flag
+                #[inline(always)]
+                pub fn get_injected_queue_overflow_flag(&self) -> bool {
+                    self.adc_reg.isr.read().jqovf().bit()
+                }
+
+                /// Resets the injected queue overflow flag
+                #[inline(always)]
+                pub fn clear_injected_queue_overflow_flag(&mut self) {
+                    self.adc_reg.isr.write(|w| w.jqovf().set_bit());
+                }
+
+                /// Lets the analog watchdog guard a channel, for regular and injected conversions.
+                /// Analog watchdog 1 guards only this channel, for 2 and 3 the channel is added to the guarded channels.
+                ///
+                /// NOTE: The software is allowed to change the channels of watchdog 1 only when ADSTART = 0 and JADSTART = 0
+                pub fn enable_watchdog_channel<CHANNEL>(&mut self, watchdog: config::AnalogWatchdog, _channel: &CHANNEL)
+                where
+                    CHANNEL: crate::hal_api_custom::adc::Channel<stm32::$adc_type, ID=u8>
+                {
+                    let channel = CHANNEL::channel();
+                    match watchdog {
+                        config::AnalogWatchdog::One => self.adc_reg.cfgr.modify(|_, w| unsafe { w
+                            .awd1ch().bits(channel)
+                            .awd1sgl().set_bit()
+                            .awd1en().set_bit()
+                            .jawd1en().set_bit()
+                        }),
+                        config::AnalogWatchdog::Two => self.adc_reg.awd2cr.modify(|r, w| unsafe { w.bits(r.bits() | 1 << channel) }),
+                        config::AnalogWatchdog::Three => self.adc_reg.awd3cr.modify(|r, w| unsafe { w.bits(r.bits() | 1 << channel) }),
+                    }
+                }
+
+                /// Lets the analog watchdog guard all channels, for regular and injected conversions.
+                pub fn enable_watchdog_all_channels(&mut self, watchdog: config::AnalogWatchdog) {
+                    match watchdog {
+                        config::AnalogWatchdog::One => self.adc_reg.cfgr.modify(|_, w| w
+                            .awd1sgl().clear_bit()
+                            .awd1en().set_bit()
+                            .jawd1en().set_bit()
+                        ),
+                        config::AnalogWatchdog::Two | config::AnalogWatchdog::Three => {
+                            self.set_watchdog_channels(watchdog, config::WatchdogChannels::all())
+                        }
+                    }
+                }
+
+                /// Sets the channels guarded by analog watchdog 2 or 3.
+                /// Panics for analog watchdog 1, which guards either one or all channels.
+                pub fn set_watchdog_channels(&mut self, watchdog: config::AnalogWatchdog, channels: config::WatchdogChannels) {
+                    match watchdog {
+                        config::AnalogWatchdog::One => panic!("Analog watchdog 1 guards one or all channels"),
+                        config::AnalogWatchdog::Two => self.adc_reg.awd2cr.write(|w| unsafe { w.bits(channels.0) }),
+                        config::AnalogWatchdog::Three => self.adc_reg.awd3cr.write(|w| unsafe { w.bits(channels.0) }),
+                    }
+                }
+
+                /// Stops the analog watchdog from guarding any channel
+                pub fn disable_watchdog(&mut self, watchdog: config::AnalogWatchdog) {
+                    match watchdog {
+                        config::AnalogWatchdog::One => self.adc_reg.cfgr.modify(|_, w| w.awd1en().clear_bit().jawd1en().clear_bit()),
+                        config::AnalogWatchdog::Two => self.adc_reg.awd2cr.reset(),
+                        config::AnalogWatchdog::Three => self.adc_reg.awd3cr.reset(),
+                    }
+                }
+
+                /// Sets the thresholds of the analog watchdog in samples at the configured resolution.
+                /// The watchdog flag is set when a conversion is below `low` or above `high`.
+                ///
+                /// Analog watchdog 2 and 3 only compare the 8 most significant bits of a 12-bit sample.
+                pub fn set_watchdog_thresholds(&mut self, watchdog: config::AnalogWatchdog, low: u16, high: u16) {
+                    // The thresholds are compared with the 12-bit aligned conversion result
+                    let low = Temperature::to_12b(low, self.config.resolution);
+                    let high = Temperature::to_12b(high, self.config.resolution);
+                    match watchdog {
+                        config::AnalogWatchdog::One => self.adc_reg.tr1.modify(|_, w| w.lt1().bits(low).ht1().bits(high)),
+                        config::AnalogWatchdog::Two => self.adc_reg.tr2.write(|w| unsafe { w.lt2().bits((low >> 4) as u8).ht2().bits((high >> 4) as u8) }),
+                        config::AnalogWatchdog::Three => self.adc_reg.tr3.write(|w| unsafe { w.lt3().bits((low >> 4) as u8).ht3().bits((high >> 4) as u8) }),
+                    }
+                }
+
+                /// Sets the thresholds of the analog watchdog in millivolts, using the calibrated VDDA.
+                #[inline(always)]
+                pub fn set_watchdog_thresholds_millivolts(&mut self, watchdog: config::AnalogWatchdog, low: u16, high: u16) {
+                    let low = self.millivolts_to_sample(low);
+                    let high = self.millivolts_to_sample(high);
+                    self.set_watchdog_thresholds(watchdog, low, high);
+                }
+
+                /// Enable/disable the analog watchdog interrupt
+                pub fn set_watchdog_interrupt(&mut self, watchdog: config::AnalogWatchdog, enable: bool) {
+                    self.adc_reg.ier.modify(|_, w| match watchdog {
+                        config::AnalogWatchdog::One => w.awd1ie().bit(enable),
+                        config::AnalogWatchdog::Two => w.awd2ie().bit(enable),
+                        config::AnalogWatchdog::Three => w.awd3ie().bit(enable),
+                    });
+                }
+
+                /// Read the analog watchdog flag
+                #[inline(always)]
+                pub fn get_watchdog_flag(&self, watchdog: config::AnalogWatchdog) -> bool {
+                    let isr = self.adc_reg.isr.read();
+                    match watchdog {
+                        config::AnalogWatchdog::One => isr.awd1().bit(),
+                        config::AnalogWatchdog::Two => isr.awd2().bit(),
+                        config::AnalogWatchdog::Three => isr.awd3().bit(),
+                    }
+                }
+
+                /// Resets the analog watchdog flag
+                #[inline(always)]
+                pub fn clear_watchdog_flag(&mut self, watchdog: config::AnalogWatchdog) {
+                    self.adc_reg.isr.write(|w| match watchdog {
+                        config::AnalogWatchdog::One => w.awd1().set_bit(),
+                        config::AnalogWatchdog::Two => w.awd2().set_bit(),
+                        config::AnalogWatchdog::Three => w.awd3().set_bit(),
+                    });
+                }
+
+                /// Connects the output of the analog watchdog to the external trigger input (ETR) of an advanced
+                /// control timer, e.g. to clear the PWM outputs (OCxCE) on overcurrent.
+                ///
+                /// The analog watchdogs can not drive the break inputs of the timers directly.
+                #[inline(always)]
+                pub fn connect_watchdog_to_etr<TIM>(&mut self, watchdog: config::AnalogWatchdog)
+                where
+                    TIM: WatchdogEtr<stm32::$adc_type>
+                {
+                    TIM::select_watchdog(watchdog);
+                }
+            }
+
+            //TODO: claim now configures the clock for all ADCs in the group (12 and 345).
+            //Ideally we should make this a function of the common group and claim all adc in that group at once.
+            //The situation now is that the clock source setting can change between claims, changing the existing
+            //setting of the allready claimed ADC.
+            impl AdcClaim<stm32::$adc_type> for stm32::$adc_type {
+                /// Enables the ADC clock, resets the peripheral (optionally), runs calibration and applies the supplied config
+                /// # Arguments
+                /// * `reset` - should a reset be performed. This is provided because on some devices multiple ADCs share the same common reset
+                /// TODO: fix needing SYST
+                #[inline(always)]
+                fn claim(self, cs: ClockSource, rcc: &Rcc, delay: &mut impl hal_api_old::blocking::delay::DelayUs<u8>, reset: bool) -> Adc<stm32::$adc_type, Disabled> {
+                    unsafe {
+                        let rcc_ptr = &(*stm32::RCC::ptr());
+                        stm32::$adc_type::enable(rcc_ptr);
+                        if reset {stm32::$adc_type::reset(rcc_ptr);}
+                    }
+                    Self::configure_clock_source(cs, rcc);
+
+                    let dynadc = DynamicAdc {
+                        config: config::AdcConfig::default(),
+                        adc_reg: self,
+                        calibrated_vdda: VDDA_CALIB,
+                        injected_config: config::InjectedConfig::default(),
+                        injected_sequence: ([0; 4], 1),
+                    };
 
                     let adc: Adc::<stm32::$adc_type, PoweredDown> = Adc {
                         adc: dynadc,
@@ -2159,6 +3351,12 @@ macro_rules! adc {
                 pub fn sample_to_millivolts(&self, sample: u16) -> u16 {
                     self.adc.sample_to_millivolts(sample)
                 }
+
+                /// Converts millivolts to a sample value using calibrated VDDA and configured resolution
+                #[inline(always)]
+                pub fn millivolts_to_sample(&self, millivolts: u16) -> u16 {
+                    self.adc.millivolts_to_sample(millivolts)
+                }
             }
 
             impl Adc<stm32::$adc_type, PoweredDown> {
@@ -2235,6 +3433,9 @@ macro_rules! adc {
             impl Adc<stm32::$adc_type, Disabled> {
                 adc!(additionals: $adc_type => ($common_type));
                 adc!(additionals_checks: $adc_type => ($common_type));
+                adc!(injected_config => ($adc_type, $injected_trigger_type));
+                adc!(watchdog_config => ($adc_type));
+                adc!(offset_config => ($adc_type));
 
                 /// Enables the adc
                 #[inline(always)]
@@ -2421,6 +3622,11 @@ macro_rules! adc {
 
             impl Adc<stm32::$adc_type, Configured> {
                 adc!(additionals_checks: $adc_type => ($common_type));
+                adc!(injected_config => ($adc_type, $injected_trigger_type));
+                adc!(watchdog_config => ($adc_type));
+                adc!(offset_config => ($adc_type));
+                adc!(injected_conversion => ($adc_type));
+                adc!(watchdog_flags => ($adc_type));
 
                 /// Disables the adc
                 #[inline(always)]
@@ -2493,6 +3699,9 @@ macro_rules! adc {
             }
 
             impl Adc<stm32::$adc_type, Active> {
+                adc!(injected_conversion => ($adc_type));
+                adc!(watchdog_flags => ($adc_type));
+
                 /// Block until the conversion is completed and return to configured
                 pub fn wait_for_conversion_sequence(mut self) -> Conversion<stm32::$adc_type> {
                     self.adc.wait_for_conversion_sequence();
@@ -2543,6 +3752,9 @@ macro_rules! adc {
             }
 
             impl Adc<stm32::$adc_type, DMA> {
+                adc!(injected_conversion => ($adc_type));
+                adc!(watchdog_flags => ($adc_type));
+
                 /// Starts conversion sequence. Waits for the hardware to indicate it's actually started.
                 #[inline(always)]
                 pub fn start_conversion(&mut self) {
@@ -2622,7 +3834,7 @@ macro_rules! adc {
     feature = "stm32g491",
     feature = "stm32g4a1",
 ))]
-adc!(ADC1 => (ExternalTrigger12, configure_clock_source12, DmaMuxResources::ADC1, (ADC12_COMMON) ));
+adc!(ADC1 => (ExternalTrigger12, InjectedTrigger12, configure_clock_source12, DmaMuxResources::ADC1, (ADC12_COMMON) ));
 
 #[cfg(any(
     feature = "stm32g431",
@@ -2635,7 +3847,7 @@ adc!(ADC1 => (ExternalTrigger12, configure_clock_source12, DmaMuxResources::ADC1
     feature = "stm32g491",
     feature = "stm32g4a1",
 ))]
-adc!(ADC2 => (ExternalTrigger12, configure_clock_source12, DmaMuxResources::ADC2, (ADC12_COMMON) ));
+adc!(ADC2 => (ExternalTrigger12, InjectedTrigger12, configure_clock_source12, DmaMuxResources::ADC2, (ADC12_COMMON) ));
 
 #[cfg(any(
     feature = "stm32g471",
@@ -2646,7 +3858,7 @@ adc!(ADC2 => (ExternalTrigger12, configure_clock_source12, DmaMuxResources::ADC2
     feature = "stm32g491",
     feature = "stm32g4a1",
 ))]
-adc!(ADC3 => (ExternalTrigger345, configure_clock_source345, DmaMuxResources::ADC3, (ADC345_COMMON) ));
+adc!(ADC3 => (ExternalTrigger345, InjectedTrigger345, configure_clock_source345, DmaMuxResources::ADC3, (ADC345_COMMON) ));
 
 #[cfg(any(
     feature = "stm32g473",
@@ -2654,7 +3866,7 @@ adc!(ADC3 => (ExternalTrigger345, configure_clock_source345, DmaMuxResources::AD
     feature = "stm32g483",
     feature = "stm32g484",
 ))]
-adc!(ADC4 => (ExternalTrigger345, configure_clock_source345, DmaMuxResources::ADC4, (ADC345_COMMON) ));
+adc!(ADC4 => (ExternalTrigger345, InjectedTrigger345, configure_clock_source345, DmaMuxResources::ADC4, (ADC345_COMMON) ));
 
 #[cfg(any(
     feature = "stm32g473",
@@ -2662,7 +3874,304 @@ adc!(ADC4 => (ExternalTrigger345, configure_clock_source345, DmaMuxResources::AD
     feature = "stm32g483",
     feature = "stm32g484",
 ))]
-adc!(ADC5 => (ExternalTrigger345, configure_clock_source345, DmaMuxResources::ADC5, (ADC345_COMMON) ));
+adc!(ADC5 => (ExternalTrigger345, InjectedTrigger345, configure_clock_source345, DmaMuxResources::ADC5, (ADC345_COMMON) ));
+
+macro_rules! dual_adc {
+    (injected => ($master:ident, $slave:ident)) => {
+        /// Starts the injected conversion sequence of both adcs, or enables the injected external
+        /// trigger of the master
+        #[inline(always)]
+        pub fn start_injected_conversion(&mut self) {
+            self.master.clear_injected_end_of_conversion_flag();
+            self.slave.clear_injected_end_of_conversion_flag();
+            self.master.start_injected_conversion()
+        }
+
+        /// Cancels an ongoing injected conversion and disables the injected external trigger
+        #[inline(always)]
+        pub fn cancel_injected_conversion(&mut self) {
+            self.master.cancel_injected_conversion()
+        }
+
+        /// Returns true when the injected sequence of both adcs has been completed (JEOS)
+        #[inline(always)]
+        pub fn is_injected_sequence_done(&self) -> bool {
+            self.master.is_injected_sequence_done() && self.slave.is_injected_sequence_done()
+        }
+
+        /// Resets the injected end-of-conversion and end-of-sequence flags of both adcs
+        #[inline(always)]
+        pub fn clear_injected_end_of_conversion_flag(&mut self) {
+            self.master.clear_injected_end_of_conversion_flag();
+            self.slave.clear_injected_end_of_conversion_flag();
+        }
+
+        /// Returns the (master, slave) samples of the injected rank
+        #[inline(always)]
+        pub fn injected_samples(&self, sequence: config::InjectedSequence) -> (u16, u16) {
+            (self.master.injected_sample(sequence), self.slave.injected_sample(sequence))
+        }
+    };
+
+    ($($master:ident, $slave:ident => ($mux:expr, $common_type:ident)),+ $(,)*) => {
+        $(
+            impl DualAdc<stm32::$master, stm32::$slave, Disabled> {
+                /// Combines a master and slave adc and configures the dual mode
+                #[inline(always)]
+                pub fn new(master: Adc<stm32::$master, Disabled>, slave: Adc<stm32::$slave, Disabled>, config: config::DualConfig) -> Self {
+                    let mut dual = DualAdc {
+                        master: master.adc,
+                        slave: slave.adc,
+                        _status: PhantomData,
+                    };
+                    dual.set_dual_config(config);
+                    dual
+                }
+
+                /// Sets the dual mode and the delay between the master and slave
+                #[inline(always)]
+                pub fn set_dual_config(&mut self, config: config::DualConfig) {
+                    unsafe {
+                        let common = &(*stm32::$common_type::ptr());
+                        common.ccr.modify(|_, w| w
+                            .dual().bits(config.mode as u8)
+                            .delay().bits(config.delay)
+                        );
+                    }
+                }
+
+                /// Enables both adcs
+                #[inline(always)]
+                pub fn enable(mut self) -> DualAdc<stm32::$master, stm32::$slave, Configured> {
+                    self.slave.enable();
+                    self.master.enable();
+
+                    DualAdc {
+                        master: self.master,
+                        slave: self.slave,
+                        _status: PhantomData,
+                    }
+                }
+
+                /// Enables both adcs and the DMA of the common data register (MDMA).
+                /// The packing is selected from the resolution of the master.
+                /// panics if set to Dma::Disabled
+                #[inline(always)]
+                pub fn enable_dma(mut self, dma: config::Dma) -> DualAdc<stm32::$master, stm32::$slave, DMA> {
+                    let circular = match dma {
+                        config::Dma::Disabled => panic!("Requesting Enabling DMA with DisableDma parameter"),
+                        config::Dma::Single => false,
+                        config::Dma::Continuous => true,
+                    };
+                    let mdma = match self.master.config.resolution {
+                        config::Resolution::Twelve | config::Resolution::Ten => 0b10,
+                        config::Resolution::Eight | config::Resolution::Six => 0b11,
+                    };
+
+                    // The DMA requests of the adcs themselves are not used in dual mode
+                    self.master.set_dma(config::Dma::Disabled);
+                    self.slave.set_dma(config::Dma::Disabled);
+                    unsafe {
+                        let common = &(*stm32::$common_type::ptr());
+                        common.ccr.modify(|_, w| w
+                            .mdma().bits(mdma)
+                            .dmacfg().bit(circular)
+                        );
+                    }
+
+                    let dual = self.enable();
+                    DualAdc {
+                        master: dual.master,
+                        slave: dual.slave,
+                        _status: PhantomData,
+                    }
+                }
+
+                /// Puts both adcs back in independent mode and releases them
+                #[inline(always)]
+                pub fn release(self) -> (Adc<stm32::$master, Disabled>, Adc<stm32::$slave, Disabled>) {
+                    unsafe {
+                        let common = &(*stm32::$common_type::ptr());
+                        common.ccr.modify(|_, w| w
+                            .dual().bits(config::DualMode::Independent as u8)
+                            .mdma().bits(0)
+                        );
+                    }
+
+                    (
+                        Adc {
+                            adc: self.master,
+                            _status: PhantomData,
+                        },
+                        Adc {
+                            adc: self.slave,
+                            _status: PhantomData,
+                        },
+                    )
+                }
+            }
+
+            impl DualAdc<stm32::$master, stm32::$slave, Configured> {
+                dual_adc!(injected => ($master, $slave));
+
+                /// Disables both adcs
+                #[inline(always)]
+                pub fn disable(mut self) -> DualAdc<stm32::$master, stm32::$slave, Disabled> {
+                    self.master.disable();
+                    self.slave.disable();
+
+                    DualAdc {
+                        master: self.master,
+                        slave: self.slave,
+                        _status: PhantomData,
+                    }
+                }
+
+                /// Starts the conversion sequence of both adcs, or enables the external trigger of the master
+                #[inline(always)]
+                pub fn start_conversion(mut self) -> DualAdc<stm32::$master, stm32::$slave, Active> {
+                    self.master.clear_end_of_conversion_flag();
+                    self.slave.clear_end_of_conversion_flag();
+                    self.master.start_conversion();
+
+                    DualAdc {
+                        master: self.master,
+                        slave: self.slave,
+                        _status: PhantomData,
+                    }
+                }
+
+                /// Returns the current (master, slave) samples from the common data register
+                #[inline(always)]
+                pub fn current_samples(&self) -> (u16, u16) {
+                    let cdr = unsafe { (*stm32::$common_type::ptr()).cdr.read() };
+                    (cdr.rdata_mst().bits(), cdr.rdata_slv().bits())
+                }
+            }
+
+            impl DualAdc<stm32::$master, stm32::$slave, Active> {
+                dual_adc!(injected => ($master, $slave));
+
+                /// Block until both adcs have completed a conversion
+                #[inline(always)]
+                pub fn wait_for_conversion(&mut self) {
+                    self.master.wait_for_conversion_sequence();
+                    self.slave.wait_for_conversion_sequence();
+                }
+
+                /// Returns if the conversion sequence has been completed
+                #[inline(always)]
+                pub fn is_conversion_done(&self) -> bool {
+                    !self.master.is_conversion_active()
+                }
+
+                /// Cancels an ongoing conversion
+                #[inline(always)]
+                pub fn cancel_conversion(mut self) -> DualAdc<stm32::$master, stm32::$slave, Configured> {
+                    self.master.cancel_conversion();
+
+                    DualAdc {
+                        master: self.master,
+                        slave: self.slave,
+                        _status: PhantomData,
+                    }
+                }
+
+                /// Returns the current (master, slave) samples from the common data register
+                #[inline(always)]
+                pub fn current_samples(&self) -> (u16, u16) {
+                    let cdr = unsafe { (*stm32::$common_type::ptr()).cdr.read() };
+                    (cdr.rdata_mst().bits(), cdr.rdata_slv().bits())
+                }
+
+                /// Resets the end-of-conversion flag of both adcs
+                #[inline(always)]
+                pub fn clear_end_conversion_flag(&mut self) {
+                    self.master.clear_end_of_conversion_flag();
+                    self.slave.clear_end_of_conversion_flag();
+                }
+            }
+
+            impl DualAdc<stm32::$master, stm32::$slave, DMA> {
+                dual_adc!(injected => ($master, $slave));
+
+                /// Starts the conversion sequence of both adcs, or enables the external trigger of the master
+                #[inline(always)]
+                pub fn start_conversion(&mut self) {
+                    self.master.start_conversion()
+                }
+
+                /// Cancels an ongoing conversion
+                #[inline(always)]
+                pub fn cancel_conversion(&mut self) {
+                    self.master.cancel_conversion()
+                }
+
+                /// Disables both adcs and the DMA of the common data register
+                #[inline(always)]
+                pub fn disable(mut self) -> DualAdc<stm32::$master, stm32::$slave, Disabled> {
+                    unsafe {
+                        let common = &(*stm32::$common_type::ptr());
+                        common.ccr.modify(|_, w| w.mdma().bits(0).dmacfg().clear_bit());
+                    }
+                    self.master.disable();
+                    self.slave.disable();
+
+                    DualAdc {
+                        master: self.master,
+                        slave: self.slave,
+                        _status: PhantomData,
+                    }
+                }
+
+                /// Read the overrun flag of the master or slave
+                #[inline(always)]
+                pub fn get_overrun_flag(&self) -> bool {
+                    self.master.get_overrun_flag() || self.slave.get_overrun_flag()
+                }
+
+                /// Resets the overrun flag of both adcs
+                #[inline(always)]
+                pub fn clear_overrun_flag(&mut self) {
+                    self.master.clear_overrun_flag();
+                    self.slave.clear_overrun_flag();
+                }
+            }
+
+            unsafe impl TargetAddress<PeripheralToMemory> for DualAdc<stm32::$master, stm32::$slave, DMA> {
+                #[inline(always)]
+                fn address(&self) -> u32 {
+                    unsafe { &(*stm32::$common_type::ptr()).cdr as *const _ as u32 }
+                }
+
+                type MemSize = u32;
+
+                const REQUEST_LINE: Option<u8> = Some($mux as u8);
+            }
+        )+
+    };
+}
+
+#[cfg(any(
+    feature = "stm32g431",
+    feature = "stm32g441",
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484",
+    feature = "stm32g491",
+    feature = "stm32g4a1",
+))]
+dual_adc!(ADC1, ADC2 => (DmaMuxResources::ADC1, ADC12_COMMON));
+
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484",
+))]
+dual_adc!(ADC3, ADC4 => (DmaMuxResources::ADC3, ADC345_COMMON));
 
 #[cfg(any(feature = "stm32g431", feature = "stm32g441", feature = "stm32g471",))]
 adc_pins!(
@@ -2802,21 +4311,14 @@ adc_pins!(
 );
 
 // See https://www.st.com/resource/en/reference_manual/rm0440-stm32g4-series-advanced-armbased-32bit-mcus-stmicroelectronics.pdf#page=782
-adc_op_pga!(
-    // TODO: Add all opamp types: OpenLoop, Follower(for all opamps)
+// Only opamps with their output routed to `opamp::InternalOutput` can be sampled
+adc_opamp!(
     // TODO: Should we restrict type parameters A and B?
     // TODO: Also allow AD-channels shared by pins
-    opamp::opamp1::Pga<A, B> => (ADC1, 13),
-    opamp::opamp2::Pga<A, B> => (ADC2, 16),
-
-    opamp::opamp3::Pga<A, B> => (ADC2, 18),
-);
-
-adc_op_follower!(
-    opamp::opamp1::Follower<A> => (ADC1, 13),
-    opamp::opamp2::Follower<A> => (ADC2, 16),
+    opamp1 => (ADC1, 13),
+    opamp2 => (ADC2, 16),
 
-    opamp::opamp3::Follower<A> => (ADC2, 18),
+    opamp3 => (ADC2, 18),
 );
 
 #[cfg(any(
@@ -2827,16 +4329,16 @@ adc_op_follower!(
     feature = "stm32g491",
     feature = "stm32g4a1",
 ))]
-adc_op_pga!(
-    opamp::opamp3::Pga<A, B> => (ADC3, 13),
-    opamp::opamp4::Pga<A, B> => (ADC5, 5),
-    opamp::opamp5::Pga<A, B> => (ADC5, 3),
-    opamp::opamp6::Pga<A, B> => (ADC4, 17),
+adc_opamp!(
+    opamp3 => (ADC3, 13),
+    opamp4 => (ADC5, 5),
+    opamp5 => (ADC5, 3),
+    opamp6 => (ADC4, 17),
 );
 
 #[cfg(any(feature = "stm32g491", feature = "stm32g4a1",))]
-adc_op_pga!(
-    opamp::opamp6::Pga<A, B> => (ADC3, 17),
+adc_opamp!(
+    opamp6 => (ADC3, 17),
 );
 
 #[cfg(any(feature = "stm32g491", feature = "stm32g4a1",))]
@@ -2898,3 +4400,44 @@ adc_pins!(
     Vref => (ADC1, 18),
     Vref => (ADC3, 18),
 );
+
+watchdog_etr!(
+    TIM1 => (ADC1, 0b1000),
+    TIM8 => (ADC2, 0b1000),
+);
+
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484",
+    feature = "stm32g491",
+    feature = "stm32g4a1",
+))]
+watchdog_etr!(
+    TIM8 => (ADC3, 0b1011),
+);
+
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484",
+    feature = "stm32g491",
+    feature = "stm32g4a1",
+))]
+watchdog_etr!(
+    TIM20 => (ADC3, 0b1000),
+);
+
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484",
+))]
+watchdog_etr!(
+    TIM1 => (ADC4, 0b1011),
+    TIM20 => (ADC5, 0b1011),
+);
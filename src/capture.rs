@@ -0,0 +1,567 @@
+//! Input capture
+//!
+//! Input capture is available for the advanced control timers (`TIM1`, `TIM8`, `TIM20`), the
+//! general purpose timers `TIM[2-5]` and the channels of `TIM15` (1-2), `TIM16` and `TIM17` (1).
+//! The pins of a timer channel are the same pins that can be used for PWM output on that channel.
+//!
+//! ## Input capture
+//!
+//! [`InputCaptureExt::input_capture`] captures the value of the free running counter on the
+//! selected edges of one channel. The captures can be read as raw counter values, as timestamps
+//! that include the counter overflows or as the frequency of the input signal.
+//!
+//! ```ignore
+//!   let pin = gpioa.pa0.into_alternate();
+//!   let mut capture = dp.TIM2.input_capture(pin, capture::Config::default(), &mut rcc);
+//!
+//!   let frequency = nb::block!(capture.frequency()).unwrap();
+//! ```
+//!
+//! The captures can also be copied into a buffer by the DMA, see
+//! [`InputCapture::enable_dma`].
+//!
+//! ## PWM input
+//!
+//! [`PwmInputExt::pwm_input`] measures the period and duty cycle of a PWM signal on
+//! channel 1. It needs the slave mode controller to reset the counter, so it is not available
+//! on `TIM16` and `TIM17`. Channel 1 captures the period on the rising edge, channel 2 the high time on the
+//! falling edge and every rising edge resets the counter.
+//!
+//! ```ignore
+//!   let pin = gpioa.pa8.into_alternate();
+//!   let mut pwm_input = dp.TIM1.pwm_input(pin, 10.Hz(), &mut rcc);
+//!
+//!   if let Some(frequency) = pwm_input.frequency() {
+//!       let duty = pwm_input.duty_cycle();
+//!   }
+//! ```
+use core::marker::PhantomData;
+
+use crate::dma::{mux::DmaMuxResources, traits::TargetAddress, PeripheralToMemory};
+use crate::pwm::{Pins, C1, C2, C3, C4};
+use crate::rcc::{Enable, GetBusFreq, Rcc, Reset};
+use crate::stm32::RCC;
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+use crate::stm32::TIM20;
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+use crate::stm32::TIM5;
+use crate::stm32::{TIM1, TIM15, TIM16, TIM17, TIM2, TIM3, TIM4, TIM8};
+use crate::time::{Hertz, RateExtU32};
+
+/// Edge(s) of the input signal that trigger a capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureEdge {
+    Rising,
+    Falling,
+    Both,
+}
+
+/// Number of edges per capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputPrescaler {
+    Div1 = 0b00,
+    Div2 = 0b01,
+    Div4 = 0b10,
+    Div8 = 0b11,
+}
+
+impl InputPrescaler {
+    fn divisor(&self) -> u32 {
+        1 << (*self as u32)
+    }
+}
+
+/// Input capture configuration
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    edge: CaptureEdge,
+    prescaler: InputPrescaler,
+    filter: u8,
+    counter_frequency: Option<Hertz>,
+}
+
+impl Config {
+    /// Selects the edge(s) that trigger a capture.
+    pub fn edge(mut self, edge: CaptureEdge) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    /// Only captures every 2nd, 4th or 8th edge.
+    pub fn prescaler(mut self, prescaler: InputPrescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    /// Sets the digital input filter (ICxF), 0 disables the filter and 15 gives the strongest
+    /// filtering. See the reference manual for the sampling rates.
+    pub fn filter(mut self, filter: u8) -> Self {
+        assert!(filter < 16);
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the frequency of the counter, which is the resolution of the captures. The counter
+    /// runs at the timer clock by default.
+    pub fn counter_frequency(mut self, frequency: Hertz) -> Self {
+        self.counter_frequency = Some(frequency);
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            edge: CaptureEdge::Rising,
+            prescaler: InputPrescaler::Div1,
+            filter: 0,
+            counter_frequency: None,
+        }
+    }
+}
+
+/// Interrupt events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A value was captured
+    Capture,
+    /// The counter overflowed
+    Overflow,
+}
+
+/// Input capture error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A capture was overwritten before it was read
+    Overcapture,
+}
+
+/// Channel of a timer that can be used for input capture
+pub trait CaptureChannel {
+    /// Channel index, starting at 0 for channel 1
+    const INDEX: usize;
+}
+
+impl CaptureChannel for C1 {
+    const INDEX: usize = 0;
+}
+impl CaptureChannel for C2 {
+    const INDEX: usize = 1;
+}
+impl CaptureChannel for C3 {
+    const INDEX: usize = 2;
+}
+impl CaptureChannel for C4 {
+    const INDEX: usize = 3;
+}
+
+/// Input capture on one channel of a timer
+pub struct InputCapture<TIM, CHANNEL, PIN> {
+    tim: TIM,
+    pin: PIN,
+    _channel: PhantomData<CHANNEL>,
+    clk: Hertz,
+    edges_per_period: u32,
+    prescaler: u32,
+    overflows: u64,
+    last_timestamp: Option<u64>,
+}
+
+/// PWM input measurement on channel 1 of a timer
+pub struct PwmInput<TIM, PIN> {
+    tim: TIM,
+    pin: PIN,
+    clk: Hertz,
+    valid: bool,
+}
+
+pub trait InputCaptureExt: Sized {
+    /// Captures the counter on the edges of the input signal on `pin`.
+    fn input_capture<PIN, CHANNEL, COMP>(
+        self,
+        pin: PIN,
+        config: Config,
+        rcc: &mut Rcc,
+    ) -> InputCapture<Self, CHANNEL, PIN>
+    where
+        PIN: Pins<Self, CHANNEL, COMP>,
+        CHANNEL: CaptureChannel;
+}
+
+pub trait PwmInputExt: Sized {
+    /// Measures the period and duty cycle of the PWM signal on the channel 1 `pin`.
+    ///
+    /// The counter resolution is chosen to measure frequencies down to `min_frequency`, lower
+    /// frequencies are reported as a lost signal.
+    fn pwm_input<PIN, COMP>(
+        self,
+        pin: PIN,
+        min_frequency: Hertz,
+        rcc: &mut Rcc,
+    ) -> PwmInput<Self, PIN>
+    where
+        PIN: Pins<Self, C1, COMP>;
+}
+
+macro_rules! capture {
+    ($($TIMX:ident: ($typ:ty, [$($CH:ident: $mux:ident),+] $(, $ccmr2:ident)?),)+) => {
+        $(
+            impl InputCaptureExt for $TIMX {
+                fn input_capture<PIN, CHANNEL, COMP>(
+                    self,
+                    pin: PIN,
+                    config: Config,
+                    rcc: &mut Rcc,
+                ) -> InputCapture<Self, CHANNEL, PIN>
+                where
+                    PIN: Pins<Self, CHANNEL, COMP>,
+                    CHANNEL: CaptureChannel,
+                {
+                    unsafe {
+                        let rcc_ptr = &(*RCC::ptr());
+                        $TIMX::enable(rcc_ptr);
+                        $TIMX::reset(rcc_ptr);
+                    }
+
+                    let timer_clk = $TIMX::get_timer_frequency(&rcc.clocks);
+                    let psc = match config.counter_frequency {
+                        Some(freq) => (timer_clk.raw() / freq.raw()).clamp(1, 1 << 16) - 1,
+                        None => 0,
+                    };
+                    self.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+                    self.arr.write(|w| unsafe { w.bits(<$typ>::MAX as u32) });
+
+                    // Map ICx on TIx and set the input prescaler and filter
+                    let n = CHANNEL::INDEX;
+                    let shift = (n % 2) * 8;
+                    let ccmr = (0b01 | (config.prescaler as u32) << 2 | (config.filter as u32) << 4) << shift;
+                    let mask = !(0xff << shift);
+                    if n < 2 {
+                        self.ccmr1_input().modify(|r, w| unsafe { w.bits((r.bits() & mask) | ccmr) });
+                    } else {
+                        $(self.$ccmr2().modify(|r, w| unsafe { w.bits((r.bits() & mask) | ccmr) });)?
+                    }
+
+                    // Select the edge and enable the capture, CCxE is bit 0, CCxP bit 1 and CCxNP bit 3
+                    let (ccp, ccnp, edges_per_period) = match config.edge {
+                        CaptureEdge::Rising => (0, 0, 1),
+                        CaptureEdge::Falling => (1, 0, 1),
+                        CaptureEdge::Both => (1, 1, 2),
+                    };
+                    let ccer = (1 | ccp << 1 | ccnp << 3) << (n * 4);
+                    self.ccer.modify(|r, w| unsafe { w.bits((r.bits() & !(0b1011 << (n * 4))) | ccer) });
+
+                    // Load the prescaler without raising the update flag and start counting
+                    self.cr1.modify(|_, w| w.urs().set_bit());
+                    self.egr.write(|w| w.ug().set_bit());
+                    self.cr1.modify(|_, w| w.cen().set_bit());
+
+                    InputCapture {
+                        tim: self,
+                        pin,
+                        _channel: PhantomData,
+                        clk: (timer_clk.raw() / (psc + 1)).Hz(),
+                        edges_per_period,
+                        prescaler: config.prescaler.divisor(),
+                        overflows: 0,
+                        last_timestamp: None,
+                    }
+                }
+            }
+
+            impl<CHANNEL: CaptureChannel, PIN> InputCapture<$TIMX, CHANNEL, PIN> {
+                /// Returns the frequency of the counter.
+                pub fn counter_frequency(&self) -> Hertz {
+                    self.clk
+                }
+
+                /// Returns the last captured counter value.
+                pub fn read(&mut self) -> nb::Result<$typ, Error> {
+                    let n = CHANNEL::INDEX;
+                    let sr = self.tim.sr.read().bits();
+                    if sr & (1 << (n + 1)) == 0 {
+                        return Err(nb::Error::WouldBlock);
+                    }
+                    if sr & (1 << (n + 9)) != 0 {
+                        self.clear_flag(n + 9);
+                        // Reading the capture clears the capture flag
+                        let _ = self.tim.ccr[n].read();
+                        return Err(nb::Error::Other(Error::Overcapture));
+                    }
+                    Ok(self.tim.ccr[n].read().bits() as $typ)
+                }
+
+                /// Returns the last capture as a number of counter ticks since the start of the
+                /// timer, including the counter overflows.
+                ///
+                /// To keep track of the overflows this has to be called, or
+                /// [`poll_overflow`](Self::poll_overflow) has to be called, at least once per
+                /// counter period.
+                pub fn read_timestamp(&mut self) -> nb::Result<u64, Error> {
+                    let overflow_pending = self.tim.sr.read().uif().bit_is_set();
+                    let value = self.read()?;
+
+                    let mut overflows = self.overflows;
+                    if overflow_pending {
+                        self.clear_flag(0);
+                        self.overflows += 1;
+                        // A capture in the lower half of the counter happened after the overflow
+                        if value <= <$typ>::MAX / 2 {
+                            overflows += 1;
+                        }
+                    }
+                    Ok(overflows * (<$typ>::MAX as u64 + 1) + value as u64)
+                }
+
+                /// Accounts for a counter overflow, to be called from the timer interrupt when
+                /// listening for [`Event::Overflow`].
+                pub fn poll_overflow(&mut self) {
+                    if self.tim.sr.read().uif().bit_is_set() {
+                        self.clear_flag(0);
+                        self.overflows += 1;
+                    }
+                }
+
+                /// Returns the frequency of the input signal, measured between the last two
+                /// captures.
+                pub fn frequency(&mut self) -> nb::Result<Hertz, Error> {
+                    let timestamp = self.read_timestamp()?;
+                    let last = self.last_timestamp.replace(timestamp);
+                    match last {
+                        Some(last) if timestamp > last => {
+                            // A capture happens every `prescaler` edges and the signal has
+                            // `edges_per_period` edges per period
+                            let ticks = (timestamp - last) * self.edges_per_period as u64;
+                            let freq = self.clk.raw() as u64 * self.prescaler as u64 / ticks;
+                            Ok((freq as u32).Hz())
+                        }
+                        _ => Err(nb::Error::WouldBlock),
+                    }
+                }
+
+                /// Starts listening for an `event`
+                pub fn listen(&mut self, event: Event) {
+                    let bit = match event {
+                        Event::Capture => CHANNEL::INDEX + 1,
+                        Event::Overflow => 0,
+                    };
+                    self.tim.dier.modify(|r, w| unsafe { w.bits(r.bits() | 1 << bit) });
+                }
+
+                /// Stops listening for an `event`
+                pub fn unlisten(&mut self, event: Event) {
+                    let bit = match event {
+                        Event::Capture => CHANNEL::INDEX + 1,
+                        Event::Overflow => 0,
+                    };
+                    self.tim.dier.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << bit)) });
+                }
+
+                /// Requests a DMA transfer on every capture, use this `InputCapture` as the
+                /// source of a `PeripheralToMemory` transfer.
+                pub fn enable_dma(&mut self, enable: bool) {
+                    let bit = CHANNEL::INDEX + 9;
+                    self.tim.dier.modify(|r, w| unsafe {
+                        w.bits(if enable { r.bits() | 1 << bit } else { r.bits() & !(1 << bit) })
+                    });
+                }
+
+                /// Releases the TIM peripheral and the pin
+                pub fn release(self) -> ($TIMX, PIN) {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    (self.tim, self.pin)
+                }
+
+                fn clear_flag(&mut self, bit: usize) {
+                    // Flags are cleared by writing 0, writing 1 has no effect
+                    self.tim.sr.write(|w| unsafe { w.bits(!(1 << bit)) });
+                }
+            }
+
+            $(
+                unsafe impl<PIN> TargetAddress<PeripheralToMemory> for InputCapture<$TIMX, $CH, PIN> {
+                    #[inline(always)]
+                    fn address(&self) -> u32 {
+                        &self.tim.ccr[<$CH as CaptureChannel>::INDEX] as *const _ as u32
+                    }
+
+                    type MemSize = $typ;
+
+                    const REQUEST_LINE: Option<u8> = Some(DmaMuxResources::$mux as u8);
+                }
+            )+
+        )+
+    };
+}
+
+macro_rules! pwm_input {
+    ($($TIMX:ident: $typ:ty,)+) => {
+        $(
+            impl PwmInputExt for $TIMX {
+                fn pwm_input<PIN, COMP>(self, pin: PIN, min_frequency: Hertz, rcc: &mut Rcc) -> PwmInput<Self, PIN>
+                where
+                    PIN: Pins<Self, C1, COMP>,
+                {
+                    unsafe {
+                        let rcc_ptr = &(*RCC::ptr());
+                        $TIMX::enable(rcc_ptr);
+                        $TIMX::reset(rcc_ptr);
+                    }
+
+                    // Choose the prescaler such that the period of min_frequency fits in the counter
+                    let timer_clk = $TIMX::get_timer_frequency(&rcc.clocks);
+                    let ticks = timer_clk.raw() as u64 / min_frequency.raw() as u64;
+                    let psc = (ticks / (<$typ>::MAX as u64 + 1)).min(0xffff) as u32;
+                    self.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+                    self.arr.write(|w| unsafe { w.bits(<$typ>::MAX as u32) });
+
+                    // IC1 on TI1 captures the period on the rising edge, IC2 on TI1 captures the
+                    // high time on the falling edge
+                    self.ccmr1_input().write(|w| unsafe { w.cc1s().bits(0b01).cc2s().bits(0b10) });
+                    self.ccer.write(|w| {
+                        w.cc1p()
+                            .clear_bit()
+                            .cc1e()
+                            .set_bit()
+                            .cc2p()
+                            .set_bit()
+                            .cc2e()
+                            .set_bit()
+                    });
+
+                    // Reset the counter on TI1FP1, only an overflow raises the update flag
+                    self.smcr.write(|w| unsafe { w.ts().bits(0b101).sms().bits(0b100) });
+                    self.cr1.modify(|_, w| w.urs().set_bit());
+                    self.egr.write(|w| w.ug().set_bit());
+                    self.cr1.modify(|_, w| w.cen().set_bit());
+
+                    PwmInput {
+                        tim: self,
+                        pin,
+                        clk: (timer_clk.raw() / (psc + 1)).Hz(),
+                        valid: false,
+                    }
+                }
+            }
+
+            impl<PIN> PwmInput<$TIMX, PIN> {
+                /// Returns the period and the high time of the input signal in counter ticks, or
+                /// `None` if no complete period has been captured since the signal was lost.
+                pub fn read(&mut self) -> Option<(u32, u32)> {
+                    let sr = self.tim.sr.read();
+                    if sr.cc1if().bit_is_set() {
+                        self.valid = true;
+                    } else if sr.uif().bit_is_set() {
+                        // No edge for a full counter period
+                        self.valid = false;
+                    }
+                    self.tim.sr.write(|w| unsafe { w.bits(!1) });
+
+                    if !self.valid {
+                        return None;
+                    }
+                    let period = self.tim.ccr[0].read().bits();
+                    let high = self.tim.ccr[1].read().bits();
+                    if period == 0 {
+                        return None;
+                    }
+                    Some((period, high))
+                }
+
+                /// Returns the frequency of the input signal.
+                pub fn frequency(&mut self) -> Option<Hertz> {
+                    self.read().map(|(period, _)| (self.clk.raw() / period).Hz())
+                }
+
+                /// Returns the duty cycle of the input signal, from 0.0 to 1.0.
+                pub fn duty_cycle(&mut self) -> Option<f32> {
+                    self.read().map(|(period, high)| high as f32 / period as f32)
+                }
+
+                /// Returns the frequency of the counter.
+                pub fn counter_frequency(&self) -> Hertz {
+                    self.clk
+                }
+
+                /// Releases the TIM peripheral and the pin
+                pub fn release(self) -> ($TIMX, PIN) {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    (self.tim, self.pin)
+                }
+            }
+        )+
+    };
+}
+
+capture! {
+    TIM1: (u16, [C1: TIM1_CH1, C2: TIM1_CH2, C3: TIM1_CH3, C4: TIM1_CH4], ccmr2_input),
+    TIM2: (u32, [C1: TIM2_CH1, C2: TIM2_CH2, C3: TIM2_CH3, C4: TIM2_CH4], ccmr2_input),
+    TIM3: (u16, [C1: TIM3_CH1, C2: TIM3_CH2, C3: TIM3_CH3, C4: TIM3_CH4], ccmr2_input),
+    TIM4: (u16, [C1: TIM4_CH1, C2: TIM4_CH2, C3: TIM4_CH3, C4: TIM4_CH4], ccmr2_input),
+    TIM8: (u16, [C1: TIM8_CH1, C2: TIM8_CH2, C3: TIM8_CH3, C4: TIM8_CH4], ccmr2_input),
+    TIM15: (u16, [C1: TIM15_CH1, C2: TIM15_CH2]),
+    TIM16: (u16, [C1: TIM16_CH1]),
+    TIM17: (u16, [C1: TIM17_CH1]),
+}
+
+pwm_input! {
+    TIM1: u16,
+    TIM2: u32,
+    TIM3: u16,
+    TIM4: u16,
+    TIM8: u16,
+    TIM15: u16,
+}
+
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+capture! {
+    TIM5: (u32, [C1: TIM5_CH1, C2: TIM5_CH2, C3: TIM5_CH3, C4: TIM5_CH4], ccmr2_input),
+}
+
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+pwm_input! {
+    TIM5: u32,
+}
+
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+capture! {
+    TIM20: (u16, [C1: TIM20_CH1, C2: TIM20_CH2, C3: TIM20_CH3, C4: TIM20_CH4], ccmr2_input),
+}
+
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+pwm_input! {
+    TIM20: u16,
+}
@@ -138,6 +138,62 @@ pub enum Hysteresis {
     H70mV = 0b111,
 }
 
+/// Timer output that can blank the output of comparator `C`
+///
+/// The available sources differ per comparator, see `COMPx_CSR.BLANKSEL` in the reference manual.
+pub trait BlankingSource<C> {
+    /// Value of `BLANKSEL` selecting this source
+    const BITS: u8;
+}
+
+macro_rules! blanking_source_types {
+    ($($source:ident: $doc:expr,)+) => {$(
+        #[doc = $doc]
+        pub struct $source;
+    )+};
+}
+
+blanking_source_types! {
+    Tim1Oc5: "TIM1 OC5 as blanking source",
+    Tim2Oc3: "TIM2 OC3 as blanking source",
+    Tim2Oc4: "TIM2 OC4 as blanking source",
+    Tim3Oc3: "TIM3 OC3 as blanking source",
+    Tim3Oc4: "TIM3 OC4 as blanking source",
+    Tim4Oc3: "TIM4 OC3 as blanking source",
+    Tim4Oc4: "TIM4 OC4 as blanking source",
+    Tim8Oc5: "TIM8 OC5 as blanking source",
+    Tim15Oc1: "TIM15 OC1 as blanking source",
+    Tim15Oc2: "TIM15 OC2 as blanking source",
+    Tim20Oc5: "TIM20 OC5 as blanking source",
+}
+
+macro_rules! blanking_sources {
+    ($($COMP:ident: { $($source:ident: $bits:expr,)+ },)+) => {$($(
+        impl BlankingSource<$COMP> for $source {
+            const BITS: u8 = $bits;
+        }
+    )+)+};
+}
+
+blanking_sources! {
+    COMP1: { Tim1Oc5: 0b001, Tim2Oc3: 0b010, Tim3Oc3: 0b011, Tim8Oc5: 0b100, Tim20Oc5: 0b101, Tim15Oc1: 0b110, Tim4Oc3: 0b111, },
+    COMP2: { Tim1Oc5: 0b001, Tim2Oc3: 0b010, Tim3Oc3: 0b011, Tim8Oc5: 0b100, Tim20Oc5: 0b101, Tim15Oc1: 0b110, Tim4Oc3: 0b111, },
+    COMP3: { Tim1Oc5: 0b001, Tim3Oc3: 0b010, Tim2Oc4: 0b011, Tim8Oc5: 0b100, Tim20Oc5: 0b101, Tim15Oc1: 0b110, Tim4Oc3: 0b111, },
+    COMP4: { Tim3Oc4: 0b001, Tim8Oc5: 0b010, Tim15Oc1: 0b011, Tim1Oc5: 0b100, Tim20Oc5: 0b101, Tim15Oc2: 0b110, Tim4Oc3: 0b111, },
+}
+
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g483",
+    feature = "stm32g474",
+    feature = "stm32g484"
+))]
+blanking_sources! {
+    COMP5: { Tim2Oc3: 0b001, Tim8Oc5: 0b010, Tim3Oc3: 0b011, Tim1Oc5: 0b100, Tim20Oc5: 0b101, Tim4Oc3: 0b110, },
+    COMP6: { Tim8Oc5: 0b001, Tim2Oc4: 0b010, Tim15Oc2: 0b011, Tim1Oc5: 0b100, Tim20Oc5: 0b101, Tim4Oc4: 0b110, },
+    COMP7: { Tim1Oc5: 0b001, Tim8Oc5: 0b010, Tim3Oc3: 0b011, Tim15Oc2: 0b100, Tim20Oc5: 0b101, Tim4Oc4: 0b110, },
+}
+
 /// Comparator positive input
 pub trait PositiveInput<C> {
     fn setup(&self, comp: &C);
@@ -380,8 +436,21 @@ pub trait ComparatorExt<COMP> {
     ) -> Comparator<COMP, Disabled>;
 }
 
+/// Comparator whose output can be routed to the break and OCREF_CLR inputs of the timers
+///
+/// See [`PwmBuilder`](crate::pwm::PwmBuilder) for using the output for cycle-by-cycle current
+/// limiting in hardware.
+pub trait TimerSource {
+    /// Index of the comparator, 0 for COMP1
+    const INDEX: u8;
+}
+
 macro_rules! impl_comparator {
-    ($COMP:ty, $comp:ident, $Event:expr) => {
+    ($COMP:ty, $comp:ident, $Event:expr, $index:expr) => {
+        impl<ED: EnabledState> TimerSource for Comparator<$COMP, ED> {
+            const INDEX: u8 = $index;
+        }
+
         impl ComparatorExt<$COMP> for $COMP {
             fn comparator<P: PositiveInput<$COMP>, N: NegativeInput<$COMP>>(
                 self,
@@ -425,6 +494,13 @@ macro_rules! impl_comparator {
                 comp.comparator(positive_input, negative_input, config, clocks)
             }
 
+            /// Masks the comparator output while the timer output `source` is active, e.g. to hide
+            /// the current spike when a power switch turns on
+            pub fn blanking<B: BlankingSource<$COMP>>(self, _source: B) -> Self {
+                self.regs.csr().modify(|_, w| unsafe { w.blanksel().bits(B::BITS) });
+                self
+            }
+
             /// Enables the comparator
             pub fn enable(self) -> Comparator<$COMP, Enabled> {
                 self.regs.csr().modify(|_, w| w.en().set_bit());
@@ -493,10 +569,10 @@ macro_rules! impl_comparator {
     };
 }
 
-impl_comparator!(COMP1, comp1, ExtiEvent::COMP1);
-impl_comparator!(COMP2, comp2, ExtiEvent::COMP2);
-impl_comparator!(COMP3, comp1, ExtiEvent::COMP3);
-impl_comparator!(COMP4, comp2, ExtiEvent::COMP4);
+impl_comparator!(COMP1, comp1, ExtiEvent::COMP1, 0);
+impl_comparator!(COMP2, comp2, ExtiEvent::COMP2, 1);
+impl_comparator!(COMP3, comp1, ExtiEvent::COMP3, 2);
+impl_comparator!(COMP4, comp2, ExtiEvent::COMP4, 3);
 
 #[cfg(any(
     feature = "stm32g473",
@@ -504,7 +580,7 @@ impl_comparator!(COMP4, comp2, ExtiEvent::COMP4);
     feature = "stm32g474",
     feature = "stm32g484"
 ))]
-impl_comparator!(COMP5, comp1, ExtiEvent::COMP5);
+impl_comparator!(COMP5, comp1, ExtiEvent::COMP5, 4);
 
 #[cfg(any(
     feature = "stm32g473",
@@ -512,7 +588,7 @@ impl_comparator!(COMP5, comp1, ExtiEvent::COMP5);
     feature = "stm32g474",
     feature = "stm32g484"
 ))]
-impl_comparator!(COMP6, comp2, ExtiEvent::COMP6);
+impl_comparator!(COMP6, comp2, ExtiEvent::COMP6, 5);
 
 #[cfg(any(
     feature = "stm32g473",
@@ -520,7 +596,7 @@ impl_comparator!(COMP6, comp2, ExtiEvent::COMP6);
     feature = "stm32g474",
     feature = "stm32g484"
 ))]
-impl_comparator!(COMP7, comp2, ExtiEvent::COMP7);
+impl_comparator!(COMP7, comp2, ExtiEvent::COMP7, 6);
 
 #[cfg(not(any(
     feature = "stm32g473",
@@ -0,0 +1,269 @@
+//! CRC calculation unit
+//!
+//! The CRC unit computes a CRC with a configurable polynomial of 7, 8, 16 or 32 bits. The
+//! polynomial, initial value and bit reversal are set through a [`Config`], which also provides
+//! presets for common algorithms:
+//!
+//! | Preset                   | Polynomial | Initial value | Reflected | Final XOR  |
+//! |--------------------------|------------|---------------|-----------|------------|
+//! | [`Config::crc32`]        | 0x04C11DB7 | 0xFFFFFFFF    | yes       | 0xFFFFFFFF |
+//! | [`Config::crc16_modbus`] | 0x8005     | 0xFFFF        | yes       | 0x0000     |
+//! | [`Config::crc8_smbus`]   | 0x07       | 0x00          | no        | 0x00       |
+//!
+//! Data can be fed byte, halfword or word wise, or by the DMA using a `MemoryToPeripheral`
+//! transfer into the [`Crc`].
+use core::cell::UnsafeCell;
+use core::ptr;
+
+use crate::dma::{traits::TargetAddress, MemoryToPeripheral};
+use crate::rcc::{Enable, Rcc, Reset};
+use crate::stm32::CRC;
+
+/// Extension trait to constrain the CRC peripheral
+pub trait CrcExt {
+    /// Constrains the CRC peripheral to play nicely with the other abstractions
+    fn constrain(self, rcc: &mut Rcc) -> Config;
+}
+
+impl CrcExt for CRC {
+    fn constrain(self, rcc: &mut Rcc) -> Config {
+        CRC::enable(&rcc.rb);
+        CRC::reset(&rcc.rb);
+
+        Config::new(self)
+    }
+}
+
+/// Polynomial settings, the value excludes the implicit highest order bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polynomial {
+    /// 7-bit polynomial, only the lowest 7 bits are valid
+    L7(u8),
+    /// 8-bit polynomial
+    L8(u8),
+    /// 16-bit polynomial
+    L16(u16),
+    /// 32-bit polynomial
+    L32(u32),
+}
+
+impl Polynomial {
+    fn mask(&self) -> u32 {
+        match self {
+            Polynomial::L7(_) => 0x7f,
+            Polynomial::L8(_) => 0xff,
+            Polynomial::L16(_) => 0xffff,
+            Polynomial::L32(_) => 0xffff_ffff,
+        }
+    }
+}
+
+/// Bit reversal of the input data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitReversal {
+    /// Reverse the bits of each byte
+    ByByte = 0b01,
+    /// Reverse the bits of each halfword
+    ByHalfWord = 0b10,
+    /// Reverse the bits of each word
+    ByWord = 0b11,
+}
+
+/// CRC configuration
+pub struct Config {
+    crc: CRC,
+    initial_value: u32,
+    polynomial: Polynomial,
+    input_bit_reversal: Option<BitReversal>,
+    output_bit_reversal: bool,
+    final_xor: u32,
+}
+
+impl Config {
+    fn new(crc: CRC) -> Self {
+        Config {
+            crc,
+            initial_value: 0xffff_ffff,
+            polynomial: Polynomial::L32(0x04c1_1db7),
+            input_bit_reversal: None,
+            output_bit_reversal: false,
+            final_xor: 0,
+        }
+    }
+
+    /// Sets the initial value of the CRC.
+    pub fn initial_value(mut self, init: u32) -> Self {
+        self.initial_value = init;
+        self
+    }
+
+    /// Sets the size and the value of the polynomial.
+    pub fn polynomial(mut self, polynomial: Polynomial) -> Self {
+        self.polynomial = polynomial;
+        self
+    }
+
+    /// Enables bit reversal of the input data.
+    pub fn input_bit_reversal(mut self, rev: Option<BitReversal>) -> Self {
+        self.input_bit_reversal = rev;
+        self
+    }
+
+    /// Enables bit reversal of the CRC result.
+    pub fn output_bit_reversal(mut self, rev: bool) -> Self {
+        self.output_bit_reversal = rev;
+        self
+    }
+
+    /// Sets a value which is XORed with the CRC result. This is done in software, the CRC unit
+    /// has no support for it.
+    pub fn final_xor(mut self, xor: u32) -> Self {
+        self.final_xor = xor;
+        self
+    }
+
+    /// CRC-32/ISO-HDLC, as used by Ethernet, zlib and PNG
+    pub fn crc32(self) -> Self {
+        self.polynomial(Polynomial::L32(0x04c1_1db7))
+            .initial_value(0xffff_ffff)
+            .input_bit_reversal(Some(BitReversal::ByByte))
+            .output_bit_reversal(true)
+            .final_xor(0xffff_ffff)
+    }
+
+    /// CRC-16/MODBUS
+    pub fn crc16_modbus(self) -> Self {
+        self.polynomial(Polynomial::L16(0x8005))
+            .initial_value(0xffff)
+            .input_bit_reversal(Some(BitReversal::ByByte))
+            .output_bit_reversal(true)
+            .final_xor(0)
+    }
+
+    /// CRC-8/SMBUS, as used for the SMBus packet error code
+    pub fn crc8_smbus(self) -> Self {
+        self.polynomial(Polynomial::L8(0x07))
+            .initial_value(0)
+            .input_bit_reversal(None)
+            .output_bit_reversal(false)
+            .final_xor(0)
+    }
+
+    /// Writes the configuration to the CRC unit and resets the CRC to the initial value.
+    pub fn freeze(self) -> Crc {
+        let crc = self.crc;
+
+        let (poly, polysize) = match self.polynomial {
+            Polynomial::L7(val) => ((val & 0x7f) as u32, 0b11),
+            Polynomial::L8(val) => (val as u32, 0b10),
+            Polynomial::L16(val) => (val as u32, 0b01),
+            Polynomial::L32(val) => (val, 0b00),
+        };
+        let rev_in = self.input_bit_reversal.map_or(0b00, |rev| rev as u8);
+
+        crc.init.write(|w| unsafe { w.crc_init().bits(self.initial_value) });
+        crc.pol.write(|w| unsafe { w.pol().bits(poly) });
+        crc.cr.write(|w| unsafe {
+            w.rev_in()
+                .bits(rev_in)
+                .polysize()
+                .bits(polysize)
+                .rev_out()
+                .bit(self.output_bit_reversal)
+                .reset()
+                .set_bit()
+        });
+
+        Crc {
+            rb: crc,
+            mask: self.polynomial.mask(),
+            final_xor: self.final_xor,
+        }
+    }
+}
+
+/// Configured CRC unit
+pub struct Crc {
+    rb: CRC,
+    mask: u32,
+    final_xor: u32,
+}
+
+impl Crc {
+    /// Resets the CRC to the initial value.
+    pub fn reset(&mut self) {
+        self.rb.cr.modify(|_, w| w.reset().set_bit());
+    }
+
+    /// Resets the CRC to a new initial value.
+    pub fn reset_with_initial_value(&mut self, init: u32) {
+        self.rb.init.write(|w| unsafe { w.crc_init().bits(init) });
+        self.reset();
+    }
+
+    /// Feeds bytes into the CRC.
+    pub fn feed(&mut self, data: &[u8]) {
+        let dr = &self.rb.dr as *const _ as *const UnsafeCell<u8>;
+        for byte in data {
+            unsafe { ptr::write_volatile(UnsafeCell::raw_get(dr), *byte) };
+        }
+    }
+
+    /// Feeds halfwords into the CRC.
+    pub fn feed_halfwords(&mut self, data: &[u16]) {
+        let dr = &self.rb.dr as *const _ as *const UnsafeCell<u16>;
+        for halfword in data {
+            unsafe { ptr::write_volatile(UnsafeCell::raw_get(dr), *halfword) };
+        }
+    }
+
+    /// Feeds words into the CRC.
+    pub fn feed_words(&mut self, data: &[u32]) {
+        for word in data {
+            self.rb.dr.write(|w| unsafe { w.dr().bits(*word) });
+        }
+    }
+
+    /// Returns the CRC of the data fed since the last reset and resets the CRC.
+    pub fn result(&mut self) -> u32 {
+        let result = self.peek_result();
+        self.reset();
+        result
+    }
+
+    /// Returns the CRC of the data fed since the last reset, more data can be fed afterwards.
+    pub fn peek_result(&self) -> u32 {
+        (self.rb.dr.read().bits() ^ self.final_xor) & self.mask
+    }
+
+    /// Returns the CRC unit to be configured again.
+    pub fn reconfigure(self) -> Config {
+        Config::new(self.rb)
+    }
+
+    /// Gives access to the independent data register, which is not affected by the CRC
+    /// calculation.
+    pub fn set_idr(&mut self, value: u32) {
+        self.rb.idr.write(|w| unsafe { w.idr().bits(value) });
+    }
+
+    /// Reads the independent data register.
+    pub fn get_idr(&self) -> u32 {
+        self.rb.idr.read().idr().bits()
+    }
+
+    /// Releases the CRC peripheral.
+    pub fn release(self) -> CRC {
+        self.rb
+    }
+}
+
+/// The DMA feeds the data byte wise, use a memory increment without a peripheral increment.
+unsafe impl TargetAddress<MemoryToPeripheral> for Crc {
+    #[inline(always)]
+    fn address(&self) -> u32 {
+        &self.rb.dr as *const _ as u32
+    }
+
+    type MemSize = u8;
+}
@@ -4,14 +4,30 @@
 //!
 //! This code has been taken from the stm32g0xx-hal project and modified to support
 //! STM32G4xx MCUs.
+//!
+//! ## DMA
+//!
+//! A channel enabled with `enable_dma` requests a new value from the DMA on every trigger, so a
+//! waveform table can be played back with a circular `MemoryToPeripheral` transfer. Both channels
+//! of a DAC can be fed together through the dual data holding register by transferring into the
+//! tuple of channel 1 in the `Dma` state and channel 2 in the `Triggered` state.
+//!
+//! ```ignore
+//! let dac1ch1 = dp.DAC1.constrain(gpioa.pa4.into_analog(), &mut rcc);
+//! let dac = dac1ch1.calibrate_buffer(&mut delay).enable_dma::<Right12>(Trigger::Tim6Trgo);
+//!
+//! let mut transfer = streams.0.into_memory_to_peripheral_transfer(dac, &SINE[..], config);
+//! transfer.start(|_dac| {});
+//! ```
 
 use core::marker::PhantomData;
 use core::mem::MaybeUninit;
 
+use crate::dma::{mux::DmaMuxResources, traits::TargetAddress, MemoryToPeripheral};
 use crate::gpio::gpioa::{PA4, PA5, PA6};
 use crate::gpio::DefaultMode;
 use crate::rcc::{self, *};
-use crate::stm32::{DAC1, DAC2, DAC3, DAC4, RCC};
+use crate::stm32::{dac1, DAC1, DAC2, DAC3, DAC4, RCC};
 use hal_api_old::blocking::delay::DelayUs;
 
 pub trait DacOut<V> {
@@ -19,9 +35,37 @@ pub trait DacOut<V> {
     fn get_value(&mut self) -> V;
 }
 
+/// Trigger that moves the data holding register to the output (TSEL)
+///
+/// The HRTIM triggers are the DAC reset triggers 1 to 6 and the DAC trigger of the DAC instance
+/// (`hrtim_dac_trg1` for DAC1, `hrtim_dac_trg2` for DAC2, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// Software trigger, see `trigger()`
+    Software = 0,
+    /// TIM8 TRGO, TIM1 TRGO for DAC3
+    Tim8Trgo = 1,
+    Tim7Trgo = 2,
+    Tim15Trgo = 3,
+    Tim2Trgo = 4,
+    Tim4Trgo = 5,
+    /// EXTI line 9
+    Exti9 = 6,
+    Tim6Trgo = 7,
+    Tim3Trgo = 8,
+    HrtimReset1 = 9,
+    HrtimReset2 = 10,
+    HrtimReset3 = 11,
+    HrtimReset4 = 12,
+    HrtimReset5 = 13,
+    HrtimReset6 = 14,
+    Hrtim = 15,
+}
+
 pub struct GeneratorConfig {
     mode: u8,
     amp: u8,
+    trigger: Trigger,
 }
 
 impl GeneratorConfig {
@@ -29,6 +73,7 @@ impl GeneratorConfig {
         Self {
             mode: 0b10,
             amp: amplitude,
+            trigger: Trigger::Software,
         }
     }
 
@@ -36,6 +81,7 @@ impl GeneratorConfig {
         Self {
             mode: 0b11,
             amp: amplitude,
+            trigger: Trigger::Software,
         }
     }
 
@@ -43,16 +89,177 @@ impl GeneratorConfig {
         Self {
             mode: 0b01,
             amp: seed,
+            trigger: Trigger::Software,
+        }
+    }
+
+    /// Selects the trigger that steps the generator, the software trigger by default
+    pub fn trigger(mut self, trigger: Trigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+}
+
+/// Trigger that increments or decrements the sawtooth generator (STINCTRIGSEL)
+///
+/// The HRTIM triggers are the DAC step triggers 1 to 6 and the DAC trigger of the DAC instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncrementTrigger {
+    /// Software trigger, see `increment()`
+    Software = 0,
+    /// TIM8 TRGO, TIM1 TRGO for DAC3
+    Tim8Trgo = 1,
+    Tim7Trgo = 2,
+    Tim15Trgo = 3,
+    Tim2Trgo = 4,
+    Tim4Trgo = 5,
+    /// EXTI line 10
+    Exti10 = 6,
+    Tim6Trgo = 7,
+    Tim3Trgo = 8,
+    HrtimStep1 = 9,
+    HrtimStep2 = 10,
+    HrtimStep3 = 11,
+    HrtimStep4 = 12,
+    HrtimStep5 = 13,
+    HrtimStep6 = 14,
+    Hrtim = 15,
+}
+
+/// Direction of the sawtooth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SawtoothDirection {
+    Down = 0,
+    Up = 1,
+}
+
+/// Sawtooth generator configuration
+///
+/// The reset trigger loads the start value, every increment trigger adds or subtracts the
+/// increment, e.g. for slope compensation of peak current mode control.
+#[derive(Debug, Clone, Copy)]
+pub struct SawtoothConfig {
+    start: u16,
+    increment: u16,
+    direction: SawtoothDirection,
+    reset_trigger: Trigger,
+    increment_trigger: IncrementTrigger,
+}
+
+impl SawtoothConfig {
+    /// Sawtooth starting at `start` (12 bits) that changes by `increment` per increment trigger.
+    /// The increment is in 12.4 fixed point format, so 16 is one LSB.
+    pub fn new(start: u16, increment: u16, direction: SawtoothDirection) -> Self {
+        assert!(start < 0x1000);
+        SawtoothConfig {
+            start,
+            increment,
+            direction,
+            reset_trigger: Trigger::Software,
+            increment_trigger: IncrementTrigger::Software,
         }
     }
+
+    /// Selects the trigger that restarts the sawtooth, the software trigger by default
+    pub fn reset_trigger(mut self, trigger: Trigger) -> Self {
+        self.reset_trigger = trigger;
+        self
+    }
+
+    /// Selects the trigger that steps the sawtooth, the software trigger by default
+    pub fn increment_trigger(mut self, trigger: IncrementTrigger) -> Self {
+        self.increment_trigger = trigger;
+        self
+    }
 }
 
+/// Sample and hold configuration, all times are in LSI clock cycles
+///
+/// In sample and hold mode the output is only driven during the sample time and refreshed every
+/// hold time, which reduces the power consumption. The LSI is enabled when this mode is used.
+#[derive(Debug, Clone, Copy)]
+pub struct SampleHoldConfig {
+    sample_time: u16,
+    hold_time: u16,
+    refresh_time: u8,
+}
+
+impl SampleHoldConfig {
+    /// `sample_time` and `hold_time` are 10 bits, `hold_time` must be at least 1
+    pub fn new(sample_time: u16, hold_time: u16, refresh_time: u8) -> Self {
+        assert!(sample_time < 0x400);
+        assert!(hold_time > 0 && hold_time < 0x400);
+        SampleHoldConfig {
+            sample_time,
+            hold_time,
+            refresh_time,
+        }
+    }
+}
+
+/// Data holding register used for DMA transfers
+pub trait DataFormat {
+    /// Value of one channel
+    type Word;
+    /// Values of both channels, channel 1 in the lower half
+    type DualWord;
+
+    #[doc(hidden)]
+    fn ch1_address(dac: &dac1::RegisterBlock) -> u32;
+    #[doc(hidden)]
+    fn ch2_address(dac: &dac1::RegisterBlock) -> u32;
+    #[doc(hidden)]
+    fn dual_address(dac: &dac1::RegisterBlock) -> u32;
+}
+
+/// 12-bit right aligned data
+pub struct Right12;
+/// 12-bit left aligned data
+pub struct Left12;
+/// 8-bit right aligned data
+pub struct Right8;
+
+macro_rules! data_format {
+    ($($F:ident: $word:ty, $dual_word:ty, ($dhr1:ident, $dhr2:ident, $dhrd:ident),)+) => {
+        $(
+            impl DataFormat for $F {
+                type Word = $word;
+                type DualWord = $dual_word;
+
+                fn ch1_address(dac: &dac1::RegisterBlock) -> u32 {
+                    &dac.$dhr1 as *const _ as u32
+                }
+
+                fn ch2_address(dac: &dac1::RegisterBlock) -> u32 {
+                    &dac.$dhr2 as *const _ as u32
+                }
+
+                fn dual_address(dac: &dac1::RegisterBlock) -> u32 {
+                    &dac.$dhrd as *const _ as u32
+                }
+            }
+        )+
+    };
+}
+
+data_format!(
+    Right12: u16, u32, (dac_dhr12r1, dac_dhr12r2, dac_dhr12rd),
+    Left12: u16, u32, (dac_dhr12l1, dac_dhr12l2, dac_dhr12ld),
+    Right8: u8, u16, (dac_dhr8r1, dac_dhr8r2, dac_dhr8rd),
+);
+
 /// Enabled DAC (type state)
 pub struct Enabled;
 // / Enabled DAC without output buffer (type state)
 //pub struct EnabledUnbuffered;
 /// Enabled DAC wave generator (type state)
 pub struct WaveGenerator;
+/// Enabled DAC that updates its output on a trigger (type state)
+pub struct Triggered;
+/// Enabled DAC that requests a new value from the DMA on every trigger (type state)
+pub struct Dma<F: DataFormat> {
+    _format: PhantomData<F>,
+}
 /// Disabled DAC (type state)
 pub struct Disabled;
 
@@ -60,6 +267,8 @@ pub trait ED {}
 impl ED for Enabled {}
 //impl ED for EnabledUnbuffered {}
 impl ED for WaveGenerator {}
+impl ED for Triggered {}
+impl<F: DataFormat> ED for Dma<F> {}
 impl ED for Disabled {}
 
 macro_rules! impl_dac {
@@ -184,16 +393,90 @@ macro_rules! dac_helper {
         $wave:ident,
         $mamp:ident,
         $ten:ident,
-        $swtrig:ident
+        $swtrig:ident,
+        $tsel:ident,
+        $dmaen:ident,
+        $dmaudrie:ident,
+        $dmaudr:ident,
+        $address:ident,
+        $mux:ident,
+        $swtrigb:ident,
+        $str:ident,
+        $strstdata:ident,
+        $stdir:ident,
+        $stincdata:ident,
+        $strsttrigsel:ident,
+        $stinctrigsel:ident,
+        $shsr:ident,
+        $tsample:ident,
+        $thold:ident,
+        $trefresh:ident
     ),)+) => {
         $(
             impl<const MODE_BITS: u8> $CX<MODE_BITS, Disabled> {
+                /// MODE_BITS, keeping sample and hold mode if it is enabled
+                fn mode_bits() -> u8 {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+                    MODE_BITS | (dac.dac_mcr.read().$mode().bits() & 0b100)
+                }
+
+                /// Enables sample and hold mode for the following `enable*` calls
+                pub fn enable_sample_and_hold(self, config: SampleHoldConfig, rcc: &mut Rcc) -> Self {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+
+                    rcc.enable_lsi();
+                    dac.$shsr.write(|w| unsafe { w.$tsample().bits(config.sample_time) });
+                    dac.dac_shhr.modify(|_, w| unsafe { w.$thold().bits(config.hold_time) });
+                    dac.dac_shrr.modify(|_, w| unsafe { w.$trefresh().bits(config.refresh_time) });
+                    dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(MODE_BITS | 0b100) });
+
+                    self
+                }
+
+                /// Disables sample and hold mode
+                pub fn disable_sample_and_hold(self) -> Self {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+                    dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(MODE_BITS) });
+
+                    self
+                }
+
+                /// Enables the sawtooth generator
+                pub fn enable_sawtooth(self, config: SawtoothConfig) -> $CX<MODE_BITS, WaveGenerator> {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+
+                    dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(Self::mode_bits()) });
+                    dac.$str.write(|w| unsafe {
+                        w.$strstdata()
+                            .bits(config.start)
+                            .$stdir()
+                            .bit(config.direction == SawtoothDirection::Up)
+                            .$stincdata()
+                            .bits(config.increment)
+                    });
+                    dac.dac_stmodr.modify(|_, w| unsafe {
+                        w.$strsttrigsel()
+                            .bits(config.reset_trigger as u8)
+                            .$stinctrigsel()
+                            .bits(config.increment_trigger as u8)
+                    });
+                    dac.dac_cr.modify(|_, w| unsafe {
+                        w.$wave().bits(0b11);
+                        w.$ten().set_bit();
+                        w.$en().set_bit()
+                    });
+
+                    $CX {
+                        _enabled: PhantomData,
+                    }
+                }
+
                 /// TODO: The DAC does not seem to work unless `calibrate_buffer` has been callen
                 /// even when only using dac output internally
                 pub fn enable(self) -> $CX<MODE_BITS, Enabled> {
                     let dac = unsafe { &(*<$DAC>::ptr()) };
 
-                    dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(MODE_BITS) });
+                    dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(Self::mode_bits()) });
                     dac.dac_cr.modify(|_, w| w.$en().set_bit());
 
                     $CX {
@@ -204,10 +487,11 @@ macro_rules! dac_helper {
                 pub fn enable_generator(self, config: GeneratorConfig) -> $CX<MODE_BITS, WaveGenerator> {
                     let dac = unsafe { &(*<$DAC>::ptr()) };
 
-                    dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(MODE_BITS) });
+                    dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(Self::mode_bits()) });
                     dac.dac_cr.modify(|_, w| unsafe {
                         w.$wave().bits(config.mode);
                         w.$ten().set_bit();
+                        w.$tsel().bits(config.trigger as u8);
                         w.$mamp().bits(config.amp);
                         w.$en().set_bit()
                     });
@@ -216,6 +500,40 @@ macro_rules! dac_helper {
                         _enabled: PhantomData,
                     }
                 }
+
+                /// Enables the channel, the value written by `set_value` is output on the next trigger
+                pub fn enable_triggered(self, trigger: Trigger) -> $CX<MODE_BITS, Triggered> {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+
+                    dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(Self::mode_bits()) });
+                    dac.dac_cr.modify(|_, w| unsafe {
+                        w.$ten().set_bit();
+                        w.$tsel().bits(trigger as u8);
+                        w.$en().set_bit()
+                    });
+
+                    $CX {
+                        _enabled: PhantomData,
+                    }
+                }
+
+                /// Enables the channel with DMA requests, every trigger outputs the current value and
+                /// requests the next one in the format `F` from the DMA.
+                pub fn enable_dma<F: DataFormat>(self, trigger: Trigger) -> $CX<MODE_BITS, Dma<F>> {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+
+                    dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(Self::mode_bits()) });
+                    dac.dac_cr.modify(|_, w| unsafe {
+                        w.$ten().set_bit();
+                        w.$tsel().bits(trigger as u8);
+                        w.$dmaen().set_bit();
+                        w.$en().set_bit()
+                    });
+
+                    $CX {
+                        _enabled: PhantomData,
+                    }
+                }
             }
 
             impl<const MODE_BITS: u8, ED> $CX<MODE_BITS, ED> {
@@ -258,7 +576,16 @@ macro_rules! dac_helper {
                 pub fn disable(self) -> $CX<MODE_BITS, Disabled> {
                     let dac = unsafe { &(*<$DAC>::ptr()) };
                     dac.dac_cr.modify(|_, w| unsafe {
-                        w.$en().clear_bit().$wave().bits(0).$ten().clear_bit()
+                        w.$en()
+                            .clear_bit()
+                            .$wave()
+                            .bits(0)
+                            .$ten()
+                            .clear_bit()
+                            .$dmaen()
+                            .clear_bit()
+                            .$dmaudrie()
+                            .clear_bit()
                     });
 
                     $CX {
@@ -287,13 +614,68 @@ macro_rules! dac_helper {
                     let dac = unsafe { &(*<$DAC>::ptr()) };
                     dac.dac_swtrgr.write(|w| { w.$swtrig().set_bit() });
                 }
+
+                /// Steps the sawtooth when the software increment trigger is selected
+                pub fn increment(&mut self) {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+                    dac.dac_swtrgr.write(|w| { w.$swtrigb().set_bit() });
+                }
+            }
+
+            impl<const MODE_BITS: u8> $CX<MODE_BITS, Triggered> {
+                /// Outputs the last written value when the software trigger is selected
+                pub fn trigger(&mut self) {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+                    dac.dac_swtrgr.write(|w| { w.$swtrig().set_bit() });
+                }
+            }
+
+            impl<const MODE_BITS: u8, F: DataFormat> $CX<MODE_BITS, Dma<F>> {
+                /// Enables the DMA underrun interrupt
+                pub fn listen_underrun(&mut self) {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+                    dac.dac_cr.modify(|_, w| w.$dmaudrie().set_bit());
+                }
+
+                /// Disables the DMA underrun interrupt
+                pub fn unlisten_underrun(&mut self) {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+                    dac.dac_cr.modify(|_, w| w.$dmaudrie().clear_bit());
+                }
+
+                /// Returns true if a trigger occurred before the DMA delivered the previous value.
+                /// The DAC stops requesting data after an underrun.
+                pub fn is_underrun(&self) -> bool {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+                    dac.dac_sr.read().$dmaudr().bit_is_set()
+                }
+
+                /// Clears the underrun flag and restarts the DMA requests. The DMA stream has to be
+                /// restarted as well, since its position no longer matches the output.
+                pub fn clear_underrun(&mut self) {
+                    let dac = unsafe { &(*<$DAC>::ptr()) };
+                    dac.dac_sr.write(|w| w.$dmaudr().set_bit());
+                    dac.dac_cr.modify(|_, w| w.$dmaen().clear_bit());
+                    dac.dac_cr.modify(|_, w| w.$dmaen().set_bit());
+                }
+            }
+
+            unsafe impl<const MODE_BITS: u8, F: DataFormat> TargetAddress<MemoryToPeripheral> for $CX<MODE_BITS, Dma<F>> {
+                #[inline(always)]
+                fn address(&self) -> u32 {
+                    F::$address(unsafe { &(*<$DAC>::ptr()) })
+                }
+
+                type MemSize = F::Word;
+
+                const REQUEST_LINE: Option<u8> = Some(DmaMuxResources::$mux as u8);
             }
         )+
     };
 }
 
 macro_rules! dac {
-    ($($DAC:ident ch1: $DACxCH1:ident $(, ch2: $DACxCH2:ident)*)+) => {$(
+    ($($DAC:ident ch1: $DACxCH1:ident => $MUX1:ident $(, ch2: $DACxCH2:ident => $MUX2:ident)*)+) => {$(
         dac_helper!{$DACxCH1: $DAC: (
             en1,
             cen1,
@@ -306,7 +688,24 @@ macro_rules! dac {
             wave1,
             mamp1,
             ten1,
-            swtrig1
+            swtrig1,
+            tsel1,
+            dmaen1,
+            dmaudrie1,
+            dmaudr1,
+            ch1_address,
+            $MUX1,
+            swtrigb1,
+            dac_str1,
+            strstdata1,
+            stdir1,
+            stincdata1,
+            strsttrigsel1,
+            stinctrigsel1,
+            dac_shsr1,
+            tsample1,
+            thold1,
+            trefresh1
         ),
         $($DACxCH2: $DAC: (
             en2,
@@ -320,8 +719,42 @@ macro_rules! dac {
             wave2,
             mamp2,
             ten2,
-            swtrig2
+            swtrig2,
+            tsel2,
+            dmaen2,
+            dmaudrie2,
+            dmaudr2,
+            ch2_address,
+            $MUX2,
+            swtrigb2,
+            dac_str2,
+            strstdata2,
+            stdir2,
+            stincdata2,
+            strsttrigsel2,
+            stinctrigsel2,
+            dac_shsr2,
+            tsample2,
+            thold2,
+            trefresh2
         ),)*}
+
+        $(
+            /// Both channels are updated from the dual data holding register on the trigger of
+            /// channel 1, channel 2 has to use the same trigger.
+            unsafe impl<const MODE_BITS1: u8, const MODE_BITS2: u8, F: DataFormat> TargetAddress<MemoryToPeripheral>
+                for ($DACxCH1<MODE_BITS1, Dma<F>>, $DACxCH2<MODE_BITS2, Triggered>)
+            {
+                #[inline(always)]
+                fn address(&self) -> u32 {
+                    F::dual_address(unsafe { &(*<$DAC>::ptr()) })
+                }
+
+                type MemSize = F::DualWord;
+
+                const REQUEST_LINE: Option<u8> = Some(DmaMuxResources::$MUX1 as u8);
+            }
+        )*
     )+};
 }
 
@@ -347,8 +780,8 @@ macro_rules! impl_dac_ext {
 impl_dac_ext!(DAC1, DAC2, DAC3, DAC4,);
 
 dac!(
-    DAC1 ch1: Dac1Ch1, ch2: Dac1Ch2
-    DAC2 ch1: Dac2Ch1
-    DAC3 ch1: Dac3Ch1, ch2: Dac3Ch2
-    DAC4 ch1: Dac4Ch1, ch2: Dac4Ch2
+    DAC1 ch1: Dac1Ch1 => DAC1_CH1, ch2: Dac1Ch2 => DAC1_CH2
+    DAC2 ch1: Dac2Ch1 => DAC2_CH1
+    DAC3 ch1: Dac3Ch1 => DAC3_CH1, ch2: Dac3Ch2 => DAC3_CH2
+    DAC4 ch1: Dac4Ch1 => DAC4_CH1, ch2: Dac4Ch2 => DAC4_CH2
 );
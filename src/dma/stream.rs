@@ -476,6 +476,13 @@ macro_rules! dma_stream {
                     });
                 }
 
+                #[inline(always)]
+                fn set_mem2mem(&mut self, mem2mem: bool) {
+                    //NOTE(unsafe) We only access the registers that belongs to the StreamX
+                    let dma = unsafe { &*I::ptr() };
+                    dma.$ccr.modify(|_, w| w.mem2mem().bit(mem2mem));
+                }
+
                 #[inline(always)]
                 fn set_circular_buffer(&mut self, circular_buffer: bool) {
                     //NOTE(unsafe) We only access the registers that belongs to the StreamX
@@ -156,6 +156,10 @@ pub trait Stream: Sealed {
     /// Set the direction (dir) of the DMA stream.
     fn set_direction(&mut self, direction: DmaDirection);
 
+    /// Enable/disable memory-to-memory mode (mem2mem), in which the stream does not wait for a
+    /// request. Used for memory-to-peripheral transfers to peripherals without a request line.
+    fn set_mem2mem(&mut self, mem2mem: bool);
+
     /// Enable/disable circular buffering for the DMA stream.
     fn set_circular_buffer(&mut self, circular_buffer: bool);
 }
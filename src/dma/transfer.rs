@@ -98,6 +98,10 @@ macro_rules! transfer_def {
                 // Set the DMAMUX request line if needed
                 if let Some(request_line) = PERIPHERAL::REQUEST_LINE {
                     stream.set_request_line(request_line);
+                } else if DIR::direction() == DmaDirection::MemoryToPeripheral {
+                    // The peripheral accepts data at any time, so let the stream run
+                    // without waiting for requests
+                    stream.set_mem2mem(true);
                 }
 
                 let mut transfer = Self {
@@ -195,6 +199,11 @@ where
         result
     }
 
+    /// Returns the buffer, the stream has to be paused before its contents are used.
+    pub(crate) fn buffer(&self) -> &BUF {
+        &self.buf
+    }
+
     /// Starts the transfer, the closure will be executed right after enabling
     /// the stream.
     pub fn start<F>(&mut self, f: F)
@@ -510,6 +519,46 @@ impl_adc_overrun!(ADC3,);
 ))]
 impl_adc_overrun!(ADC4, ADC5,);
 
+macro_rules! impl_dual_adc_overrun {
+    ($($master:ident, $slave:ident, )*) => {$(
+        impl<STREAM, CONFIG, BUF> CircTransfer<STREAM, crate::adc::DualAdc<crate::stm32::$master, crate::stm32::$slave, crate::adc::DMA>, BUF>
+        where
+            STREAM: Stream<Config = CONFIG>,
+            BUF: WriteBuffer + Deref,
+            <BUF as Deref>::Target: Index<Range<usize>, Output = [u32]> {
+            /// This is set when the master or slave finishes a conversion before the DMA has had time to transfer the previous value
+            pub fn get_overrun_flag(&self) -> bool {
+                self.transfer.peripheral.get_overrun_flag()
+            }
+
+            pub fn clear_overrun_flag(&mut self) {
+                self.transfer.peripheral.clear_overrun_flag();
+            }
+        }
+    )*};
+}
+
+#[cfg(any(
+    feature = "stm32g431",
+    feature = "stm32g441",
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484",
+    feature = "stm32g491",
+    feature = "stm32g4a1",
+))]
+impl_dual_adc_overrun!(ADC1, ADC2,);
+
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484",
+))]
+impl_dual_adc_overrun!(ADC3, ADC4,);
+
 macro_rules! impl_serial_timeout {
     ($($uart:ident, )*) => {$(
         impl<STREAM, BUF, Pin> CircTransfer<STREAM, crate::serial::Rx<crate::stm32::$uart, Pin, crate::serial::DMA>, BUF>
@@ -43,8 +43,10 @@ pub enum Event {
     UART5 = 35,
     LPUART1 = 36,
     LPTIM1 = 37,
-    PVM1 = 40,
-    PVM2 = 41,
+    PVM1 = 38,
+    PVM2 = 39,
+    PVM3 = 40,
+    PVM4 = 41,
     I2C4 = 42,
     UCPD1 = 43,
 }
@@ -71,6 +73,12 @@ impl Event {
             _ => unreachable!(),
         }
     }
+
+    /// Returns true if the line has edge selection and a pending register (configurable line).
+    /// The remaining lines are direct lines which are driven by their peripheral.
+    pub(crate) fn is_configurable(self) -> bool {
+        matches!(self as u8, 0..=17 | 19..=22 | 29..=33 | 38..=41)
+    }
 }
 
 pub trait ExtiExt {
@@ -83,19 +91,30 @@ pub trait ExtiExt {
 
 impl ExtiExt for EXTI {
     fn listen(&self, ev: Event, edge: SignalEdge) {
-        let line = ev as u8;
-        assert!(line <= 17);
-        let mask = 1 << line;
-        match edge {
-            SignalEdge::Rising => {
-                self.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
-            }
-            SignalEdge::Falling => {
-                self.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        assert!(ev.is_configurable());
+        let (rising, falling) = match edge {
+            SignalEdge::Rising => (true, false),
+            SignalEdge::Falling => (false, true),
+            SignalEdge::RisingFalling => (true, true),
+        };
+        match ev as u8 {
+            line if line < 32 => {
+                let mask = 1 << line;
+                if rising {
+                    self.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+                }
+                if falling {
+                    self.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+                }
             }
-            SignalEdge::RisingFalling => {
-                self.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
-                self.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+            line => {
+                let mask = 1 << (line - 32);
+                if rising {
+                    self.rtsr2.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+                }
+                if falling {
+                    self.ftsr2.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+                }
             }
         }
         self.wakeup(ev);
@@ -114,35 +133,44 @@ impl ExtiExt for EXTI {
 
     fn unlisten(&self, ev: Event) {
         self.unpend(ev);
+        let configurable = ev.is_configurable();
         match ev as u8 {
             line if line < 32 => {
                 let mask = !(1 << line);
                 self.imr1.modify(|r, w| unsafe { w.bits(r.bits() & mask) });
-                if line <= 18 {
+                if configurable {
                     self.rtsr1.modify(|r, w| unsafe { w.bits(r.bits() & mask) });
                     self.ftsr1.modify(|r, w| unsafe { w.bits(r.bits() & mask) });
                 }
             }
             line => {
                 let mask = !(1 << (line - 32));
-                self.imr2.modify(|r, w| unsafe { w.bits(r.bits() & mask) })
+                self.imr2.modify(|r, w| unsafe { w.bits(r.bits() & mask) });
+                if configurable {
+                    self.rtsr2.modify(|r, w| unsafe { w.bits(r.bits() & mask) });
+                    self.ftsr2.modify(|r, w| unsafe { w.bits(r.bits() & mask) });
+                }
             }
         }
     }
 
     fn is_pending(&self, ev: Event) -> bool {
-        let line = ev as u8;
-        if line > 18 {
+        if !ev.is_configurable() {
             return false;
         }
-        let mask = 1 << line;
-        self.pr1.read().bits() & mask != 0
+        match ev as u8 {
+            line if line < 32 => self.pr1.read().bits() & (1 << line) != 0,
+            line => self.pr2.read().bits() & (1 << (line - 32)) != 0,
+        }
     }
 
     fn unpend(&self, ev: Event) {
-        let line = ev as u8;
-        if line <= 18 {
-            self.pr1.modify(|_, w| unsafe { w.bits(1 << line) });
+        if !ev.is_configurable() {
+            return;
+        }
+        match ev as u8 {
+            line if line < 32 => self.pr1.write(|w| unsafe { w.bits(1 << line) }),
+            line => self.pr2.write(|w| unsafe { w.bits(1 << (line - 32)) }),
         }
     }
 }
@@ -1,9 +1,25 @@
 //! I2C
 //!
 //! File containing the implementations of the embedded-hal [`I2C API`](hal_api::i2c)
+//!
+//! Besides the blocking API, [`I2cObj::write_dma`], [`I2cObj::read_dma`] and
+//! [`I2cObj::write_read_dma`] start a controller transaction of which the data is moved by DMA.
+//! The returned [`DmaTransaction`] hands the buffers back once it has finished:
+//!
+//! ```rust,ignore
+//!   let mut transaction = i2c.write_read_dma(0x50, streams.0, &WORD_ADDRESS, streams.1, buffer, DmaConfig::default());
+//!   transaction.wait().unwrap();
+//!   let (i2c, tx_stream, _, rx_stream, buffer) = transaction.free();
+//! ```
 
+use hal_api::delay::DelayNs;
+use hal_api::digital::{InputPin, OutputPin};
 use hal_api::i2c::{SevenBitAddress, TenBitAddress, I2c, Operation, ErrorType, ErrorKind, NoAcknowledgeSource};
 
+use crate::dma::{
+    config::DmaConfig, mux::DmaMuxResources, traits::{Stream, TargetAddress},
+    transfer::{ConstTransfer, MutTransfer, Transfer}, MemoryToPeripheral, PeripheralToMemory,
+};
 use crate::gpio::{gpioa::*, gpiob::*, gpioc::*, gpiof::*};
 #[cfg(any(
 feature = "stm32g471",
@@ -24,15 +40,20 @@ feature = "stm32g484"
 ))]
 use crate::stm32::I2C4;
 use crate::stm32::{I2C1, I2C2, I2C3, RCC};
-use crate::time::Hertz;
+use crate::stm32::i2c1::RegisterBlock;
+use crate::time::{cycles_us, Hertz, MicroSecond};
 use core::cmp;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use embedded_dma::{ReadBuffer, WriteBuffer};
 
 /// I2C bus configuration.
 pub struct Config {
     speed: Option<Hertz>,
     timing: Option<u32>,
-    analog_filter: bool,
-    digital_filter: u8,
+    timeout: Option<MicroSecond>,
+    pub(crate) analog_filter: bool,
+    pub(crate) digital_filter: u8,
 }
 
 impl Config {
@@ -44,6 +65,7 @@ impl Config {
         Config {
             speed: Some(speed.into()),
             timing: None,
+            timeout: None,
             analog_filter: true,
             digital_filter: 0,
         }
@@ -63,6 +85,7 @@ impl Config {
         Config {
             timing: Some(timing),
             speed: None,
+            timeout: None,
             analog_filter: true,
             digital_filter: 0,
         }
@@ -81,7 +104,25 @@ impl Config {
         self
     }
 
-    fn timing_bits(&self, i2c_clk: Hertz) -> u32 {
+    /// Enables the SCL low timeout: a transaction fails with [`Error::Timeout`] when SCL is held
+    /// low for longer than `timeout`, e.g. by a target that hangs. The peripheral is reset to
+    /// release the bus.
+    ///
+    /// The timeout is rounded up to a multiple of 2048 I2C clock cycles, up to 4096 multiples.
+    pub fn scl_timeout(mut self, timeout: MicroSecond) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub(crate) fn timeout_bits(&self, i2c_clk: Hertz) -> Option<u16> {
+        // tTIMEOUT = (TIMEOUTA + 1) * 2048 * tI2CCLK
+        self.timeout.map(|timeout| {
+            let cycles = cycles_us(timeout, i2c_clk);
+            cmp::min(cycles.div_ceil(2048).saturating_sub(1), 0xfff) as u16
+        })
+    }
+
+    pub(crate) fn timing_bits(&self, i2c_clk: Hertz) -> u32 {
         if let Some(bits) = self.timing {
             return bits;
         }
@@ -107,7 +148,7 @@ impl Config {
 
 /// I2C abstraction
 pub struct I2cObj<I2C, SDA, SCL> {
-    i2c: I2C,
+    pub(crate) i2c: I2C,
     sda: SDA,
     scl: SCL,
 }
@@ -125,6 +166,33 @@ pub trait I2cExt<I2C> {
             SCL: SCLPin<I2C>;
 }
 
+/// I2C error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Misplaced START or STOP condition
+    Bus,
+    /// Another controller took over the bus
+    ArbitrationLoss,
+    /// The address or a data byte was not acknowledged
+    NoAcknowledge(NoAcknowledgeSource),
+    /// A received byte was not read in time, only with clock stretching disabled
+    Overrun,
+    /// SCL was held low for longer than the [`Config::scl_timeout`]
+    Timeout,
+}
+
+impl hal_api::i2c::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match *self {
+            Error::Bus => ErrorKind::Bus,
+            Error::ArbitrationLoss => ErrorKind::ArbitrationLoss,
+            Error::NoAcknowledge(source) => ErrorKind::NoAcknowledge(source),
+            Error::Overrun => ErrorKind::Overrun,
+            Error::Timeout => ErrorKind::Other,
+        }
+    }
+}
+
 /// Sequence to flush the TXDR register. This resets the TXIS and TXE flags
 macro_rules! flush_txdr {
     ($i2c:expr) => {
@@ -141,7 +209,7 @@ macro_rules! flush_txdr {
 }
 
 macro_rules! busy_wait {
-    ($i2c:expr, $flag:ident, $variant:ident) => {
+    ($i2c:expr, $flag:ident, $variant:ident, $nack_source:expr) => {
         loop {
             let isr = $i2c.isr.read();
 
@@ -149,14 +217,17 @@ macro_rules! busy_wait {
                 break;
             } else if isr.berr().bit_is_set() {
                 $i2c.icr.write(|w| w.berrcf().set_bit());
-                return Err(ErrorKind::Bus);
+                return Err(Error::Bus);
             } else if isr.arlo().bit_is_set() {
                 $i2c.icr.write(|w| w.arlocf().set_bit());
-                return Err(ErrorKind::ArbitrationLoss);
+                return Err(Error::ArbitrationLoss);
+            } else if isr.timeout().bit_is_set() {
+                $i2c.icr.write(|w| w.timoutcf().set_bit());
+                software_reset($i2c);
+                return Err(Error::Timeout);
             } else if isr.nackf().bit_is_set() {
-                $i2c.icr.write(|w| w.stopcf().set_bit().nackcf().set_bit());
-                flush_txdr!($i2c);
-                return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)); //TODO: Split into both reasons.
+                end_after_nack($i2c);
+                return Err(Error::NoAcknowledge($nack_source));
             } else {
                 // try again
             }
@@ -164,8 +235,489 @@ macro_rules! busy_wait {
     };
 }
 
+/// Waits for the STOP condition that the hardware sends after a NACK, then clears both flags.
+///
+/// Clearing STOPF together with NACKF would not work, as the STOP condition only follows the
+/// NACK and would leave STOPF set for the next transaction.
+pub(crate) fn end_after_nack(i2c: &RegisterBlock) {
+    loop {
+        let isr = i2c.isr.read();
+        // Give up on a bus error, the flag is reported by the next transaction
+        if isr.stopf().bit_is_set()
+            || isr.berr().bit_is_set()
+            || isr.arlo().bit_is_set()
+            || isr.timeout().bit_is_set()
+        {
+            break;
+        }
+    }
+    i2c.icr.write(|w| w.stopcf().set_bit().nackcf().set_bit());
+    flush_txdr!(i2c);
+}
+
+/// Resets the communication of the peripheral and releases SCL and SDA, the configuration is kept
+pub(crate) fn software_reset(i2c: &RegisterBlock) {
+    i2c.cr1.modify(|_, w| w.pe().clear_bit());
+    // PE has to stay low for at least 3 APB clock cycles
+    while i2c.cr1.read().pe().bit_is_set() {}
+    i2c.cr1.modify(|_, w| w.pe().set_bit());
+}
+
+/// Target address as programmed in CR2
+#[derive(Clone, Copy)]
+pub(crate) struct Address {
+    sadd: u16,
+    ten_bit: bool,
+}
+
+impl Address {
+    pub(crate) fn seven_bit(address: SevenBitAddress) -> Self {
+        Address { sadd: (address as u16) << 1, ten_bit: false }
+    }
+
+    pub(crate) fn ten_bit(address: TenBitAddress) -> Self {
+        Address { sadd: address & 0x3ff, ten_bit: true }
+    }
+}
+
+/// Transfer in one direction of any length, split in chunks of at most 255 bytes using RELOAD
+struct ChunkedTransfer<'a> {
+    i2c: &'a RegisterBlock,
+    /// Total number of bytes in the transfer
+    len: usize,
+    /// Bytes left in the current chunk
+    chunk: usize,
+    /// Bytes left after the current chunk
+    remaining: usize,
+    /// Whether the transfer ends with a STOP condition, instead of waiting for a repeated START
+    last: bool,
+    /// Whether the address has been acknowledged
+    addressed: bool,
+}
+
+impl<'a> ChunkedTransfer<'a> {
+    fn start(i2c: &'a RegisterBlock, address: Address, read: bool, len: usize, last: bool) -> Self {
+        let mut transfer = ChunkedTransfer { i2c, len, chunk: 0, remaining: len, last, addressed: false };
+        let nbytes = transfer.next_chunk();
+        let (reload, autoend) = (transfer.reload(), transfer.autoend());
+
+        // The START bit can be set even if the bus is BUSY or I2C is in slave mode.
+        i2c.cr2.write(|w| {
+            w
+                // Start transfer
+                .start().set_bit()
+                // Set address to transfer to/from
+                .sadd().bits(address.sadd)
+                .add10().bit(address.ten_bit)
+                // Set transfer direction
+                .rd_wrn().bit(read)
+                // Set number of bytes to transfer
+                .nbytes().bits(nbytes)
+                // Continue with the next chunk after this one
+                .reload().bit(reload)
+                // Automatic end mode after the last chunk
+                .autoend().bit(autoend)
+        });
+
+        transfer
+    }
+
+    fn next_chunk(&mut self) -> u8 {
+        self.chunk = cmp::min(self.remaining, 255);
+        self.remaining -= self.chunk;
+        self.chunk as u8
+    }
+
+    fn reload(&self) -> bool {
+        self.remaining > 0
+    }
+
+    fn autoend(&self) -> bool {
+        self.last && self.remaining == 0
+    }
+
+    fn nack_source(&self) -> NoAcknowledgeSource {
+        if self.addressed {
+            NoAcknowledgeSource::Data
+        } else {
+            NoAcknowledgeSource::Address
+        }
+    }
+
+    /// Continues with the next chunk once the current one has been transferred
+    fn next_byte(&mut self) -> Result<(), Error> {
+        if self.chunk == 0 {
+            busy_wait!(self.i2c, tcr, bit_is_set, self.nack_source());
+
+            let nbytes = self.next_chunk();
+            let (reload, autoend) = (self.reload(), self.autoend());
+            self.i2c.cr2.modify(|_, w| w.nbytes().bits(nbytes).reload().bit(reload).autoend().bit(autoend));
+        }
+        self.chunk -= 1;
+
+        Ok(())
+    }
+
+    fn write(&mut self, byte: u8) -> Result<(), Error> {
+        self.next_byte()?;
+
+        // Wait until we are allowed to send data
+        // (START has been ACKed or last byte went through)
+        busy_wait!(self.i2c, txis, bit_is_set, self.nack_source());
+        self.addressed = true;
+
+        // Put byte on the wire
+        self.i2c.txdr.write(|w| w.txdata().bits(byte));
+
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<u8, Error> {
+        self.next_byte()?;
+
+        // Wait until we have received something
+        busy_wait!(self.i2c, rxne, bit_is_set, self.nack_source());
+        self.addressed = true;
+
+        Ok(self.i2c.rxdr.read().rxdata().bits())
+    }
+
+    /// Waits for the STOP condition, or until a repeated START can be sent
+    fn finish(mut self) -> Result<(), Error> {
+        // With all bytes transferred, a NACK can only refer to the last data byte. Without any
+        // data bytes it refers to the address.
+        self.addressed |= self.len > 0 && self.chunk == 0 && self.remaining == 0;
+
+        if self.last {
+            busy_wait!(self.i2c, stopf, bit_is_set, self.nack_source());
+            self.i2c.icr.write(|w| w.stopcf().set_bit());
+        } else {
+            busy_wait!(self.i2c, tc, bit_is_set, self.nack_source());
+        }
+
+        Ok(())
+    }
+}
+
+/// Executes `operations` as one transaction: consecutive operations of the same kind are merged
+/// into one transfer, a repeated START is sent when the direction changes and the transaction
+/// ends with a STOP condition.
+pub(crate) fn transaction(
+    i2c: &RegisterBlock,
+    address: Address,
+    operations: &mut [Operation<'_>],
+) -> Result<(), Error> {
+    // Wait for any previous address sequence to end automatically.
+    // This could be up to 50% of a bus cycle (ie. up to 0.5/freq)
+    while i2c.cr2.read().start().bit_is_set() {}
+
+    let mut first = 0;
+    while first < operations.len() {
+        let read = matches!(operations[first], Operation::Read(_));
+        let mut end = first;
+        let mut len = 0;
+        while let Some(operation) = operations.get(end) {
+            match operation {
+                Operation::Read(buffer) if read => len += buffer.len(),
+                Operation::Write(bytes) if !read => len += bytes.len(),
+                _ => break,
+            }
+            end += 1;
+        }
+
+        let mut transfer = ChunkedTransfer::start(i2c, address, read, len, end == operations.len());
+        for operation in &mut operations[first..end] {
+            match operation {
+                Operation::Read(buffer) => {
+                    for byte in buffer.iter_mut() {
+                        *byte = transfer.read()?;
+                    }
+                }
+                Operation::Write(bytes) => {
+                    for byte in bytes.iter() {
+                        transfer.write(*byte)?;
+                    }
+                }
+            }
+        }
+        transfer.finish()?;
+
+        first = end;
+    }
+
+    Ok(())
+}
+
+/// Releases a target that holds SDA low after a transaction was interrupted, e.g. by a reset of
+/// the controller. Returns whether SDA is released.
+///
+/// The pins have to be configured as open drain outputs, so the peripheral has to be released
+/// first. SCL is clocked up to nine times at about 100kHz until the target releases SDA, after
+/// which a STOP condition is generated.
+///
+/// ```rust,ignore
+///   let (i2c, sda, scl) = i2c.release();
+///   let (mut sda, mut scl) = (sda.into_open_drain_output(), scl.into_open_drain_output());
+///   recover_bus(&mut sda, &mut scl, &mut delay);
+///   let i2c = i2c.i2c(sda.into_alternate_open_drain(), scl.into_alternate_open_drain(), config, &mut rcc);
+/// ```
+pub fn recover_bus<SDA, SCL, D>(sda: &mut SDA, scl: &mut SCL, delay: &mut D) -> bool
+where
+    SDA: InputPin + OutputPin,
+    SCL: OutputPin,
+    D: DelayNs,
+{
+    // Half of an SCL period at 100kHz
+    const HALF_PERIOD_US: u32 = 5;
+
+    sda.set_high().ok();
+    scl.set_high().ok();
+    delay.delay_us(HALF_PERIOD_US);
+
+    for _ in 0..9 {
+        if sda.is_high().unwrap_or(false) {
+            break;
+        }
+        scl.set_low().ok();
+        delay.delay_us(HALF_PERIOD_US);
+        scl.set_high().ok();
+        delay.delay_us(HALF_PERIOD_US);
+    }
+
+    // STOP condition: SDA rises while SCL is high
+    scl.set_low().ok();
+    sda.set_low().ok();
+    delay.delay_us(HALF_PERIOD_US);
+    scl.set_high().ok();
+    delay.delay_us(HALF_PERIOD_US);
+    sda.set_high().ok();
+    delay.delay_us(HALF_PERIOD_US);
+
+    sda.is_high().unwrap_or(false)
+}
+
+/// DMA target for the transmit data register of an I2C peripheral, used by [`I2cObj::write_dma`]
+/// and [`I2cObj::write_read_dma`]
+pub struct TxDma<I2C> {
+    _i2c: PhantomData<I2C>,
+}
+
+/// DMA target for the receive data register of an I2C peripheral, used by [`I2cObj::read_dma`]
+/// and [`I2cObj::write_read_dma`]
+pub struct RxDma<I2C> {
+    _i2c: PhantomData<I2C>,
+}
+
+/// Controller transaction of which the data is moved by DMA
+///
+/// The I2C peripheral has to be serviced with [`poll`](Self::poll) to split transfers of more
+/// than 255 bytes and to switch from writing to reading, either from a loop or from the I2C event
+/// and error interrupts after calling [`listen`](Self::listen). When the transaction has
+/// finished, [`free`](Self::free) hands the I2C peripheral, DMA streams and buffers back. Calling
+/// it earlier aborts the transaction.
+pub struct DmaTransaction<I2C, SDA, SCL, TX, RX> {
+    i2c: I2cObj<I2C, SDA, SCL>,
+    tx: TX,
+    rx: RX,
+    address: SevenBitAddress,
+    write_remaining: usize,
+    read_remaining: usize,
+    reading: bool,
+    /// Whether the transaction has ended, either with a STOP condition or with an error
+    finished: bool,
+}
+
+impl<I2C, SDA, SCL, TX, RX> DmaTransaction<I2C, SDA, SCL, TX, RX>
+where
+    I2C: Deref<Target = RegisterBlock>,
+{
+    /// Sets up NBYTES, RELOAD and AUTOEND for the next chunk of at most 255 bytes of the current
+    /// direction, optionally generating a (repeated) START condition
+    fn next_chunk(&mut self, start: bool) {
+        let remaining = if self.reading {
+            &mut self.read_remaining
+        } else {
+            &mut self.write_remaining
+        };
+        let nbytes = cmp::min(*remaining, 255);
+        *remaining -= nbytes;
+        let reload = *remaining > 0;
+        // A read after the write is started by a repeated START, so the write must not end in a STOP
+        let autoend = self.reading || self.read_remaining == 0;
+
+        let (address, reading) = (self.address, self.reading);
+        if start {
+            // Wait for any previous address sequence to end automatically
+            while self.i2c.i2c.cr2.read().start().bit_is_set() {}
+
+            // Writing CR2 as a whole also clears the 10-bit addressing bits of earlier transfers
+            self.i2c.i2c.cr2.write(|w| {
+                w
+                    // Start transfer
+                    .start().set_bit()
+                    // Set address to transfer to/from
+                    .sadd().bits((address << 1) as u16)
+                    // Set transfer direction
+                    .rd_wrn().bit(reading)
+                    // Set number of bytes to transfer
+                    .nbytes().bits(nbytes as u8)
+                    // Continue with the next chunk after this one
+                    .reload().bit(reload)
+                    // Automatic end mode after the last chunk
+                    .autoend().bit(autoend)
+            });
+        } else {
+            self.i2c.i2c.cr2.modify(|_, w| {
+                w.nbytes().bits(nbytes as u8).reload().bit(reload).autoend().bit(autoend)
+            });
+        }
+    }
+
+    /// Services the I2C peripheral, returns `Ok` once the STOP condition has been sent
+    ///
+    /// After an error the transaction is aborted and only [`free`](Self::free) can be used.
+    pub fn poll(&mut self) -> nb::Result<(), Error> {
+        let result = self.service();
+        if !matches!(result, Err(nb::Error::WouldBlock)) {
+            self.finished = true;
+        }
+        result
+    }
+
+    fn service(&mut self) -> nb::Result<(), Error> {
+        let isr = self.i2c.i2c.isr.read();
+
+        if isr.berr().bit_is_set() {
+            self.i2c.i2c.icr.write(|w| w.berrcf().set_bit());
+            Err(nb::Error::Other(Error::Bus))
+        } else if isr.arlo().bit_is_set() {
+            self.i2c.i2c.icr.write(|w| w.arlocf().set_bit());
+            Err(nb::Error::Other(Error::ArbitrationLoss))
+        } else if isr.timeout().bit_is_set() {
+            self.i2c.i2c.icr.write(|w| w.timoutcf().set_bit());
+            software_reset(&self.i2c.i2c);
+            Err(nb::Error::Other(Error::Timeout))
+        } else if isr.nackf().bit_is_set() {
+            // The STOP condition is sent by hardware after the NACK
+            end_after_nack(&self.i2c.i2c);
+            Err(nb::Error::Other(Error::NoAcknowledge(NoAcknowledgeSource::Unknown)))
+        } else if isr.stopf().bit_is_set() {
+            self.i2c.i2c.icr.write(|w| w.stopcf().set_bit());
+            Ok(())
+        } else if isr.tcr().bit_is_set() {
+            self.next_chunk(false);
+            Err(nb::Error::WouldBlock)
+        } else if isr.tc().bit_is_set() {
+            // The write has finished, continue with the read after a repeated START
+            self.reading = true;
+            self.next_chunk(true);
+            Err(nb::Error::WouldBlock)
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Blocks until the transaction has finished
+    pub fn wait(&mut self) -> Result<(), Error> {
+        nb::block!(self.poll())
+    }
+
+    /// Enables the interrupts needed to service the transaction with [`poll`](Self::poll)
+    pub fn listen(&mut self) {
+        self.i2c.i2c.cr1.modify(|_, w| {
+            w.tcie().set_bit().stopie().set_bit().nackie().set_bit().errie().set_bit()
+        });
+    }
+
+    /// Disables the interrupts enabled by [`listen`](Self::listen)
+    pub fn unlisten(&mut self) {
+        self.i2c.i2c.cr1.modify(|_, w| {
+            w.tcie().clear_bit().stopie().clear_bit().nackie().clear_bit().errie().clear_bit()
+        });
+    }
+
+    /// Disables the DMA requests and interrupts of the I2C peripheral, resetting it to abort a
+    /// transaction that hasn't finished yet
+    fn release_i2c(mut self) -> (I2cObj<I2C, SDA, SCL>, TX, RX) {
+        self.unlisten();
+        self.i2c.i2c.cr1.modify(|_, w| w.txdmaen().clear_bit().rxdmaen().clear_bit());
+        if !self.finished {
+            software_reset(&self.i2c.i2c);
+        }
+
+        (self.i2c, self.tx, self.rx)
+    }
+}
+
+impl<I2C, SDA, SCL, STREAM, BUF>
+    DmaTransaction<I2C, SDA, SCL, Transfer<STREAM, TxDma<I2C>, MemoryToPeripheral, BUF, ConstTransfer>, ()>
+where
+    I2C: Deref<Target = RegisterBlock>,
+    STREAM: Stream,
+    TxDma<I2C>: TargetAddress<MemoryToPeripheral>,
+{
+    /// Returns the I2C peripheral, the DMA stream and the buffer
+    ///
+    /// A transaction that hasn't finished yet is aborted by resetting the I2C peripheral, which
+    /// releases SCL and SDA without sending a STOP condition.
+    pub fn free(self) -> (I2cObj<I2C, SDA, SCL>, STREAM, BUF) {
+        let (i2c, tx, _) = self.release_i2c();
+        let (stream, _, buf) = tx.free();
+
+        (i2c, stream, buf)
+    }
+}
+
+impl<I2C, SDA, SCL, STREAM, BUF>
+    DmaTransaction<I2C, SDA, SCL, (), Transfer<STREAM, RxDma<I2C>, PeripheralToMemory, BUF, MutTransfer>>
+where
+    I2C: Deref<Target = RegisterBlock>,
+    STREAM: Stream,
+    RxDma<I2C>: TargetAddress<PeripheralToMemory>,
+{
+    /// Returns the I2C peripheral, the DMA stream and the buffer
+    ///
+    /// A transaction that hasn't finished yet is aborted by resetting the I2C peripheral, which
+    /// releases SCL and SDA without sending a STOP condition.
+    pub fn free(self) -> (I2cObj<I2C, SDA, SCL>, STREAM, BUF) {
+        let (i2c, _, rx) = self.release_i2c();
+        let (stream, _, buf) = rx.free();
+
+        (i2c, stream, buf)
+    }
+}
+
+impl<I2C, SDA, SCL, TXSTREAM, TXBUF, RXSTREAM, RXBUF>
+    DmaTransaction<
+        I2C,
+        SDA,
+        SCL,
+        Transfer<TXSTREAM, TxDma<I2C>, MemoryToPeripheral, TXBUF, ConstTransfer>,
+        Transfer<RXSTREAM, RxDma<I2C>, PeripheralToMemory, RXBUF, MutTransfer>,
+    >
+where
+    I2C: Deref<Target = RegisterBlock>,
+    TXSTREAM: Stream,
+    RXSTREAM: Stream,
+    TxDma<I2C>: TargetAddress<MemoryToPeripheral>,
+    RxDma<I2C>: TargetAddress<PeripheralToMemory>,
+{
+    /// Returns the I2C peripheral, the DMA streams and the buffers
+    ///
+    /// A transaction that hasn't finished yet is aborted by resetting the I2C peripheral, which
+    /// releases SCL and SDA without sending a STOP condition.
+    #[allow(clippy::type_complexity)]
+    pub fn free(self) -> (I2cObj<I2C, SDA, SCL>, TXSTREAM, TXBUF, RXSTREAM, RXBUF) {
+        let (i2c, tx, rx) = self.release_i2c();
+        let (tx_stream, _, tx_buf) = tx.free();
+        let (rx_stream, _, rx_buf) = rx.free();
+
+        (i2c, tx_stream, tx_buf, rx_stream, rx_buf)
+    }
+}
+
 macro_rules! i2c {
-    ($I2CX:ident, $i2cx:ident,
+    ($I2CX:ident, $i2cx:ident, $dmamux_rx:ident, $dmamux_tx:ident,
         sda: [ $($( #[ $pmetasda:meta ] )* $PSDA:ty,)+ ],
         scl: [ $($( #[ $pmetascl:meta ] )* $PSCL:ty,)+ ],
     ) => {
@@ -219,6 +771,11 @@ macro_rules! i2c {
                 let timing_bits = config.timing_bits(<$I2CX as RccBus>::Bus::get_frequency(&rcc.clocks));
                 i2c.timingr.write(|w| unsafe { w.bits(timing_bits) });
 
+                // Setup the SCL low timeout
+                if let Some(timeout_bits) = config.timeout_bits(<$I2CX as RccBus>::Bus::get_frequency(&rcc.clocks)) {
+                    i2c.timeoutr.write(|w| w.timeouta().bits(timeout_bits).tidle().clear_bit().timouten().set_bit());
+                }
+
                 // Enable the I2C processing
                 i2c.cr1.modify(|_, w| {
                     w.pe()
@@ -243,6 +800,153 @@ macro_rules! i2c {
 
                 (self.i2c, self.sda, self.scl)
             }
+
+            /// Starts a write of the whole buffer to the device at `address`, fed by DMA
+            ///
+            /// The memory increment of the DMA configuration is always enabled.
+            pub fn write_dma<STREAM, BUF>(
+                self,
+                address: SevenBitAddress,
+                stream: STREAM,
+                buf: BUF,
+                config: DmaConfig,
+            ) -> DmaTransaction<$I2CX, SDA, SCL, Transfer<STREAM, TxDma<$I2CX>, MemoryToPeripheral, BUF, ConstTransfer>, ()>
+            where
+                STREAM: Stream<Config = DmaConfig>,
+                BUF: ReadBuffer<Word = u8>,
+            {
+                let (_, len) = unsafe { buf.read_buffer() };
+                assert!(len > 0);
+
+                let mut tx = Transfer::init_const(stream, TxDma { _i2c: PhantomData }, buf, config.memory_increment(true));
+                self.i2c.cr1.modify(|_, w| w.txdmaen().set_bit());
+                tx.start(|_| {});
+
+                let mut transaction = DmaTransaction {
+                    i2c: self,
+                    tx,
+                    rx: (),
+                    address,
+                    write_remaining: len,
+                    read_remaining: 0,
+                    reading: false,
+                    finished: false,
+                };
+                transaction.next_chunk(true);
+
+                transaction
+            }
+
+            /// Starts a read from the device at `address` that fills the whole buffer by DMA
+            ///
+            /// The memory increment of the DMA configuration is always enabled.
+            pub fn read_dma<STREAM, BUF>(
+                self,
+                address: SevenBitAddress,
+                stream: STREAM,
+                mut buf: BUF,
+                config: DmaConfig,
+            ) -> DmaTransaction<$I2CX, SDA, SCL, (), Transfer<STREAM, RxDma<$I2CX>, PeripheralToMemory, BUF, MutTransfer>>
+            where
+                STREAM: Stream<Config = DmaConfig>,
+                BUF: WriteBuffer<Word = u8>,
+            {
+                let (_, len) = unsafe { buf.write_buffer() };
+                assert!(len > 0);
+
+                let mut rx = Transfer::init(stream, RxDma { _i2c: PhantomData }, buf, config.memory_increment(true));
+                self.i2c.cr1.modify(|_, w| w.rxdmaen().set_bit());
+                rx.start(|_| {});
+
+                let mut transaction = DmaTransaction {
+                    i2c: self,
+                    tx: (),
+                    rx,
+                    address,
+                    write_remaining: 0,
+                    read_remaining: len,
+                    reading: true,
+                    finished: false,
+                };
+                transaction.next_chunk(true);
+
+                transaction
+            }
+
+            /// Starts a write of `tx_buf` followed by a repeated START and a read into `rx_buf`,
+            /// both moved by DMA
+            ///
+            /// The memory increment of the DMA configurations is always enabled.
+            #[allow(clippy::type_complexity)]
+            pub fn write_read_dma<TXSTREAM, TXBUF, RXSTREAM, RXBUF>(
+                self,
+                address: SevenBitAddress,
+                tx_stream: TXSTREAM,
+                tx_buf: TXBUF,
+                rx_stream: RXSTREAM,
+                mut rx_buf: RXBUF,
+                config: DmaConfig,
+            ) -> DmaTransaction<
+                $I2CX,
+                SDA,
+                SCL,
+                Transfer<TXSTREAM, TxDma<$I2CX>, MemoryToPeripheral, TXBUF, ConstTransfer>,
+                Transfer<RXSTREAM, RxDma<$I2CX>, PeripheralToMemory, RXBUF, MutTransfer>,
+            >
+            where
+                TXSTREAM: Stream<Config = DmaConfig>,
+                TXBUF: ReadBuffer<Word = u8>,
+                RXSTREAM: Stream<Config = DmaConfig>,
+                RXBUF: WriteBuffer<Word = u8>,
+            {
+                let (_, write_len) = unsafe { tx_buf.read_buffer() };
+                let (_, read_len) = unsafe { rx_buf.write_buffer() };
+                assert!(write_len > 0 && read_len > 0);
+
+                // Both streams can be started right away, the peripheral only requests data for
+                // the direction it is transferring in
+                let mut tx = Transfer::init_const(tx_stream, TxDma { _i2c: PhantomData }, tx_buf, config.memory_increment(true));
+                let mut rx = Transfer::init(rx_stream, RxDma { _i2c: PhantomData }, rx_buf, config.memory_increment(true));
+                self.i2c.cr1.modify(|_, w| w.txdmaen().set_bit().rxdmaen().set_bit());
+                rx.start(|_| {});
+                tx.start(|_| {});
+
+                let mut transaction = DmaTransaction {
+                    i2c: self,
+                    tx,
+                    rx,
+                    address,
+                    write_remaining: write_len,
+                    read_remaining: read_len,
+                    reading: false,
+                    finished: false,
+                };
+                transaction.next_chunk(true);
+
+                transaction
+            }
+        }
+
+        unsafe impl TargetAddress<MemoryToPeripheral> for TxDma<$I2CX> {
+            #[inline(always)]
+            fn address(&self) -> u32 {
+                &unsafe { &*<$I2CX>::ptr() }.txdr as *const _ as u32
+            }
+
+            type MemSize = u8;
+
+            const REQUEST_LINE: Option<u8> = Some(DmaMuxResources::$dmamux_tx as u8);
+        }
+
+        unsafe impl TargetAddress<PeripheralToMemory> for RxDma<$I2CX> {
+            #[inline(always)]
+            fn address(&self) -> u32 {
+                &unsafe { &*<$I2CX>::ptr() }.rxdr as *const _ as u32
+            }
+
+            type MemSize = u8;
+
+            const REQUEST_LINE: Option<u8> = Some(DmaMuxResources::$dmamux_rx as u8);
         }
 
         impl<SDA, SCL> I2c<SevenBitAddress> for I2cObj<$I2CX, SDA, SCL>
@@ -253,65 +957,7 @@ macro_rules! i2c {
                 operations: &mut [Operation<'_>],
             ) -> Result<(), Self::Error>
             {
-                for operation in operations {
-
-                    match operation {
-                        Operation::Read(ref mut data) => {
-                            // TODO support transfers of more than 255 bytes
-                            assert!(data.len() < 256 && data.len() > 0); //For now only transfers of max 255 bytes are supported.
-
-                            self.i2c.cr2.modify(|_, w| {
-                                w
-                                    // Start transfer
-                                    .start().set_bit()
-                                    // Set number of bytes to transfer
-                                    .nbytes().bits(data.len() as u8)
-                                    // Set address to transfer to/from
-                                    .sadd().bits((address << 1) as u16)
-                                    // Set transfer direction to read
-                                    .rd_wrn().set_bit()
-                                    // automatic end mode
-                                    .autoend().set_bit()
-                            });
-
-                            for byte in data.iter_mut() {
-                                // Wait until we have received something
-                                busy_wait!(self.i2c, rxne, bit_is_set);
-
-                                *byte = self.i2c.rxdr.read().rxdata().bits();
-                            }
-                        }
-                        Operation::Write(data) => {
-                            assert!(data.len() < 256 && data.len() > 0);
-
-                            self.i2c.cr2.modify(|_, w| {
-                                w
-                                    // Start transfer
-                                    .start().set_bit()
-                                    // Set address to transfer to/from
-                                    .sadd().bits((address << 1) as u16)
-                                    // Set transfer direction to write
-                                    .rd_wrn().clear_bit()
-                                    // Software end mode
-                                    .autoend().set_bit()
-                                    // Set number of bytes to transfer
-                                    .nbytes().bits(data.len() as u8)
-                            });
-
-                            //Write each byte in data
-                            for byte in data.iter() {
-                                // Wait until we are allowed to send data
-                                // (START has been ACKed or last byte when through)
-                                busy_wait!(self.i2c, txis, bit_is_set);
-
-                                // Put byte on the wire
-                                self.i2c.txdr.write(|w| w.txdata().bits(*byte) );
-                            }
-                        }
-                    }
-                }
-
-                Ok(())
+                transaction(&self.i2c, Address::seven_bit(address), operations)
             }
         }
 
@@ -323,74 +969,12 @@ macro_rules! i2c {
                 operations: &mut [Operation<'_>],
             ) -> Result<(), Self::Error>
             {
-                for operation in operations {
-
-                    match operation {
-                        Operation::Read(ref mut data) => {
-                            // TODO support transfers of more than 255 bytes
-                            assert!(data.len() < 256 && data.len() > 0); //For now only transfers of max 255 bytes are supported.
-
-                            self.i2c.cr2.modify(|_, w| {
-                                w
-                                    // Start transfer
-                                    .start().set_bit()
-                                    // Set number of bytes to transfer
-                                    .nbytes().bits(data.len() as u8)
-                                    // Set address to transfer to/from
-                                    .sadd().bits((address << 1) as u16)
-                                    // Set transfer direction to read
-                                    .rd_wrn().set_bit()
-                                    //Enable 10-bit addressing
-                                    .add10().set_bit()
-                                    // automatic end mode
-                                    .autoend().set_bit()
-                            });
-
-                            for byte in data.iter_mut() {
-                                // Wait until we have received something
-                                busy_wait!(self.i2c, rxne, bit_is_set);
-
-                                *byte = self.i2c.rxdr.read().rxdata().bits();
-                            }
-                        }
-                        Operation::Write(data) => {
-                            assert!(data.len() < 256 && data.len() > 0);
-
-                            self.i2c.cr2.modify(|_, w| {
-                                w
-                                    // Start transfer
-                                    .start().set_bit()
-                                    // Set address to transfer to/from
-                                    .sadd().bits((address << 1) as u16)
-                                    // Set transfer direction to write
-                                    .rd_wrn().clear_bit()
-                                    // Software end mode
-                                    .autoend().set_bit()
-                                    //Enable 10-bit addressing
-                                    .add10().set_bit()
-                                    // Set number of bytes to transfer
-                                    .nbytes().bits(data.len() as u8)
-                            });
-
-                            //Write each byte in data
-                            for byte in data.iter() {
-                                // Wait until we are allowed to send data
-                                // (START has been ACKed or last byte when through)
-                                busy_wait!(self.i2c, txis, bit_is_set);
-
-                                // Put byte on the wire
-                                self.i2c.txdr.write(|w| w.txdata().bits(*byte) );
-                            }
-                        }
-                    }
-                }
-
-                Ok(())
+                transaction(&self.i2c, Address::ten_bit(address), operations)
             }
         }
 
         impl<SDA, SCL> ErrorType for I2cObj<$I2CX, SDA, SCL> {
-                type Error = ErrorKind;
+                type Error = Error;
             }
     };
 }
@@ -398,6 +982,8 @@ macro_rules! i2c {
 i2c!(
     I2C1,
     i2c1,
+    I2C1_RX,
+    I2C1_TX,
     sda: [
         PA14<AlternateOD<AF4>>,
         PB7<AlternateOD<AF4>>,
@@ -413,6 +999,8 @@ i2c!(
 i2c!(
     I2C2,
     i2c2,
+    I2C2_RX,
+    I2C2_TX,
     sda: [
         PA8<AlternateOD<AF4>>,
         PF0<AlternateOD<AF4>>,
@@ -434,6 +1022,8 @@ i2c!(
 i2c!(
     I2C3,
     i2c3,
+    I2C3_RX,
+    I2C3_TX,
     sda: [
         PB5<AlternateOD<AF8>>,
         PC11<AlternateOD<AF8>>,
@@ -487,6 +1077,8 @@ feature = "stm32g484"
 i2c!(
     I2C4,
     i2c4,
+    I2C4_RX,
+    I2C4_TX,
     sda: [
         PB7<AlternateOD<AF3>>,
         PC7<AlternateOD<AF8>>,
@@ -1,4 +1,5 @@
 //! I2C
+use hal_api::i2c::{NoAcknowledgeSource, Operation};
 use hal_api_old::blocking::i2c::{Read, Write, WriteRead};
 
 use crate::gpio::{gpioa::*, gpiob::*, gpioc::*, gpiof::*};
@@ -21,13 +22,16 @@ use crate::rcc::{Enable, GetBusFreq, Rcc, RccBus, Reset};
 ))]
 use crate::stm32::I2C4;
 use crate::stm32::{I2C1, I2C2, I2C3, RCC};
-use crate::time::Hertz;
+use crate::i2c::{transaction, Address};
+pub use crate::i2c::recover_bus;
+use crate::time::{cycles_us, Hertz, MicroSecond};
 use core::cmp;
 
 /// I2C bus configuration.
 pub struct Config {
     speed: Option<Hertz>,
     timing: Option<u32>,
+    timeout: Option<MicroSecond>,
     analog_filter: bool,
     digital_filter: u8,
 }
@@ -41,6 +45,7 @@ impl Config {
         Config {
             speed: Some(speed.into()),
             timing: None,
+            timeout: None,
             analog_filter: true,
             digital_filter: 0,
         }
@@ -60,6 +65,7 @@ impl Config {
         Config {
             timing: Some(timing),
             speed: None,
+            timeout: None,
             analog_filter: true,
             digital_filter: 0,
         }
@@ -78,6 +84,23 @@ impl Config {
         self
     }
 
+    /// Enables the SCL low timeout: a transaction fails with [`Error::Timeout`] when SCL is held
+    /// low for longer than `timeout`. The peripheral is reset to release the bus.
+    ///
+    /// The timeout is rounded up to a multiple of 2048 I2C clock cycles, up to 4096 multiples.
+    pub fn scl_timeout(mut self, timeout: MicroSecond) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn timeout_bits(&self, i2c_clk: Hertz) -> Option<u16> {
+        // tTIMEOUT = (TIMEOUTA + 1) * 2048 * tI2CCLK
+        self.timeout.map(|timeout| {
+            let cycles = cycles_us(timeout, i2c_clk);
+            cmp::min(cycles.div_ceil(2048).saturating_sub(1), 0xfff) as u16
+        })
+    }
+
     fn timing_bits(&self, i2c_clk: Hertz) -> u32 {
         if let Some(bits) = self.timing {
             return bits;
@@ -119,10 +142,23 @@ pub trait SCLPin<I2C> {}
 #[derive(Debug)]
 pub enum Error {
     Overrun,
-    Nack,
+    Nack(NoAcknowledgeSource),
     PECError,
     BusError,
     ArbitrationLost,
+    Timeout,
+}
+
+impl From<crate::i2c::Error> for Error {
+    fn from(error: crate::i2c::Error) -> Self {
+        match error {
+            crate::i2c::Error::Bus => Error::BusError,
+            crate::i2c::Error::ArbitrationLoss => Error::ArbitrationLost,
+            crate::i2c::Error::NoAcknowledge(source) => Error::Nack(source),
+            crate::i2c::Error::Overrun => Error::Overrun,
+            crate::i2c::Error::Timeout => Error::Timeout,
+        }
+    }
 }
 
 pub trait I2cExt<I2C> {
@@ -132,45 +168,6 @@ pub trait I2cExt<I2C> {
         SCL: SCLPin<I2C>;
 }
 
-/// Sequence to flush the TXDR register. This resets the TXIS and TXE flags
-macro_rules! flush_txdr {
-    ($i2c:expr) => {
-        // If a pending TXIS flag is set, write dummy data to TXDR
-        if $i2c.isr.read().txis().bit_is_set() {
-            $i2c.txdr.write(|w| w.txdata().bits(0));
-        }
-
-        // If TXDR is not flagged as empty, write 1 to flush it
-        if $i2c.isr.read().txe().bit_is_set() {
-            $i2c.isr.write(|w| w.txe().set_bit());
-        }
-    };
-}
-
-macro_rules! busy_wait {
-    ($i2c:expr, $flag:ident, $variant:ident) => {
-        loop {
-            let isr = $i2c.isr.read();
-
-            if isr.$flag().$variant() {
-                break;
-            } else if isr.berr().bit_is_set() {
-                $i2c.icr.write(|w| w.berrcf().set_bit());
-                return Err(Error::BusError);
-            } else if isr.arlo().bit_is_set() {
-                $i2c.icr.write(|w| w.arlocf().set_bit());
-                return Err(Error::ArbitrationLost);
-            } else if isr.nackf().bit_is_set() {
-                $i2c.icr.write(|w| w.stopcf().set_bit().nackcf().set_bit());
-                flush_txdr!($i2c);
-                return Err(Error::Nack);
-            } else {
-                // try again
-            }
-        }
-    };
-}
-
 macro_rules! i2c {
     ($I2CX:ident, $i2cx:ident,
         sda: [ $($( #[ $pmetasda:meta ] )* $PSDA:ty,)+ ],
@@ -226,6 +223,11 @@ macro_rules! i2c {
                 let timing_bits = config.timing_bits(<$I2CX as RccBus>::Bus::get_frequency(&rcc.clocks));
                 i2c.timingr.write(|w| unsafe { w.bits(timing_bits) });
 
+                // Setup the SCL low timeout
+                if let Some(timeout_bits) = config.timeout_bits(<$I2CX as RccBus>::Bus::get_frequency(&rcc.clocks)) {
+                    i2c.timeoutr.write(|w| w.timeouta().bits(timeout_bits).tidle().clear_bit().timouten().set_bit());
+                }
+
                 // Enable the I2C processing
                 i2c.cr1.modify(|_, w| {
                     w.pe()
@@ -261,70 +263,8 @@ macro_rules! i2c {
                 bytes: &[u8],
                 buffer: &mut [u8],
             ) -> Result<(), Self::Error> {
-                // TODO support transfers of more than 255 bytes
-                assert!(bytes.len() < 256 && bytes.len() > 0);
-                assert!(buffer.len() < 256 && buffer.len() > 0);
-
-                // Wait for any previous address sequence to end automatically.
-                // This could be up to 50% of a bus cycle (ie. up to 0.5/freq)
-                while self.i2c.cr2.read().start().bit_is_set() {};
-
-                // Set START and prepare to send `bytes`.
-                // The START bit can be set even if the bus is BUSY or
-                // I2C is in slave mode.
-                self.i2c.cr2.write(|w| {
-                    w
-                        // Start transfer
-                        .start().set_bit()
-                        // Set number of bytes to transfer
-                        .nbytes().bits(bytes.len() as u8)
-                        // Set address to transfer to/from
-                        .sadd().bits((addr << 1) as u16)
-                        // 7-bit addressing mode
-                        .add10().clear_bit()
-                        // Set transfer direction to write
-                        .rd_wrn().clear_bit()
-                        // Software end mode
-                        .autoend().clear_bit()
-                });
-
-                for byte in bytes {
-                    // Wait until we are allowed to send data
-                    // (START has been ACKed or last byte went through)
-                    busy_wait!(self.i2c, txis, bit_is_set);
-
-                    // Put byte on the wire
-                    self.i2c.txdr.write(|w| { w.txdata().bits(*byte) });
-                }
-
-                // Wait until the write finishes before beginning to read.
-                busy_wait!(self.i2c, tc, bit_is_set);
-
-                // reSTART and prepare to receive bytes into `buffer`
-                self.i2c.cr2.write(|w| {
-                    w
-                        // Start transfer
-                        .start().set_bit()
-                        // Set number of bytes to transfer
-                        .nbytes().bits(buffer.len() as u8)
-                        // Set address to transfer to/from
-                        .sadd().bits((addr << 1) as u16)
-                        // 7-bit addressing mode
-                        .add10().clear_bit()
-                        // Set transfer direction to read
-                        .rd_wrn().set_bit()
-                        // Automatic end mode
-                        .autoend().set_bit()
-                });
-
-                for byte in buffer {
-                    // Wait until we have received something
-                    busy_wait!(self.i2c, rxne, bit_is_set);
-
-                    *byte = self.i2c.rxdr.read().rxdata().bits();
-                }
-
-                // automatic STOP
+                let mut operations = [Operation::Write(bytes), Operation::Read(buffer)];
+                transaction(&self.i2c, Address::seven_bit(addr), &mut operations)?;
 
                 Ok(())
             }
@@ -334,32 +274,7 @@ macro_rules! i2c {
             type Error = Error;
 
             fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Self::Error> {
-                assert!(bytes.len() < 256 && bytes.len() > 0);
-
-                self.i2c.cr2.modify(|_, w| {
-                    w
-                        // Start transfer
-                        .start().set_bit()
-                        // Set number of bytes to transfer
-                        .nbytes().bits(bytes.len() as u8)
-                        // Set address to transfer to/from
-                        .sadd().bits((addr << 1) as u16)
-                        // Set transfer direction to write
-                        .rd_wrn().clear_bit()
-                        // Automatic end mode
-                        .autoend().set_bit()
-                });
-
-                for byte in bytes {
-                    // Wait until we are allowed to send data
-                    // (START has been ACKed or last byte when through)
-                    busy_wait!(self.i2c, txis, bit_is_set);
-
-                    // Put byte on the wire
-                    self.i2c.txdr.write(|w| w.txdata().bits(*byte) );
-                }
-
-                // automatic STOP
+                transaction(&self.i2c, Address::seven_bit(addr), &mut [Operation::Write(bytes)])?;
 
                 Ok(())
             }
@@ -369,38 +284,7 @@ macro_rules! i2c {
             type Error = Error;
 
             fn read(&mut self, addr: u8, bytes: &mut [u8]) -> Result<(), Self::Error> {
-                // TODO support transfers of more than 255 bytes
-                assert!(bytes.len() < 256 && bytes.len() > 0);
-
-                // Wait for any previous address sequence to end automatically.
-                // This could be up to 50% of a bus cycle (ie. up to 0.5/freq)
-                while self.i2c.cr2.read().start().bit_is_set() {};
-
-                // Set START and prepare to receive bytes into `buffer`.
-                // The START bit can be set even if the bus
-                // is BUSY or I2C is in slave mode.
-                self.i2c.cr2.modify(|_, w| {
-                    w
-                        // Start transfer
-                        .start().set_bit()
-                        // Set number of bytes to transfer
-                        .nbytes().bits(bytes.len() as u8)
-                        // Set address to transfer to/from
-                        .sadd().bits((addr << 1) as u16)
-                        // Set transfer direction to read
-                        .rd_wrn().set_bit()
-                        // automatic end mode
-                        .autoend().set_bit()
-                });
-
-                for byte in bytes {
-                    // Wait until we have received something
-                    busy_wait!(self.i2c, rxne, bit_is_set);
-
-                    *byte = self.i2c.rxdr.read().rxdata().bits();
-                }
-
-                // automatic STOP
+                transaction(&self.i2c, Address::seven_bit(addr), &mut [Operation::Read(bytes)])?;
 
                 Ok(())
             }
@@ -0,0 +1,365 @@
+//! I2C target (slave) mode
+//!
+//! An [`I2cTarget`] answers to its own address(es) instead of starting transactions. The
+//! peripheral is serviced with [`I2cTarget::poll`], which returns the next [`Event`] of the
+//! transaction, either from a loop or from the I2C event and error interrupts after calling
+//! [`I2cTarget::listen`]. The clock is stretched until an event has been handled, unless clock
+//! stretching is disabled in the [`TargetConfig`].
+//!
+//! A register-map device can be emulated by storing the first received byte as register pointer
+//! and answering reads from there:
+//!
+//! ```rust,ignore
+//!   let config = TargetConfig::new(Config::new(100.kHz()), OwnAddress::SevenBit(0x42));
+//!   let mut target = dp.I2C1.i2c_target(sda, scl, config, &mut rcc);
+//!
+//!   loop {
+//!       match nb::block!(target.poll()) {
+//!           Ok(Event::AddressMatch { direction: Direction::Write, .. }) => first = true,
+//!           Ok(Event::Received(byte)) if first => { pointer = byte; first = false; }
+//!           Ok(Event::Received(byte)) => { registers[pointer as usize] = byte; pointer += 1; }
+//!           Ok(Event::TransmitRequest) => { target.write(registers[pointer as usize]); pointer += 1; }
+//!           _ => {}
+//!       }
+//!   }
+//! ```
+
+use core::ops::Deref;
+
+use crate::i2c::{Config, Error, SCLPin, SDAPin};
+use crate::rcc::{Enable, GetBusFreq, Rcc, RccBus, Reset};
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+use crate::stm32::I2C4;
+use crate::stm32::{i2c1::RegisterBlock, I2C1, I2C2, I2C3, RCC};
+
+/// Primary address of the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OwnAddress {
+    SevenBit(u8),
+    TenBit(u16),
+}
+
+/// I2C target configuration
+pub struct TargetConfig {
+    bus: Config,
+    address: OwnAddress,
+    secondary_address: Option<(u8, u8)>,
+    general_call: bool,
+    clock_stretching: bool,
+    byte_control: bool,
+}
+
+impl TargetConfig {
+    /// Creates a configuration answering to `address`, with clock stretching enabled.
+    ///
+    /// The timing of `bus` is used for the data setup and hold times.
+    pub fn new(bus: Config, address: OwnAddress) -> Self {
+        TargetConfig {
+            bus,
+            address,
+            secondary_address: None,
+            general_call: false,
+            clock_stretching: true,
+            byte_control: false,
+        }
+    }
+
+    /// Also answers to the 7-bit `address`, of which the `masked_bits` least significant bits
+    /// are ignored when comparing.
+    pub fn secondary_address(mut self, address: u8, masked_bits: u8) -> Self {
+        assert!(address < 0x80);
+        assert!(masked_bits <= 7);
+        self.secondary_address = Some((address, masked_bits));
+        self
+    }
+
+    /// Acknowledges the general call address 0x00.
+    pub fn general_call(mut self, enable: bool) -> Self {
+        self.general_call = enable;
+        self
+    }
+
+    /// Disables clock stretching, events then have to be handled within a bit period to prevent
+    /// an [`Error::Overrun`].
+    pub fn disable_clock_stretching(mut self) -> Self {
+        self.clock_stretching = false;
+        self
+    }
+
+    /// Enables slave byte control: the acknowledge of each received byte is delayed until the
+    /// [`Event::Received`] has been handled, so it can be refused with [`I2cTarget::nack`].
+    pub fn byte_control(mut self) -> Self {
+        self.byte_control = true;
+        self
+    }
+}
+
+/// Transfer direction requested by the controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The controller writes to the target
+    Write,
+    /// The controller reads from the target
+    Read,
+}
+
+/// Event of an I2C target, returned by [`I2cTarget::poll`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A controller addressed the target. `address` is the 7-bit address that matched, the
+    /// header `0b11110xx` for a 10-bit address, or 0 for a general call.
+    AddressMatch { address: u8, direction: Direction },
+    /// The controller wrote a byte
+    Received(u8),
+    /// The controller reads, the next byte has to be written with [`I2cTarget::write`]
+    TransmitRequest,
+    /// The transaction was ended by a STOP condition
+    Stop,
+}
+
+/// I2C peripheral in target mode
+pub struct I2cTarget<I2C, SDA, SCL> {
+    i2c: I2C,
+    sda: SDA,
+    scl: SCL,
+    byte_control: bool,
+}
+
+pub trait I2cTargetExt<I2C> {
+    fn i2c_target<SDA, SCL>(
+        self,
+        sda: SDA,
+        scl: SCL,
+        config: TargetConfig,
+        rcc: &mut Rcc,
+    ) -> I2cTarget<I2C, SDA, SCL>
+    where
+        SDA: SDAPin<I2C>,
+        SCL: SCLPin<I2C>;
+}
+
+impl<I2C, SDA, SCL> I2cTarget<I2C, SDA, SCL>
+where
+    I2C: Deref<Target = RegisterBlock>,
+{
+    fn configure(i2c: &I2C, config: TargetConfig, timing_bits: u32) {
+        assert!(
+            config.clock_stretching || !config.byte_control,
+            "slave byte control needs clock stretching"
+        );
+
+        // Make sure the I2C unit is disabled so we can configure it
+        i2c.cr1.modify(|_, w| w.pe().clear_bit());
+
+        // Setup protocol timings, only the data setup and hold times are used by a target
+        i2c.timingr.write(|w| unsafe { w.bits(timing_bits) });
+
+        // The own addresses can only be changed while they are disabled
+        i2c.oar1.write(|w| w.oa1en().clear_bit());
+        match config.address {
+            OwnAddress::SevenBit(address) => {
+                assert!(address < 0x80);
+                i2c.oar1.write(|w| w.oa1().bits((address as u16) << 1).oa1mode().clear_bit().oa1en().set_bit());
+            }
+            OwnAddress::TenBit(address) => {
+                assert!(address < 0x400);
+                i2c.oar1.write(|w| w.oa1().bits(address).oa1mode().set_bit().oa1en().set_bit());
+            }
+        }
+
+        i2c.oar2.write(|w| w.oa2en().clear_bit());
+        if let Some((address, masked_bits)) = config.secondary_address {
+            i2c.oar2.write(|w| w.oa2().bits(address).oa2msk().bits(masked_bits).oa2en().set_bit());
+        }
+
+        // Enable the I2C processing
+        i2c.cr1.modify(|_, w| {
+            w.pe()
+                .set_bit()
+                .dnf()
+                .bits(config.bus.digital_filter)
+                .anfoff()
+                .bit(!config.bus.analog_filter)
+                .gcen()
+                .bit(config.general_call)
+                .nostretch()
+                .bit(!config.clock_stretching)
+                .sbc()
+                .bit(config.byte_control)
+        });
+    }
+
+    /// Services the I2C peripheral and returns the next event of the transaction
+    pub fn poll(&mut self) -> nb::Result<Event, Error> {
+        let isr = self.i2c.isr.read();
+
+        if isr.berr().bit_is_set() {
+            self.i2c.icr.write(|w| w.berrcf().set_bit());
+            Err(nb::Error::Other(Error::Bus))
+        } else if isr.arlo().bit_is_set() {
+            self.i2c.icr.write(|w| w.arlocf().set_bit());
+            Err(nb::Error::Other(Error::ArbitrationLoss))
+        } else if isr.ovr().bit_is_set() {
+            self.i2c.icr.write(|w| w.ovrcf().set_bit());
+            Err(nb::Error::Other(Error::Overrun))
+        } else if isr.addr().bit_is_set() {
+            let direction = if isr.dir().bit_is_set() {
+                // Discard a byte that was left over from a previous read
+                self.i2c.isr.write(|w| w.txe().set_bit());
+                Direction::Read
+            } else {
+                if self.byte_control {
+                    // Hand over each byte before it is acknowledged
+                    self.i2c.cr2.modify(|_, w| w.nbytes().bits(1).reload().set_bit());
+                }
+                Direction::Write
+            };
+            let address = isr.addcode().bits();
+
+            // Releases the clock stretching of the address phase
+            self.i2c.icr.write(|w| w.addrcf().set_bit());
+
+            Ok(Event::AddressMatch { address, direction })
+        } else if isr.rxne().bit_is_set() {
+            Ok(Event::Received(self.i2c.rxdr.read().rxdata().bits()))
+        } else if isr.tcr().bit_is_set() {
+            // The received byte has been handled, acknowledge it and continue with the next one
+            self.i2c.cr2.modify(|_, w| w.nbytes().bits(1));
+            Err(nb::Error::WouldBlock)
+        } else if isr.txis().bit_is_set() {
+            Ok(Event::TransmitRequest)
+        } else if isr.nackf().bit_is_set() {
+            // The controller does not want more data, the STOP condition follows
+            self.i2c.icr.write(|w| w.nackcf().set_bit());
+            Err(nb::Error::WouldBlock)
+        } else if isr.stopf().bit_is_set() {
+            self.i2c.icr.write(|w| w.stopcf().set_bit());
+            self.i2c.isr.write(|w| w.txe().set_bit());
+            Ok(Event::Stop)
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Answers an [`Event::TransmitRequest`]
+    pub fn write(&mut self, byte: u8) {
+        self.i2c.txdr.write(|w| w.txdata().bits(byte));
+    }
+
+    /// Refuses the byte of the last [`Event::Received`] and ends the write of the controller.
+    ///
+    /// Only has effect with [`TargetConfig::byte_control`], otherwise every byte is acknowledged.
+    pub fn nack(&mut self) {
+        self.i2c.cr2.modify(|_, w| w.nack().set_bit());
+    }
+
+    /// Enables the interrupts needed to service the target with [`poll`](Self::poll)
+    pub fn listen(&mut self) {
+        self.i2c.cr1.modify(|_, w| {
+            w.addrie()
+                .set_bit()
+                .rxie()
+                .set_bit()
+                .txie()
+                .set_bit()
+                .tcie()
+                .set_bit()
+                .nackie()
+                .set_bit()
+                .stopie()
+                .set_bit()
+                .errie()
+                .set_bit()
+        });
+    }
+
+    /// Disables the interrupts enabled by [`listen`](Self::listen)
+    pub fn unlisten(&mut self) {
+        self.i2c.cr1.modify(|_, w| {
+            w.addrie()
+                .clear_bit()
+                .rxie()
+                .clear_bit()
+                .txie()
+                .clear_bit()
+                .tcie()
+                .clear_bit()
+                .nackie()
+                .clear_bit()
+                .stopie()
+                .clear_bit()
+                .errie()
+                .clear_bit()
+        });
+    }
+}
+
+macro_rules! i2c_target {
+    ($($I2CX:ident,)+) => {$(
+        impl I2cTargetExt<$I2CX> for $I2CX {
+            fn i2c_target<SDA, SCL>(
+                self,
+                sda: SDA,
+                scl: SCL,
+                config: TargetConfig,
+                rcc: &mut Rcc,
+            ) -> I2cTarget<$I2CX, SDA, SCL>
+            where
+                SDA: SDAPin<$I2CX>,
+                SCL: SCLPin<$I2CX>,
+            {
+                I2cTarget::<$I2CX, SDA, SCL>::new(self, sda, scl, config, rcc)
+            }
+        }
+
+        impl<SDA, SCL> I2cTarget<$I2CX, SDA, SCL> where
+            SDA: SDAPin<$I2CX>,
+            SCL: SCLPin<$I2CX>
+        {
+            /// Initializes the I2C peripheral in target mode.
+            pub fn new(i2c: $I2CX, sda: SDA, scl: SCL, config: TargetConfig, rcc: &mut Rcc) -> Self {
+                // Enable and reset I2C
+                unsafe {
+                    let rcc_ptr = &(*RCC::ptr());
+                    $I2CX::enable(rcc_ptr);
+                    $I2CX::reset(rcc_ptr);
+                }
+
+                let timing_bits = config.bus.timing_bits(<$I2CX as RccBus>::Bus::get_frequency(&rcc.clocks));
+                let byte_control = config.byte_control;
+                Self::configure(&i2c, config, timing_bits);
+
+                I2cTarget { i2c, sda, scl, byte_control }
+            }
+
+            /// Disables I2C and releases the peripheral as well as the pins.
+            pub fn release(self) -> ($I2CX, SDA, SCL) {
+                // Disable I2C.
+                unsafe {
+                    let rcc_ptr = &(*RCC::ptr());
+                    $I2CX::reset(rcc_ptr);
+                    $I2CX::disable(rcc_ptr);
+                }
+
+                (self.i2c, self.sda, self.scl)
+            }
+        }
+    )+};
+}
+
+i2c_target!(I2C1, I2C2, I2C3,);
+
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+i2c_target!(I2C4,);
@@ -149,3 +149,17 @@ impl IndependentWatchdog {
         }
     }
 }
+
+impl hal_api_old::watchdog::Watchdog for IndependentWatchdog {
+    fn feed(&mut self) {
+        IndependentWatchdog::feed(self)
+    }
+}
+
+impl hal_api_old::watchdog::WatchdogEnable for IndependentWatchdog {
+    type Time = MicroSecond;
+
+    fn start<T: Into<MicroSecond>>(&mut self, period: T) {
+        IndependentWatchdog::start(self, period)
+    }
+}
@@ -31,6 +31,7 @@ extern crate void;
 pub extern crate cortex_m;
 pub extern crate embedded_hal_1 as hal_api;
 pub extern crate embedded_hal_02 as hal_api_old;
+pub extern crate embedded_io;
 pub extern crate nb;
 pub extern crate stm32g4;
 
@@ -82,8 +83,9 @@ pub use crate::stm32::interrupt;
 pub mod adc;
 pub mod bb;
 pub mod can;
+pub mod capture;
 pub mod comparator;
-// pub mod crc;
+pub mod crc;
 pub mod dac;
 pub mod delay;
 pub mod dma;
@@ -95,9 +97,10 @@ pub mod opamp;
 pub mod prelude;
 pub mod pwm;
 pub mod pwr;
-// pub mod qei;
+pub mod qei;
 pub mod rcc;
-// pub mod rng;
+pub mod rng;
+pub mod rtc;
 pub mod serial;
 pub mod signature;
 pub mod spi_compat;
@@ -105,10 +108,13 @@ pub mod spi_compat;
 pub mod syscfg;
 pub mod time;
 pub mod timer;
-// pub mod watchdog;
+pub mod watchdog;
 pub mod independent_watchdog;
 pub mod i2c;
+pub mod i2c_target;
+pub mod smbus;
 pub mod preludes;
 pub mod spi;
+pub mod spi_slave;
 pub mod usb;
 pub mod hal_api_custom;
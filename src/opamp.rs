@@ -8,9 +8,7 @@
 //! extern crate panic_halt;
 //!
 //! use prelude::*;
-//! use gpio::gpioa::*;
-//! use gpio::gpiob::*;
-//! use gpio::Analog;
+//! use opamp::{InternalOutput, NonInvertingGain, PgaModeInternal};
 //!
 //! #[cortex_m_rt::entry]
 //! fn main() -> ! {
@@ -28,33 +26,56 @@
 //!     // setup opamps
 //!     let (opamp1, opamp2, opamp3, opamp4, _opamp5, _opamp6) = dp.OPAMP.split(&mut rcc);
 //!
-//!     let opamp1 = opamp1.follower(gpioa.pa1, Some(gpioa.pa2));
-//!     let opamp2 = opamp2.follower(gpioa.pa7, Option::<PA6<Analog>>::None);
+//!     let opamp1 = opamp1.follower(gpioa.pa1, gpioa.pa2);
+//!     let opamp2 = opamp2.follower(gpioa.pa7, InternalOutput);
 //!
-//!     let opamp3 = opamp3.open_loop(gpiob.pb0, gpiob.pb2, Some(gpiob.pb1));
-//!     let opamp4 = opamp4.open_loop(gpiob.pb11, gpiob.pb10, Option::<PB12<Analog>>::None);
+//!     let opamp3 = opamp3.open_loop(gpiob.pb0, gpiob.pb2, gpiob.pb1);
+//!     let opamp4 = opamp4.open_loop(gpiob.pb11, gpiob.pb10, InternalOutput);
 //!
 //!     // disable opamps
-//!     let (opamp1, pa1, some_pa2) = opamp1.disable();
-//!     let (opamp2, pa7, _none) = opamp2.disable();
+//!     let (opamp1, pa1, pa2) = opamp1.disable();
+//!     let (opamp2, pa7, _internal) = opamp2.disable();
 //!
-//!     let (_opamp3, _pb0, _pb2, _some_pb1) = opamp3.disable();
-//!     let (_opamp4, _pb11, _pb10, _none) = opamp4.disable();
+//!     let (_opamp3, _pb0, _pb2, _pb1) = opamp3.disable();
+//!     let (_opamp4, _pb11, _pb10, _internal) = opamp4.disable();
 //!
-//!     let opamp1 = opamp1.pga(pa1, some_pa2);
-//!     let opamp2 = opamp2.pga(pa7, Option::<PA6<Analog>>::None);
+//!     let opamp1 = opamp1.pga(pa1, PgaModeInternal::gain(NonInvertingGain::Gain2), pa2);
+//!     let opamp2 = opamp2.pga(pa7, PgaModeInternal::gain(NonInvertingGain::Gain4), InternalOutput);
 //!
-//!     let (_opamp1, _pa1, _some_pa2) = opamp1.disable();
-//!     let (_opamp2, _pa7, _none) = opamp2.disable();
+//!     // move the output of opamp1 from its pin to the ADC
+//!     let (opamp1, _pa2) = opamp1.disable_output();
+//!
+//!     let (_opamp1, _pa1, _internal) = opamp1.disable();
+//!     let (_opamp2, _pa7, _internal) = opamp2.disable();
 //!
 //!     loop {}
 //! }
 //! ```
+//!
+//! For precise measurements the input offset can be trimmed with `calibrate` while the opamp is
+//! disabled, and the configuration can be protected against changes with `lock`.
+//! ```ignore
+//! let mut opamp1 = opamp1;
+//! opamp1.high_speed(true);
+//! let (trim_p, trim_n) = opamp1.calibrate(&mut delay);
+//! let opamp1 = opamp1
+//!     .pga(pa1, PgaModeInverting::new(pa3, InvertingGain::Gain7), InternalOutput)
+//!     .lock();
+//! ```
+//!
+//! An opamp whose output is routed to [`InternalOutput`] can be sampled as an ADC channel,
+//! so the amplified signal does not have to leave the chip. While the output pin is held by
+//! the opamp, it can not be used as ADC channel.
+//! ```ignore
+//! let opamp2 = opamp2.follower(gpioa.pa7, InternalOutput);
+//! adc.configure_channel(&opamp2, config::Sequence::One, config::SampleTime::Cycles_640_5);
+//! let sample = adc.convert(&opamp2, config::SampleTime::Cycles_640_5);
+//! ```
 
-// TODO: Add support for locking using the `LOCK` bit in `OPAMPx_CSR`
-// TODO: Add support for calibration
-// TODO: The output can not be a Option<PIN> if we want to handle "route to pin vs adc"
-//       in a compile time way. See OPAINTOEN in OPAMPx_CSR
+/// Output routed internally to the ADC channel of the opamp instead of to its output pin
+///
+/// Opamps in this state can be sampled with `Adc::configure_channel`/`Adc::convert`.
+pub struct InternalOutput;
 
 /// Pga mode internal
 ///
@@ -74,6 +95,58 @@ pub struct PgaModeInvertedInputFiltered<PIN> {
     gain: NonInvertingGain,
     pin: core::marker::PhantomData<PIN>,
 }
+impl<PIN> PgaModeInvertedInputFiltered<PIN> {
+    /// Create new instance with the given gain setting
+    pub fn gain(gain: NonInvertingGain) -> Self {
+        PgaModeInvertedInputFiltered {
+            gain,
+            pin: core::marker::PhantomData,
+        }
+    }
+}
+
+/// Inverting Pga mode
+///
+/// The input signal is connected to VINM0 and amplified with the inverting gain, the
+/// non-inverting input is used as bias. The bias is amplified with `1 - gain`.
+pub struct PgaModeInverting<PIN> {
+    gain: InvertingGain,
+    input: PIN,
+}
+impl<PIN> PgaModeInverting<PIN> {
+    /// Create new instance with the input pin and the given gain setting
+    pub fn new(input: PIN, gain: InvertingGain) -> Self {
+        PgaModeInverting { gain, input }
+    }
+
+    /// Returns the input pin
+    pub fn release(self) -> PIN {
+        self.input
+    }
+}
+
+/// Same as PgaModeInverting but the feedback is routed to VINM1
+/// to allow external filter
+pub struct PgaModeInvertingFiltered<PIN, FILTER> {
+    gain: InvertingGain,
+    input: PIN,
+    filter: FILTER,
+}
+impl<PIN, FILTER> PgaModeInvertingFiltered<PIN, FILTER> {
+    /// Create new instance with the input and filter pins and the given gain setting
+    pub fn new(input: PIN, filter: FILTER, gain: InvertingGain) -> Self {
+        PgaModeInvertingFiltered {
+            gain,
+            input,
+            filter,
+        }
+    }
+
+    /// Returns the input and filter pins
+    pub fn release(self) -> (PIN, FILTER) {
+        (self.input, self.filter)
+    }
+}
 
 /// PGA Gain for non inverted modes
 pub enum NonInvertingGain {
@@ -96,6 +169,27 @@ pub enum NonInvertingGain {
     Gain64 = 5,
 }
 
+/// PGA Gain for inverting modes
+pub enum InvertingGain {
+    /// -1x Gain
+    Gain1 = 0,
+
+    /// -3x Gain
+    Gain3 = 1,
+
+    /// -7x Gain
+    Gain7 = 2,
+
+    /// -15x Gain
+    Gain15 = 3,
+
+    /// -31x Gain
+    Gain31 = 4,
+
+    /// -63x Gain
+    Gain63 = 5,
+}
+
 macro_rules! opamps {
     {
         $(
@@ -103,6 +197,7 @@ macro_rules! opamps {
             :
             {
                 vinm0: $vinm0:ty,
+                vinm1: $vinm1:ty,
 
                 inverting
                 :
@@ -172,10 +267,42 @@ macro_rules! opamps {
                     }
                 }
 
+                impl<PIN> From<&PgaModeInverting<PIN>> for crate::stm32::opamp::[<$opamp _csr>]::PGA_GAIN_A {
+                    fn from(x: &PgaModeInverting<PIN>) -> crate::stm32::opamp::[<$opamp _csr>]::PGA_GAIN_A {
+                        use crate::stm32::opamp::[<$opamp _csr>]::PGA_GAIN_A;
+
+                        match x.gain {
+                            InvertingGain::Gain1 => PGA_GAIN_A::Gain2InputVinm0,
+                            InvertingGain::Gain3 => PGA_GAIN_A::Gain4InputVinm0,
+                            InvertingGain::Gain7 => PGA_GAIN_A::Gain8InputVinm0,
+                            InvertingGain::Gain15 => PGA_GAIN_A::Gain16InputVinm0,
+                            InvertingGain::Gain31 => PGA_GAIN_A::Gain32InputVinm0,
+                            InvertingGain::Gain63 => PGA_GAIN_A::Gain64InputVinm0,
+                        }
+                    }
+                }
+
+                impl<PIN, FILTER> From<&PgaModeInvertingFiltered<PIN, FILTER>> for crate::stm32::opamp::[<$opamp _csr>]::PGA_GAIN_A {
+                    fn from(x: &PgaModeInvertingFiltered<PIN, FILTER>) -> crate::stm32::opamp::[<$opamp _csr>]::PGA_GAIN_A {
+                        use crate::stm32::opamp::[<$opamp _csr>]::PGA_GAIN_A;
+
+                        match x.gain {
+                            InvertingGain::Gain1 => PGA_GAIN_A::Gain2InputVinm0filteringVinm1,
+                            InvertingGain::Gain3 => PGA_GAIN_A::Gain4InputVinm0filteringVinm1,
+                            InvertingGain::Gain7 => PGA_GAIN_A::Gain8InputVinm0filteringVinm1,
+                            InvertingGain::Gain15 => PGA_GAIN_A::Gain16InputVinm0filteringVinm1,
+                            InvertingGain::Gain31 => PGA_GAIN_A::Gain32InputVinm0filteringVinm1,
+                            InvertingGain::Gain63 => PGA_GAIN_A::Gain64InputVinm0filteringVinm1,
+                        }
+                    }
+                }
+
                 /// States for opampX.
                 pub mod $opamp {
                     use core::{borrow::Borrow, marker::PhantomData};
 
+                    use crate::opamp::InternalOutput;
+
                     #[allow(unused_imports)]
                     use crate::gpio::gpioa::*;
 
@@ -185,147 +312,286 @@ macro_rules! opamps {
                     /// State type for disabled opamp.
                     pub struct Disabled;
 
+                    /// State type for an opamp whose configuration is locked until the next reset.
+                    pub struct Locked<STATE> {
+                        state: STATE,
+                    }
+
+                    impl<STATE> Locked<STATE> {
+                        /// Returns the locked state, which can not be changed anymore.
+                        pub fn state(&self) -> &STATE {
+                            &self.state
+                        }
+                    }
+
+                    impl Disabled {
+                        /// Selects high-speed mode, which has a higher slew rate and bandwidth.
+                        /// Calibrate after changing the mode, since the offset differs per mode.
+                        pub fn high_speed(&mut self, enable: bool) {
+                            unsafe {
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
+                                    w.opahsm().bit(enable));
+                            }
+                        }
+
+                        /// Trims the input offset of both differential pairs and selects the user
+                        /// trimming values, in the currently selected speed mode.
+                        ///
+                        /// The trimming values (TRIMOFFSETP, TRIMOFFSETN) are returned so they can
+                        /// be restored with [`Disabled::set_trim`] instead of calibrating again.
+                        pub fn calibrate(&mut self, delay: &mut impl hal_api_old::blocking::delay::DelayUs<u32>) -> (u8, u8) {
+                            use crate::stm32::opamp::[<$opamp _csr>]::CALSEL_A;
+
+                            let csr = unsafe { &(*crate::stm32::OPAMP::ptr()).[<$opamp _csr>] };
+                            csr.modify(|_, w| w.usertrim().user().calon().enabled().opaen().enabled());
+
+                            // The NMOS pair is trimmed at 0.9 VDDA, the PMOS pair at 0.1 VDDA
+                            let mut trim = [0u8; 2];
+                            for (i, calsel) in [CALSEL_A::Percent90, CALSEL_A::Percent10].into_iter().enumerate() {
+                                csr.modify(|_, w| w.calsel().variant(calsel));
+                                let set = |value: u8| match calsel {
+                                    CALSEL_A::Percent90 => csr.modify(|_, w| w.trimoffsetn().bits(value)),
+                                    _ => csr.modify(|_, w| w.trimoffsetp().bits(value)),
+                                };
+
+                                // Binary search for the value at which CALOUT toggles
+                                let mut value = 16;
+                                let mut delta = 8;
+                                while delta != 0 {
+                                    set(value);
+                                    delay.delay_us(2000);
+                                    if csr.read().calout().bit_is_set() {
+                                        value -= delta;
+                                    } else {
+                                        value += delta;
+                                    }
+                                    delta >>= 1;
+                                }
+
+                                // The right value can still be one step below
+                                set(value);
+                                delay.delay_us(2000);
+                                if csr.read().calout().bit_is_set() {
+                                    value -= 1;
+                                    set(value);
+                                }
+                                trim[i] = value;
+                            }
+
+                            csr.modify(|_, w| w.calon().disabled().opaen().disabled());
+                            (trim[1], trim[0])
+                        }
+
+                        /// Selects the user trimming values for the PMOS and NMOS differential pairs
+                        pub fn set_trim(&mut self, trimoffsetp: u8, trimoffsetn: u8) {
+                            assert!(trimoffsetp < 32 && trimoffsetn < 32);
+                            unsafe {
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
+                                    w.usertrim().user().trimoffsetp().bits(trimoffsetp).trimoffsetn().bits(trimoffsetn));
+                            }
+                        }
+                    }
+
+                    /// Destination of the opamp output, either the output pin in any mode that
+                    /// converts into analog, or [`InternalOutput`] for the internal ADC channel.
+                    pub trait OutputRoute<Output> {
+                        #[doc(hidden)]
+                        const OPAINTOEN: crate::stm32::opamp::[<$opamp _csr>]::OPAINTOEN_A;
+
+                        #[doc(hidden)]
+                        fn into_output(self) -> Output;
+                    }
+
+                    impl<IntoOutput: Into<$output>> OutputRoute<$output> for IntoOutput {
+                        const OPAINTOEN: crate::stm32::opamp::[<$opamp _csr>]::OPAINTOEN_A =
+                            crate::stm32::opamp::[<$opamp _csr>]::OPAINTOEN_A::OutputPin;
+
+                        fn into_output(self) -> $output {
+                            self.into()
+                        }
+                    }
+
+                    impl OutputRoute<InternalOutput> for InternalOutput {
+                        const OPAINTOEN: crate::stm32::opamp::[<$opamp _csr>]::OPAINTOEN_A =
+                            crate::stm32::opamp::[<$opamp _csr>]::OPAINTOEN_A::Adcchannel;
+
+                        fn into_output(self) -> InternalOutput {
+                            self
+                        }
+                    }
+
                     /// State type for opamp running in voltage follower mode.
-                    pub struct Follower<Input> {
+                    pub struct Follower<Input, Output> {
                         input: Input,
-                        output: Option<$output>,
+                        output: Output,
                     }
 
                     /// State type for opamp running in open-loop mode.
-                    pub struct OpenLoop<NonInverting, Inverting> {
+                    pub struct OpenLoop<NonInverting, Inverting, Output> {
                         non_inverting: NonInverting,
                         inverting: Inverting,
-                        output: Option<$output>,
+                        output: Output,
                     }
 
-                    // TODO: Inverting gain
-
                     /// Trait for opamps that can be run in follower mode.
-                    pub trait IntoFollower <IntoInput, IntoOutput, Input>
-                        where
-                            IntoOutput: Into<$output>,
-                    {
+                    pub trait IntoFollower <IntoInput, Input> {
                         /// Coonfigures the opamp as voltage follower.
-                        fn follower(self, input: IntoInput, output: Option<IntoOutput>) -> Follower<Input>;
+                        fn follower<IntoOutput: OutputRoute<Output>, Output>(self, input: IntoInput, output: IntoOutput) -> Follower<Input, Output>;
                     }
 
                     /// Trait for opamps that can be run in open-loop mode.
-                    pub trait IntoOpenLoop <IntoNonInverting, IntoInverting, IntoOutput, NonInverting, Inverting>
-                        where
-                            IntoOutput: Into<$output>,
-                    {
+                    pub trait IntoOpenLoop <IntoNonInverting, IntoInverting, NonInverting, Inverting> {
                         /// Configures the opamp for open-loop operation.
-                        fn open_loop(self, non_inverting: IntoNonInverting, inverting: IntoInverting, output: Option<IntoOutput>)
-                            -> OpenLoop<NonInverting, Inverting>;
+                        fn open_loop<IntoOutput: OutputRoute<Output>, Output>(self, non_inverting: IntoNonInverting, inverting: IntoInverting, output: IntoOutput)
+                            -> OpenLoop<NonInverting, Inverting, Output>;
                     }
 
                     /// State type for opamp running in programmable-gain mode.
-                    pub struct Pga<NonInverting, MODE> {
+                    pub struct Pga<NonInverting, MODE, Output> {
                         non_inverting: PhantomData<NonInverting>,
                         config: MODE,
-                        output: Option<$output>,
+                        output: Output,
                     }
 
                     /// Trait for opamps that can be run in programmable gain mode.
-                    pub trait IntoPga <MODE, IntoOutput, NonInverting>
-                        where
-                            IntoOutput: Into<$output>,
-                    {
+                    pub trait IntoPga <MODE, NonInverting> {
                         /// Configures the opamp for programmable gain operation.
-                        fn pga<B: Borrow<NonInverting>>(self, non_inverting: B, config: MODE, output: Option<IntoOutput>)
-                            -> Pga<NonInverting, MODE>;
+                        fn pga<B: Borrow<NonInverting>, IntoOutput: OutputRoute<Output>, Output>(self, non_inverting: B, config: MODE, output: IntoOutput)
+                            -> Pga<NonInverting, MODE, Output>;
                     }
 
-                    impl<Input> Follower<Input> {
+                    impl<Input, Output> Follower<Input, Output> {
+
+                        /// Locks the configuration until the next reset.
+                        pub fn lock(self) -> Locked<Self> {
+                            unsafe {
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
+                                    w.lock().read_only());
+                            }
+                            Locked { state: self }
+                        }
 
                         /// Disables the opamp and returns the resources it held.
-                        pub fn disable(self) -> (Disabled, Input, Option<$output>) {
-                            unsafe { (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].reset() }
+                        pub fn disable(self) -> (Disabled, Input, Output) {
+                            unsafe { (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w| w.opaen().disabled()) }
                             (Disabled, self.input, self.output)
                         }
 
+                    }
+
+                    impl<Input> Follower<Input, InternalOutput> {
                         /// Enables the external output pin.
-                        pub fn enable_output(&mut self, output:$output) {
-                            self.output = Some(output);
+                        /// This disconnects the opamp output from the internal ADC.
+                        pub fn enable_output(self, output: $output) -> Follower<Input, $output> {
                             unsafe {
-                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].write(|w|
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
                                     w.opaintoen().output_pin());
                             }
+                            Follower { input: self.input, output }
                         }
+                    }
 
-                        /// Disables the external output.
+                    impl<Input> Follower<Input, $output> {
+                        /// Disables the external output and returns the output pin.
                         /// This will connect the opamp output to the internal ADC.
-                        /// If the output was enabled, the output pin is returned.
-                        pub fn disable_output(&mut self) -> Option<$output> {
+                        pub fn disable_output(self) -> (Follower<Input, InternalOutput>, $output) {
                             unsafe {
-                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].write(|w|
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
                                     w.opaintoen().adcchannel());
                             }
-                            self.output.take()
+                            (Follower { input: self.input, output: InternalOutput }, self.output)
                         }
                     }
 
-                    impl<NonInverting, Inverting> OpenLoop<NonInverting, Inverting> {
+                    impl<NonInverting, Inverting, Output> OpenLoop<NonInverting, Inverting, Output> {
+
+                        /// Locks the configuration until the next reset.
+                        pub fn lock(self) -> Locked<Self> {
+                            unsafe {
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
+                                    w.lock().read_only());
+                            }
+                            Locked { state: self }
+                        }
 
                         /// Disables the opamp and returns the resources it held.
-                        pub fn disable(self) -> (Disabled, NonInverting, Inverting, Option<$output>) {
-                            unsafe { (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].reset() }
+                        pub fn disable(self) -> (Disabled, NonInverting, Inverting, Output) {
+                            unsafe { (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w| w.opaen().disabled()) }
                             (Disabled, self.non_inverting, self.inverting, self.output)
                         }
 
+                    }
+
+                    impl<NonInverting, Inverting> OpenLoop<NonInverting, Inverting, InternalOutput> {
                         /// Enables the external output pin.
-                        pub fn enable_output(&mut self, output:$output) {
-                            self.output = Some(output);
+                        /// This disconnects the opamp output from the internal ADC.
+                        pub fn enable_output(self, output: $output) -> OpenLoop<NonInverting, Inverting, $output> {
                             unsafe {
-                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].write(|w|
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
                                     w.opaintoen().output_pin());
                             }
+                            OpenLoop { non_inverting: self.non_inverting, inverting: self.inverting, output }
                         }
+                    }
 
-                        /// Disables the external output.
+                    impl<NonInverting, Inverting> OpenLoop<NonInverting, Inverting, $output> {
+                        /// Disables the external output and returns the output pin.
                         /// This will connect the opamp output to the internal ADC.
-                        /// If the output was enabled, the output pin is returned.
-                        pub fn disable_output(&mut self) -> Option<$output> {
+                        pub fn disable_output(self) -> (OpenLoop<NonInverting, Inverting, InternalOutput>, $output) {
                             unsafe {
-                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].write(|w|
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
                                     w.opaintoen().adcchannel());
                             }
-                            self.output.take()
+                            (OpenLoop { non_inverting: self.non_inverting, inverting: self.inverting, output: InternalOutput }, self.output)
                         }
                     }
 
-                    impl<NonInverting, MODE> Pga<NonInverting, MODE> {
+                    impl<NonInverting, MODE, Output> Pga<NonInverting, MODE, Output> {
+
+                        /// Locks the configuration until the next reset.
+                        pub fn lock(self) -> Locked<Self> {
+                            unsafe {
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
+                                    w.lock().read_only());
+                            }
+                            Locked { state: self }
+                        }
 
                         /// Disables the opamp and returns the resources it held.
-                        pub fn disable(self) -> (Disabled, MODE, Option<$output>) {
-                            unsafe { (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].reset() }
+                        pub fn disable(self) -> (Disabled, MODE, Output) {
+                            unsafe { (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w| w.opaen().disabled()) }
                             (Disabled, self.config, self.output)
                         }
 
+                    }
+
+                    impl<NonInverting, MODE> Pga<NonInverting, MODE, InternalOutput> {
                         /// Enables the external output pin.
-                        pub fn enable_output(&mut self, output: $output) {
-                            self.output = Some(output);
+                        /// This disconnects the opamp output from the internal ADC.
+                        pub fn enable_output(self, output: $output) -> Pga<NonInverting, MODE, $output> {
                             unsafe {
-                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].write(|w|
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
                                     w.opaintoen().output_pin());
                             }
+                            Pga { non_inverting: PhantomData, config: self.config, output }
                         }
+                    }
 
-                        /// Disables the external output.
+                    impl<NonInverting, MODE> Pga<NonInverting, MODE, $output> {
+                        /// Disables the external output and returns the output pin.
                         /// This will connect the opamp output to the internal ADC.
-                        /// If the output was enabled, the output pin is returned.
-                        pub fn disable_output(&mut self) -> Option<$output> {
+                        pub fn disable_output(self) -> (Pga<NonInverting, MODE, InternalOutput>, $output) {
                             unsafe {
-                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].write(|w|
+                                (*crate::stm32::OPAMP::ptr()).[<$opamp _csr>].modify(|_, w|
                                     w.opaintoen().adcchannel());
                             }
-                            self.output.take()
+                            (Pga { non_inverting: PhantomData, config: self.config, output: InternalOutput }, self.output)
                         }
                     }
 
                     opamps!{ @follower $opamp, $output, $($non_inverting_mask, $non_inverting),* }
                     opamps!{ @open_loop_tt $opamp, $output, $($non_inverting_mask, $non_inverting),* : ($($inverting_mask, $inverting),*) }
-                    opamps!{ @pga_tt $opamp, $output, $($non_inverting_mask, $non_inverting),* : $vinm0 }
+                    opamps!{ @pga_tt $opamp, $output, $($non_inverting_mask, $non_inverting),* : $vinm0, $vinm1 }
                 }
             )*
 
@@ -370,38 +636,32 @@ macro_rules! opamps {
         ),*
     } => {
         paste::paste!{
-            $(impl <IntoInput, IntoOutput> IntoFollower <IntoInput, IntoOutput, $input> for Disabled
+            $(impl <IntoInput> IntoFollower <IntoInput, $input> for Disabled
                 where
                     IntoInput: Into<$input>,
-                    IntoOutput: Into<$output>,
             {
-                fn follower(
+                fn follower<IntoOutput: OutputRoute<Output>, Output>(
                     self,
                     input: IntoInput,
-                    output: Option<IntoOutput>,
-                ) -> Follower<$input> {
+                    output: IntoOutput,
+                ) -> Follower<$input, Output> {
                     let input = input.into();
-                    let output = output.map(|output| output.into());
                     unsafe {
-                        use crate::stm32::opamp::[<$opamp _csr>]::OPAINTOEN_A;
                         (*crate::stm32::OPAMP::ptr())
                             .[<$opamp _csr>]
-                            .write(|csr_w|
+                            .modify(|_, csr_w|
                                 csr_w
                                     .vp_sel()
                                     .$input_mask()
                                     .vm_sel()
                                     .output()
                                     .opaintoen()
-                                    .variant(match output {
-                                        Some(_) => OPAINTOEN_A::OutputPin,
-                                        None => OPAINTOEN_A::Adcchannel,
-                                    })
+                                    .variant(IntoOutput::OPAINTOEN)
                                     .opaen()
                                     .enabled()
                             );
                     }
-                    Follower {input, output}
+                    Follower {input, output: output.into_output()}
                 }
             })*
         }
@@ -435,41 +695,35 @@ macro_rules! opamps {
         ($($inverting_mask:tt, $inverting:ty),*)
     } => {
         paste::paste!{
-            $(impl <IntoNonInverting, IntoInverting, IntoOutput> IntoOpenLoop
-                <IntoNonInverting, IntoInverting, IntoOutput, $non_inverting, $inverting> for Disabled
+            $(impl <IntoNonInverting, IntoInverting> IntoOpenLoop
+                <IntoNonInverting, IntoInverting, $non_inverting, $inverting> for Disabled
                 where
                     IntoNonInverting: Into<$non_inverting>,
                     IntoInverting: Into<$inverting>,
-                    IntoOutput: Into<$output>,
             {
-                fn open_loop(
+                fn open_loop<IntoOutput: OutputRoute<Output>, Output>(
                     self,
                     non_inverting: IntoNonInverting,
                     inverting: IntoInverting,
-                    output: Option<IntoOutput>,
-                ) -> OpenLoop<$non_inverting, $inverting> {
+                    output: IntoOutput,
+                ) -> OpenLoop<$non_inverting, $inverting, Output> {
                     let non_inverting = non_inverting.into();
                     let inverting = inverting.into();
-                    let output = output.map(|output| output.into());
                     unsafe {
-                        use crate::stm32::opamp::[<$opamp _csr>]::OPAINTOEN_A;
                         (*crate::stm32::OPAMP::ptr())
                             .[<$opamp _csr>]
-                            .write(|csr_w|
+                            .modify(|_, csr_w|
                                 csr_w.vp_sel()
                                     .$non_inverting_mask()
                                     .vm_sel()
                                     .$inverting_mask()
                                     .opaintoen()
-                                    .variant(match output {
-                                        Some(_) => OPAINTOEN_A::OutputPin,
-                                        None => OPAINTOEN_A::Adcchannel,
-                                    })
+                                    .variant(IntoOutput::OPAINTOEN)
                                     .opaen()
                                     .enabled()
                             );
                     }
-                    OpenLoop {non_inverting, inverting, output}
+                    OpenLoop {non_inverting, inverting, output: output.into_output()}
                 }
             })*
         }
@@ -484,12 +738,14 @@ macro_rules! opamps {
         $($non_inverting_mask:tt, $non_inverting:ty),*
         :
         $vinm0:ident
+        ,
+        $vinm1:ty
     } => {
         $(
             opamps!{ @pga $opamp, $output, $non_inverting_mask, $non_inverting, crate::opamp::PgaModeInternal }
             opamps!{ @pga $opamp, $output, $non_inverting_mask, $non_inverting, crate::opamp::PgaModeInvertedInputFiltered<$vinm0<crate::gpio::Analog>> }
-            // TODO: Add `PGA mode, non-inverting gain setting (x2/x4/x8/x16/x32/x64) or inverting gain setting (x-1/x-3/x-7/x-15/x-31/x-63)`
-            // TODO: Add `PGA mode, non-inverting gain setting (x2/x4/x8/x16/x32/x64) or inverting gain setting (x-1/x-3/x-7/x-15/x-31/x-63) with filtering`
+            opamps!{ @pga $opamp, $output, $non_inverting_mask, $non_inverting, crate::opamp::PgaModeInverting<$vinm0<crate::gpio::Analog>> }
+            opamps!{ @pga $opamp, $output, $non_inverting_mask, $non_inverting, crate::opamp::PgaModeInvertingFiltered<$vinm0<crate::gpio::Analog>, $vinm1> }
         )*
     };
 
@@ -506,23 +762,17 @@ macro_rules! opamps {
         $mode:ty
     } => {
         paste::paste!{
-            impl<IntoOutput> IntoPga<$mode, IntoOutput, $non_inverting> for Disabled
-                where
-                    IntoOutput: Into<$output>,
-            {
-                fn pga<B: Borrow<$non_inverting>>(
+            impl IntoPga<$mode, $non_inverting> for Disabled {
+                fn pga<B: Borrow<$non_inverting>, IntoOutput: OutputRoute<Output>, Output>(
                     self,
                     _non_inverting: B,
                     config: $mode,
-                    output: Option<IntoOutput>,
-                ) -> Pga<$non_inverting, $mode> {
-                    let output = output.map(|output| output.into());
+                    output: IntoOutput,
+                ) -> Pga<$non_inverting, $mode, Output> {
                     unsafe {
-                        use crate::stm32::opamp::[<$opamp _csr>]::OPAINTOEN_A;
-
                         (*crate::stm32::OPAMP::ptr())
                             .[<$opamp _csr>]
-                            .write(|csr_w|
+                            .modify(|_, csr_w|
                                 csr_w.vp_sel()
                                     .$non_inverting_mask()
                                     .vm_sel()
@@ -530,15 +780,12 @@ macro_rules! opamps {
                                     .pga_gain()
                                     .variant((&config).into())
                                     .opaintoen()
-                                    .variant(match output {
-                                        Some(_) => OPAINTOEN_A::OutputPin,
-                                        None => OPAINTOEN_A::Adcchannel,
-                                    })
+                                    .variant(IntoOutput::OPAINTOEN)
                                     .opaen()
                                     .enabled()
                             );
                     }
-                    Pga {non_inverting: PhantomData, config, output}
+                    Pga {non_inverting: PhantomData, config, output: output.into_output()}
                 }
             }
         }
@@ -549,6 +796,7 @@ macro_rules! opamps {
 opamps! {
     opamp1: {
         vinm0: PA3,
+        vinm1: crate::gpio::gpioc::PC5<crate::gpio::Analog>,
         inverting: {
             crate::gpio::gpioa::PA3<crate::gpio::Analog>: vinm0,
             crate::gpio::gpioc::PC5<crate::gpio::Analog>: vinm1,
@@ -562,6 +810,7 @@ opamps! {
     },
     opamp2: {
         vinm0: PA5,
+        vinm1: crate::gpio::gpioc::PC5<crate::gpio::Analog>,
         inverting: {
             crate::gpio::gpioa::PA5<crate::gpio::Analog>: vinm0,
             crate::gpio::gpioc::PC5<crate::gpio::Analog>: vinm1,
@@ -576,6 +825,7 @@ opamps! {
     },
     opamp3: {
         vinm0: PB2,
+        vinm1: crate::gpio::gpiob::PB10<crate::gpio::Analog>,
         inverting: {
             crate::gpio::gpiob::PB2<crate::gpio::Analog>: vinm0,
             crate::gpio::gpiob::PB10<crate::gpio::Analog>: vinm1,
@@ -598,6 +848,7 @@ opamps! {
 opamps! {
     opamp1: {
         vinm0: PA3,
+        vinm1: crate::gpio::gpioc::PC5<crate::gpio::Analog>,
         inverting: {
             crate::gpio::gpioa::PA3<crate::gpio::Analog>: vinm0,
             crate::gpio::gpioc::PC5<crate::gpio::Analog>: vinm1,
@@ -611,6 +862,7 @@ opamps! {
     },
     opamp2: {
         vinm0: PA5,
+        vinm1: crate::gpio::gpioc::PC5<crate::gpio::Analog>,
         inverting: {
             crate::gpio::gpioa::PA5<crate::gpio::Analog>: vinm0,
             crate::gpio::gpioc::PC5<crate::gpio::Analog>: vinm1,
@@ -625,6 +877,7 @@ opamps! {
     },
     opamp3: {
         vinm0: PB2,
+        vinm1: crate::gpio::gpiob::PB10<crate::gpio::Analog>,
         inverting: {
             crate::gpio::gpiob::PB2<crate::gpio::Analog>: vinm0,
             crate::gpio::gpiob::PB10<crate::gpio::Analog>: vinm1,
@@ -638,6 +891,7 @@ opamps! {
     },
     opamp4: {
         vinm0: PB10,
+        vinm1: crate::gpio::gpiod::PD8<crate::gpio::Analog>,
         inverting: {
             crate::gpio::gpiob::PB10<crate::gpio::Analog>: vinm0,
             crate::gpio::gpiod::PD8<crate::gpio::Analog>: vinm1,
@@ -651,6 +905,7 @@ opamps! {
     },
     opamp5: {
         vinm0: PB15,
+        vinm1: crate::gpio::gpioa::PA3<crate::gpio::Analog>,
         inverting: {
             crate::gpio::gpiob::PB15<crate::gpio::Analog>: vinm0,
             crate::gpio::gpioa::PA3<crate::gpio::Analog>: vinm1,
@@ -664,6 +919,7 @@ opamps! {
     },
     opamp6: {
         vinm0: PA1,
+        vinm1: crate::gpio::gpiob::PB1<crate::gpio::Analog>,
         inverting: {
             crate::gpio::gpioa::PA1<crate::gpio::Analog>: vinm0,
             crate::gpio::gpiob::PB1<crate::gpio::Analog>: vinm1,
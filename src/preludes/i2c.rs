@@ -2,5 +2,7 @@ pub use crate::hal_api::i2c::*;
 
 pub use crate::{
     i2c::{Config, I2cExt},
+    i2c_target::I2cTargetExt,
+    smbus::SMBusExt,
     time::RateExtU32,
 };
\ No newline at end of file
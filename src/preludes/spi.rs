@@ -1,2 +1,3 @@
 pub use crate::spi::*;
-pub use crate::hal_api::spi::*;
\ No newline at end of file
+pub use crate::hal_api::spi::*;
+pub use crate::spi_slave::SpiSlaveExt;
@@ -113,6 +113,25 @@
 //!
 //! Currently only one break input (BKIN or BKIN2) can be enabled, this could be changed to allow two break inputs at the same time.
 //!
+//! ## Comparator break and OCREF_CLR inputs
+//!
+//! Comparator outputs can be used as break source with [PwmBuilder::with_break_comparator](struct.PwmBuilder.html#method.with_break_comparator),
+//! which stops all PWM like a break pin. They are connected to BKIN, together with the break pin if one is configured.
+//!
+//! For cycle-by-cycle current limiting, [PwmBuilder::with_ocref_clear](struct.PwmBuilder.html#method.with_ocref_clear) connects a comparator
+//! to the OCREF_CLR input instead. Each channel with [Pwm::enable_ocref_clear](struct.Pwm.html#method.enable_ocref_clear) set is then
+//! driven inactive while the comparator output is high, and resumes at the start of the next PWM period.
+//!
+//! ```rust,ignore
+//!   let comp1 = comp1.comparator(&pa1, &dac3ch1, Config::default(), &rcc.clocks).blanking(Tim1Oc5).enable();
+//!
+//!   let (mut control, mut pwm) = dp.TIM1.pwm_advanced(pin, &mut rcc)
+//!       .frequency(100.kHz())
+//!       .with_ocref_clear(&comp1)
+//!       .finalize();
+//!   pwm.enable_ocref_clear();
+//! ```
+//!
 //! ## Complementary outputs
 //!
 //! Once a PWM channel has been created through TIMx.pwm(...) or TIMx.pwm_advanced(...).finalize(), it can be put into complementary mode or have its polarity changed.
@@ -193,6 +212,7 @@ use crate::stm32::TIM20;
 use crate::stm32::TIM5;
 use crate::stm32::{TIM1, TIM15, TIM16, TIM17, TIM2, TIM3, TIM4, TIM8};
 
+use crate::comparator::TimerSource;
 use crate::rcc::{Enable, GetBusFreq, Rcc, Reset};
 use crate::time::{ExtU32, Hertz, NanoSecond, RateExtU32};
 
@@ -304,8 +324,10 @@ pub struct PwmBuilder<TIM, PINS, CHANNEL, FAULT, COMP, WIDTH> {
     count: CountSettings<WIDTH>,
     bkin_enabled: bool, // If the FAULT type parameter is FaultEnabled, either bkin or bkin2 must be enabled
     bkin2_enabled: bool,
+    break_comparators: u8, // Bitmask of the comparators connected to BKIN, bit 0 is COMP1
     fault_polarity: Polarity,
     deadtime: NanoSecond,
+    ocref_clear: Option<u8>,
 }
 
 /// Allows a PwmControl to monitor and control faults (break inputs) of a timer's PWM channels
@@ -1220,8 +1242,10 @@ macro_rules! tim_pwm {
                         count: CountSettings::Explicit { period: 65535, prescaler: 0, },
                         bkin_enabled: false,
                         bkin2_enabled: false,
+                        break_comparators: 0,
                         fault_polarity: Polarity::ActiveLow,
                         deadtime: 0.nanos(),
+                        ocref_clear: None,
                     }
                 }
             }
@@ -1265,7 +1289,7 @@ macro_rules! tim_pwm {
                             Polarity::ActiveHigh => true,
                         };
 
-                        if self.bkin_enabled {
+                        if self.bkin_enabled || self.break_comparators != 0 {
                             // BDTR:
                             //  BKF = 1 -> break pin filtering of 2 cycles of CK_INT (peripheral source clock)
                             //  AOE = 0 -> after a fault, master output enable MOE can only be set by software, not automatically
@@ -1275,9 +1299,20 @@ macro_rules! tim_pwm {
                             unsafe { tim.$bdtr.write(|w| w.dtg().bits(dtg).bkf().bits(1).aoe().clear_bit().bke().set_bit().bkp().bit(bkp).moe().$moe_set()); }
 
                             // AF1:
-                            //  BKINE = 1 -> break input enabled
+                            //  BKINE = 1 -> break input enabled, if a break pin is used
                             //  BKINP should make input active high (BDTR BKP will set polarity), bit value varies timer to timer
-                            tim.$af1.write(|w| w.bkine().set_bit().bkinp().$bkinp_setting());
+                            //  BKCMPxE = 1 -> comparator x output connected to break input
+                            let cmp = self.break_comparators;
+                            tim.$af1.write(|w| w
+                                .bkine().bit(self.bkin_enabled).bkinp().$bkinp_setting()
+                                .bkcmp1e().bit(cmp & (1 << 0) != 0)
+                                .bkcmp2e().bit(cmp & (1 << 1) != 0)
+                                .bkcmp3e().bit(cmp & (1 << 2) != 0)
+                                .bkcmp4e().bit(cmp & (1 << 3) != 0)
+                                .bkcmp5e().bit(cmp & (1 << 4) != 0)
+                                .bkcmp6e().bit(cmp & (1 << 5) != 0)
+                                .bkcmp7e().bit(cmp & (1 << 6) != 0)
+                            );
                         }
                         $(
                             // Not all timers that have break inputs have break2 inputs
@@ -1318,6 +1353,14 @@ macro_rules! tim_pwm {
                         }
                     )*
 
+                    // AF2:
+                    //  OCRSEL -> comparator output connected to OCREF_CLR, OCCS is left at its reset value to select it
+                    // ocref_clear is only set for timers with OCREF_CLR support, see tim_ocref_clear
+                    if let Some(comp) = self.ocref_clear {
+                        // Safety: comp is the index of a comparator, which is a valid value for the field per the reference manual
+                        tim.af2.modify(|_, w| unsafe { w.ocrsel().bits(comp) });
+                    }
+
                     tim.cr1.modify(|_, w| w.cen().set_bit());
 
                     unsafe {
@@ -1416,8 +1459,40 @@ macro_rules! tim_pwm {
                             count: self.count,
                             bkin_enabled: self.bkin_enabled || P::INPUT == BreakInput::BreakIn,
                             bkin2_enabled: self.bkin2_enabled || P::INPUT == BreakInput::BreakIn2,
+                            break_comparators: self.break_comparators,
+                            fault_polarity: polarity,
+                            deadtime: self.deadtime,
+                            ocref_clear: self.ocref_clear,
+                        }
+                    }
+                }
+
+                impl<PINS, CHANNEL, FAULT, COMP> PwmBuilder<$TIMX, PINS, CHANNEL, FAULT, COMP, $typ> {
+                    /// Connect a comparator output to the break input (BKIN) to disable PWM when it becomes active (active level based on polarity argument)
+                    /// Multiple comparators can be connected; the polarity is shared with the break pin.
+                    /// Note: comparators always use BKIN, so they can not be combined with a BKIN2 break pin.
+                    ///
+                    /// # Panics
+                    ///
+                    /// Panics if a BKIN2 break pin has been configured.
+                    pub fn with_break_comparator<C: TimerSource>(self, _comp: &C, polarity: Polarity) -> PwmBuilder<$TIMX, PINS, CHANNEL, FaultEnabled, COMP, $typ> {
+                        assert!(!self.bkin2_enabled, "Break comparators can not be combined with a BKIN2 break pin");
+
+                        PwmBuilder {
+                            _tim: PhantomData,
+                            _pins: PhantomData,
+                            _channel: PhantomData,
+                            _fault: PhantomData,
+                            _comp: PhantomData,
+                            alignment: self.alignment,
+                            base_freq: self.base_freq,
+                            count: self.count,
+                            bkin_enabled: self.bkin_enabled,
+                            bkin2_enabled: self.bkin2_enabled,
+                            break_comparators: self.break_comparators | 1 << C::INDEX,
                             fault_polarity: polarity,
                             deadtime: self.deadtime,
+                            ocref_clear: self.ocref_clear,
                         }
                     }
                 }
@@ -1473,14 +1548,76 @@ tim_pwm! {
     feature = "stm32g473",
     feature = "stm32g474",
     feature = "stm32g483",
-    feature = "stm32g484",
-    feature = "stm32g491",
-    feature = "stm32g4a1"
+    feature = "stm32g484"
 ))]
 tim_pwm! {
     TIM20: (tim20, u16, 16, BDTR: bdtr, set_bit, af1, set_bit),
 }
 
+// Implement clearing the output of PWM channels from a comparator (OCREF_CLR)
+macro_rules! tim_ocref_clear {
+    ($($TIMX:ident: [$(($CH:ty, $ccmrx_output:ident, $ocxce:ident)),+],)+) => {
+        $(
+            impl<PINS, CHANNEL, FAULT, COMP, WIDTH> PwmBuilder<$TIMX, PINS, CHANNEL, FAULT, COMP, WIDTH> {
+                /// Connect a comparator output to the OCREF_CLR input of the timer
+                /// Channels with `enable_ocref_clear` set will have their output cleared while the comparator output is high,
+                /// until the next PWM cycle; this can be used for cycle-by-cycle current limiting.
+                pub fn with_ocref_clear<C: TimerSource>(mut self, _comp: &C) -> Self {
+                    self.ocref_clear = Some(C::INDEX);
+
+                    self
+                }
+            }
+
+            $(
+                impl<COMP, POL, NPOL> Pwm<$TIMX, $CH, COMP, POL, NPOL> {
+                    /// Clear the output on a high OCREF_CLR input, see [PwmBuilder::with_ocref_clear]
+                    pub fn enable_ocref_clear(&mut self) {
+                        let tim = unsafe { &*$TIMX::ptr() };
+
+                        tim.$ccmrx_output().modify(|_, w| w.$ocxce().set_bit());
+                    }
+
+                    /// Ignore the OCREF_CLR input
+                    pub fn disable_ocref_clear(&mut self) {
+                        let tim = unsafe { &*$TIMX::ptr() };
+
+                        tim.$ccmrx_output().modify(|_, w| w.$ocxce().clear_bit());
+                    }
+                }
+            )+
+        )+
+    };
+}
+
+tim_ocref_clear! {
+    TIM1: [(C1, ccmr1_output, oc1ce), (C2, ccmr1_output, oc2ce), (C3, ccmr2_output, oc3ce), (C4, ccmr2_output, oc4ce)],
+    TIM2: [(C1, ccmr1_output, oc1ce), (C2, ccmr1_output, oc2ce), (C3, ccmr2_output, oc3ce), (C4, ccmr2_output, oc4ce)],
+    TIM3: [(C1, ccmr1_output, oc1ce), (C2, ccmr1_output, oc2ce), (C3, ccmr2_output, oc3ce), (C4, ccmr2_output, oc4ce)],
+    TIM4: [(C1, ccmr1_output, oc1ce), (C2, ccmr1_output, oc2ce), (C3, ccmr2_output, oc3ce), (C4, ccmr2_output, oc4ce)],
+    TIM8: [(C1, ccmr1_output, oc1ce), (C2, ccmr1_output, oc2ce), (C3, ccmr2_output, oc3ce), (C4, ccmr2_output, oc4ce)],
+    TIM15: [(C1, ccmr1_output, oc1ce)],
+}
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+tim_ocref_clear! {
+    TIM5: [(C1, ccmr1_output, oc1ce), (C2, ccmr1_output, oc2ce), (C3, ccmr2_output, oc3ce), (C4, ccmr2_output, oc4ce)],
+}
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+tim_ocref_clear! {
+    TIM20: [(C1, ccmr1_output, oc1ce), (C2, ccmr1_output, oc2ce), (C3, ccmr2_output, oc3ce), (C4, ccmr2_output, oc4ce)],
+}
+
 pub trait PwmPinEnable {
     fn ccer_enable(&mut self);
     fn ccer_disable(&mut self);
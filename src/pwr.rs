@@ -10,9 +10,37 @@
 //!
 //! There is a Boost Mode that allows higher clock speeds.
 //!
+//! ## Low-power modes
+//!
+//! [`PowerConfiguration`] also gives access to the low-power modes of the device:
+//!
+//! | Mode              | Entered by                                 | Exit                       |
+//! |-------------------|--------------------------------------------|----------------------------|
+//! | Sleep             | [`PowerConfiguration::sleep`]              | Any interrupt              |
+//! | Low-power run     | [`PowerConfiguration::enter_low_power_run`]| Software                   |
+//! | Low-power sleep   | `sleep` while in low-power run             | Any interrupt              |
+//! | Stop 0 / Stop 1   | [`PowerConfiguration::stop`]               | EXTI line (GPIO, RTC, ...) |
+//! | Standby           | [`PowerConfiguration::standby`]            | WKUP pin, RTC, NRST        |
+//! | Shutdown          | [`PowerConfiguration::shutdown`]           | WKUP pin, RTC, NRST        |
+//!
+//! Waking up from Standby or Shutdown restarts the device from reset, use
+//! [`PowerConfiguration::standby_flag`] and [`PowerConfiguration::wakeup_flags`] on boot to find
+//! out why the device woke up.
+//!
+//! ## Voltage monitoring
+//!
+//! The programmable voltage detector (PVD) compares VDD to a [`PvdLevel`] and the peripheral
+//! voltage monitors ([`Pvm`]) compare VDDA to the minimum voltage of the analog peripherals. Both
+//! can be routed to the `PVD_PVM` interrupt through the EXTI, e.g. to save state before a
+//! brown-out reset.
+//!
 //! Adapted from stm32h7xx-hal
 
-use crate::stm32::PWR;
+use crate::exti::{Event, ExtiExt};
+use crate::gpio::SignalEdge;
+use crate::rcc::{Enable, Rcc};
+use crate::stm32::{EXTI, PWR, RCC};
+use cortex_m::peripheral::SCB;
 
 /// Extension trait that constrains the `PWR` peripheral
 pub trait PwrExt {
@@ -21,6 +49,12 @@ pub trait PwrExt {
 
 impl PwrExt for PWR {
     fn constrain(self) -> Pwr {
+        // Enable the PWR register interface
+        unsafe {
+            let rcc_ptr = &(*RCC::ptr());
+            PWR::enable(rcc_ptr);
+        }
+
         Pwr {
             vos: VoltageScale::Range1 {
                 enable_boost: false,
@@ -87,15 +121,430 @@ pub enum VoltageScale {
 /// Generated when the PWR peripheral is frozen. The existence of this
 /// value indicates that the voltage scaling configuration can no
 /// longer be changed.
+#[derive(Clone, Copy)]
 pub struct PowerConfiguration {
     pub(crate) vos: VoltageScale,
 }
 
+/// Stop mode variant
+///
+/// Stop 1 uses the low-power regulator and has a lower consumption than Stop 0, at the cost of
+/// a longer wakeup time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopMode {
+    Stop0 = 0b000,
+    Stop1 = 0b001,
+}
+
+/// Wakeup pins usable to leave Standby and Shutdown mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeupPin {
+    /// PA0
+    WKUP1,
+    /// PC13
+    WKUP2,
+    /// PE6
+    WKUP3,
+    /// PA2
+    WKUP4,
+    /// PC5
+    WKUP5,
+}
+
+/// Active level of a wakeup pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeupPolarity {
+    /// Wake up on a rising edge (high level)
+    High,
+    /// Wake up on a falling edge (low level)
+    Low,
+}
+
+/// GPIO port, used to select the Standby/Shutdown pull configuration registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+}
+
+/// Pull resistor applied to a pin in Standby and Shutdown mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+/// Wakeup sources latched by the PWR unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WakeupFlags {
+    /// Wakeup event on WKUP1-5, indexed by pin number - 1
+    pub pins: [bool; 5],
+    /// Internal wakeup event (RTC, ...)
+    pub internal: bool,
+}
+
+/// PVD threshold level
+///
+/// The approximate falling thresholds are given, the rising thresholds are about 100mV higher.
+/// See the datasheet for the exact values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PvdLevel {
+    /// 2.0V
+    Level0 = 0b000,
+    /// 2.2V
+    Level1 = 0b001,
+    /// 2.3V
+    Level2 = 0b010,
+    /// 2.5V
+    Level3 = 0b011,
+    /// 2.6V
+    Level4 = 0b100,
+    /// 2.8V
+    Level5 = 0b101,
+    /// 2.9V
+    Level6 = 0b110,
+    /// External input on PB7 (PVD_IN), compared to the internal reference voltage
+    External = 0b111,
+}
+
+/// Peripheral voltage monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pvm {
+    /// VDDA vs. COMP min voltage
+    Pvm1,
+    /// VDDA vs. fast DAC min voltage
+    Pvm2,
+    /// VDDA vs. ADC min voltage (1.62V)
+    Pvm3,
+    /// VDDA vs. OPAMP/DAC min voltage
+    Pvm4,
+}
+
+impl Pvm {
+    /// EXTI line the monitor output is connected to
+    pub fn event(self) -> Event {
+        match self {
+            Pvm::Pvm1 => Event::PVM1,
+            Pvm::Pvm2 => Event::PVM2,
+            Pvm::Pvm3 => Event::PVM3,
+            Pvm::Pvm4 => Event::PVM4,
+        }
+    }
+}
+
 impl PowerConfiguration {
     /// Gets the `VoltageScale` which was configured by `Pwr::freeze()`.
     pub fn vos(&self) -> VoltageScale {
         self.vos
     }
+
+    /// Enters Sleep mode until an interrupt occurs.
+    ///
+    /// When the device is in low-power run mode this enters low-power sleep mode.
+    pub fn sleep(&self, scb: &mut SCB) {
+        scb.clear_sleepdeep();
+        cortex_m::asm::dsb();
+        cortex_m::asm::wfi();
+    }
+
+    /// Switches the main regulator to low-power mode.
+    ///
+    /// # Safety
+    ///
+    /// Caller has to ensure the system clock is at most 2 MHz and stays there until
+    /// [`exit_low_power_run`](Self::exit_low_power_run) is called.
+    pub unsafe fn enter_low_power_run(&self) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr1.modify(|_, w| w.lpr().set_bit());
+        while pwr.sr2.read().reglpf().bit_is_clear() {}
+    }
+
+    /// Switches the main regulator back to normal mode, after which the system clock can be raised
+    /// again.
+    pub fn exit_low_power_run(&self) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr1.modify(|_, w| w.lpr().clear_bit());
+        while pwr.sr2.read().reglpf().bit_is_set() {}
+    }
+
+    /// Enters Stop 0 or Stop 1 mode until an EXTI wakeup event occurs.
+    ///
+    /// The device wakes up running from HSI16 with the PLL and HSE turned off, before returning
+    /// the clock configuration of the last [`Rcc::freeze`] is restored.
+    pub fn stop(&self, mode: StopMode, scb: &mut SCB, rcc: &mut Rcc) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr1.modify(|_, w| unsafe { w.lpms().bits(mode as u8) });
+
+        scb.set_sleepdeep();
+        cortex_m::asm::dsb();
+        cortex_m::asm::wfi();
+        scb.clear_sleepdeep();
+
+        rcc.restore_clocks();
+    }
+
+    /// Enters Standby mode. The device restarts from reset when it wakes up.
+    ///
+    /// Only the backup domain, and SRAM2 if [`retain_sram2`](Self::retain_sram2) is set, keep
+    /// their contents.
+    pub fn standby(&self, scb: &mut SCB) -> ! {
+        self.enter_low_power_mode(0b011, scb)
+    }
+
+    /// Enters Shutdown mode, the lowest power mode. The device restarts from reset when it wakes
+    /// up.
+    ///
+    /// Only the backup domain keeps its contents and the brown-out reset is disabled.
+    pub fn shutdown(&self, scb: &mut SCB) -> ! {
+        self.enter_low_power_mode(0b100, scb)
+    }
+
+    fn enter_low_power_mode(&self, lpms: u8, scb: &mut SCB) -> ! {
+        let pwr = unsafe { &*PWR::ptr() };
+        // A pending wakeup flag would prevent the device from entering the mode
+        self.clear_wakeup_flags();
+        pwr.cr1.modify(|_, w| unsafe { w.lpms().bits(lpms) });
+
+        scb.set_sleepdeep();
+        cortex_m::asm::dsb();
+        loop {
+            cortex_m::asm::wfi();
+        }
+    }
+
+    /// Enables a wakeup pin for leaving Standby and Shutdown mode.
+    pub fn enable_wakeup_pin(&self, pin: WakeupPin, polarity: WakeupPolarity) {
+        let pwr = unsafe { &*PWR::ptr() };
+        let low = polarity == WakeupPolarity::Low;
+        pwr.cr4.modify(|_, w| match pin {
+            WakeupPin::WKUP1 => w.wp1().bit(low),
+            WakeupPin::WKUP2 => w.wp2().bit(low),
+            WakeupPin::WKUP3 => w.wp3().bit(low),
+            WakeupPin::WKUP4 => w.wp4().bit(low),
+            WakeupPin::WKUP5 => w.wp5().bit(low),
+        });
+        // Changing the polarity may set the wakeup flag
+        pwr.scr.write(|w| unsafe { w.bits(1 << (pin as u8)) });
+        pwr.cr3.modify(|_, w| match pin {
+            WakeupPin::WKUP1 => w.ewup1().set_bit(),
+            WakeupPin::WKUP2 => w.ewup2().set_bit(),
+            WakeupPin::WKUP3 => w.ewup3().set_bit(),
+            WakeupPin::WKUP4 => w.ewup4().set_bit(),
+            WakeupPin::WKUP5 => w.ewup5().set_bit(),
+        });
+    }
+
+    /// Disables a wakeup pin.
+    pub fn disable_wakeup_pin(&self, pin: WakeupPin) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr3.modify(|_, w| match pin {
+            WakeupPin::WKUP1 => w.ewup1().clear_bit(),
+            WakeupPin::WKUP2 => w.ewup2().clear_bit(),
+            WakeupPin::WKUP3 => w.ewup3().clear_bit(),
+            WakeupPin::WKUP4 => w.ewup4().clear_bit(),
+            WakeupPin::WKUP5 => w.ewup5().clear_bit(),
+        });
+    }
+
+    /// Enables the internal wakeup line, needed to wake up from Standby and Shutdown mode by the
+    /// RTC.
+    pub fn enable_internal_wakeup(&self, enable: bool) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr3.modify(|_, w| w.eiwul().bit(enable));
+    }
+
+    /// Keeps the contents of SRAM2 in Standby mode.
+    pub fn retain_sram2(&self, retain: bool) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr3.modify(|_, w| w.rrs().bit(retain));
+    }
+
+    /// Selects the pull resistor applied to a pin in Standby and Shutdown mode.
+    ///
+    /// The pull configuration only takes effect once it is enabled with
+    /// [`apply_pull_config`](Self::apply_pull_config).
+    pub fn set_standby_pull(&self, port: Port, pin: u8, pull: Pull) {
+        assert!(pin < 16);
+        let pwr = unsafe { &*PWR::ptr() };
+        let mask = 1 << pin;
+        let up = |bits: u32| match pull {
+            Pull::Up => bits | mask,
+            _ => bits & !mask,
+        };
+        let down = |bits: u32| match pull {
+            Pull::Down => bits | mask,
+            _ => bits & !mask,
+        };
+        match port {
+            Port::A => {
+                pwr.pucra.modify(|r, w| unsafe { w.bits(up(r.bits())) });
+                pwr.pdcra.modify(|r, w| unsafe { w.bits(down(r.bits())) });
+            }
+            Port::B => {
+                pwr.pucrb.modify(|r, w| unsafe { w.bits(up(r.bits())) });
+                pwr.pdcrb.modify(|r, w| unsafe { w.bits(down(r.bits())) });
+            }
+            Port::C => {
+                pwr.pucrc.modify(|r, w| unsafe { w.bits(up(r.bits())) });
+                pwr.pdcrc.modify(|r, w| unsafe { w.bits(down(r.bits())) });
+            }
+            Port::D => {
+                pwr.pucrd.modify(|r, w| unsafe { w.bits(up(r.bits())) });
+                pwr.pdcrd.modify(|r, w| unsafe { w.bits(down(r.bits())) });
+            }
+            Port::E => {
+                pwr.pucre.modify(|r, w| unsafe { w.bits(up(r.bits())) });
+                pwr.pdcre.modify(|r, w| unsafe { w.bits(down(r.bits())) });
+            }
+            Port::F => {
+                pwr.pucrf.modify(|r, w| unsafe { w.bits(up(r.bits())) });
+                pwr.pdcrf.modify(|r, w| unsafe { w.bits(down(r.bits())) });
+            }
+            Port::G => {
+                pwr.pucrg.modify(|r, w| unsafe { w.bits(up(r.bits())) });
+                pwr.pdcrg.modify(|r, w| unsafe { w.bits(down(r.bits())) });
+            }
+        }
+    }
+
+    /// Applies the pull configuration of [`set_standby_pull`](Self::set_standby_pull) in Standby
+    /// and Shutdown mode.
+    pub fn apply_pull_config(&self, enable: bool) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr3.modify(|_, w| w.apc().bit(enable));
+    }
+
+    /// Returns true if the device was in Standby mode before the last reset.
+    pub fn standby_flag(&self) -> bool {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.sr1.read().sbf().bit_is_set()
+    }
+
+    /// Clears the Standby flag.
+    pub fn clear_standby_flag(&self) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.scr.write(|w| w.csbf().set_bit());
+    }
+
+    /// Returns the latched wakeup sources.
+    pub fn wakeup_flags(&self) -> WakeupFlags {
+        let pwr = unsafe { &*PWR::ptr() };
+        let sr1 = pwr.sr1.read();
+        WakeupFlags {
+            pins: [
+                sr1.wuf1().bit_is_set(),
+                sr1.wuf2().bit_is_set(),
+                sr1.wuf3().bit_is_set(),
+                sr1.wuf4().bit_is_set(),
+                sr1.wuf5().bit_is_set(),
+            ],
+            internal: sr1.wufi().bit_is_set(),
+        }
+    }
+
+    /// Clears the wakeup flags of all wakeup pins.
+    ///
+    /// The internal wakeup flag is cleared by clearing the flag of the source (e.g. the RTC).
+    pub fn clear_wakeup_flags(&self) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.scr.write(|w| {
+            w.cwuf1()
+                .set_bit()
+                .cwuf2()
+                .set_bit()
+                .cwuf3()
+                .set_bit()
+                .cwuf4()
+                .set_bit()
+                .cwuf5()
+                .set_bit()
+        });
+    }
+
+    /// Enables the programmable voltage detector.
+    pub fn enable_pvd(&self, level: PvdLevel) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr2
+            .modify(|_, w| unsafe { w.pls().bits(level as u8).pvde().set_bit() });
+    }
+
+    /// Disables the programmable voltage detector.
+    pub fn disable_pvd(&self) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr2.modify(|_, w| w.pvde().clear_bit());
+    }
+
+    /// Returns true if VDD is below the selected PVD threshold.
+    pub fn pvd_output(&self) -> bool {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.sr2.read().pvdo().bit_is_set()
+    }
+
+    /// Enables a peripheral voltage monitor.
+    pub fn enable_pvm(&self, pvm: Pvm) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr2.modify(|_, w| match pvm {
+            Pvm::Pvm1 => w.pvmen1().set_bit(),
+            Pvm::Pvm2 => w.pvmen2().set_bit(),
+            Pvm::Pvm3 => w.pvmen3().set_bit(),
+            Pvm::Pvm4 => w.pvmen4().set_bit(),
+        });
+    }
+
+    /// Disables a peripheral voltage monitor.
+    pub fn disable_pvm(&self, pvm: Pvm) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.cr2.modify(|_, w| match pvm {
+            Pvm::Pvm1 => w.pvmen1().clear_bit(),
+            Pvm::Pvm2 => w.pvmen2().clear_bit(),
+            Pvm::Pvm3 => w.pvmen3().clear_bit(),
+            Pvm::Pvm4 => w.pvmen4().clear_bit(),
+        });
+    }
+
+    /// Returns true if VDDA is below the threshold of the peripheral voltage monitor.
+    pub fn pvm_output(&self, pvm: Pvm) -> bool {
+        let pwr = unsafe { &*PWR::ptr() };
+        let sr2 = pwr.sr2.read();
+        match pvm {
+            Pvm::Pvm1 => sr2.pvmo1().bit_is_set(),
+            Pvm::Pvm2 => sr2.pvmo2().bit_is_set(),
+            Pvm::Pvm3 => sr2.pvmo3().bit_is_set(),
+            Pvm::Pvm4 => sr2.pvmo4().bit_is_set(),
+        }
+    }
+
+    /// Routes the PVD output to the EXTI.
+    ///
+    /// `SignalEdge::Rising` triggers when VDD drops below the threshold, `SignalEdge::Falling`
+    /// when it rises above it again. Clear the event with `exti.unpend(Event::PVD)`.
+    pub fn listen_pvd(&self, edge: SignalEdge, exti: &EXTI) {
+        exti.listen(Event::PVD, edge);
+    }
+
+    /// Stops routing the PVD output to the EXTI.
+    pub fn unlisten_pvd(&self, exti: &EXTI) {
+        exti.unlisten(Event::PVD);
+    }
+
+    /// Routes a peripheral voltage monitor output to the EXTI.
+    ///
+    /// `SignalEdge::Rising` triggers when VDDA drops below the threshold, `SignalEdge::Falling`
+    /// when it rises above it again. Clear the event with `exti.unpend(pvm.event())`.
+    pub fn listen_pvm(&self, pvm: Pvm, edge: SignalEdge, exti: &EXTI) {
+        exti.listen(pvm.event(), edge);
+    }
+
+    /// Stops routing a peripheral voltage monitor output to the EXTI.
+    pub fn unlisten_pvm(&self, pvm: Pvm, exti: &EXTI) {
+        exti.unlisten(pvm.event());
+    }
 }
 
 /// Returns the voltage scale at the current moment
@@ -106,7 +555,8 @@ pub(crate) fn current_vos() -> VoltageScale {
     match pwr.cr1.read().vos().bits() {
         0b00 => unreachable!(),
         0b01 => VoltageScale::Range1 {
-            enable_boost: pwr.cr5.read().r1mode().bit(),
+            // R1MODE is cleared in boost mode
+            enable_boost: pwr.cr5.read().r1mode().bit_is_clear(),
         },
         0b10 => VoltageScale::Range2,
         0b11 => unreachable!(),
@@ -121,7 +571,6 @@ pub(crate) fn current_vos() -> VoltageScale {
 /// SAFETY: Caller has to ensure we are in a valid frequency range, wait states etc to enter the new voltage range
 /// and that the correct sequence is respected (see 'Dynamic voltage scaling management' in RM0440). Also ensure unique
 /// access of PWR peripheral
-#[allow(dead_code)]
 pub(crate) unsafe fn set_vos(vos: VoltageScale) {
     let pwr = unsafe { &*PWR::ptr() };
 
@@ -0,0 +1,398 @@
+//! Quadrature Encoder Interface
+//!
+//! Puts a general purpose or advanced timer (`TIM1`, `TIM[2-5]`, `TIM8`, `TIM20`) into encoder
+//! mode. The encoder signals A and B are connected to channel 1 and 2 of the timer, the same pins
+//! that can be used for PWM on these channels.
+//!
+//! ```ignore
+//!   let gpioa = dp.GPIOA.split(&mut rcc);
+//!   let pins = (gpioa.pa8.into_alternate(), gpioa.pa9.into_alternate());
+//!   let mut qei = dp.TIM1.qei(pins, qei::Config::default(), &mut rcc);
+//!
+//!   let position = qei.position();
+//! ```
+//!
+//! The G4 timers can also reset the counter on an index pulse, see [`IndexConfig`]. The index
+//! signal is taken from the `TIMx_ETR` input of the timer, that pin has to be put into the
+//! corresponding alternate function by the user.
+use crate::pwm::{Pins, C1, C2};
+use crate::rcc::{Enable, Rcc, Reset};
+use crate::stm32::RCC;
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+use crate::stm32::TIM20;
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+use crate::stm32::TIM5;
+use crate::stm32::{TIM1, TIM2, TIM3, TIM4, TIM8};
+
+pub use hal_api_old::Direction;
+
+/// QeiPins marks a pair of pins that can be used as encoder inputs (channel 1 and 2) of a timer
+pub trait QeiPins<TIM, COMP> {}
+
+impl<TIM, P1, P2, COMP1, COMP2> QeiPins<TIM, (COMP1, COMP2)> for (P1, P2)
+where
+    P1: Pins<TIM, C1, COMP1>,
+    P2: Pins<TIM, C2, COMP2>,
+{
+}
+
+/// Edges of the encoder signals that are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncoderMode {
+    /// Count both edges of input A (x2)
+    CountA = 0b001,
+    /// Count both edges of input B (x2)
+    CountB = 0b010,
+    /// Count both edges of both inputs (x4)
+    CountAB = 0b011,
+}
+
+/// Encoder configuration
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    mode: EncoderMode,
+    filter: u8,
+    invert_a: bool,
+    invert_b: bool,
+}
+
+impl Config {
+    /// Selects which edges are counted.
+    pub fn mode(mut self, mode: EncoderMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the digital input filter (ICxF) of both inputs, 0 disables the filter and 15 gives
+    /// the strongest filtering. See the reference manual for the sampling rates.
+    pub fn filter(mut self, filter: u8) -> Self {
+        assert!(filter < 16);
+        self.filter = filter;
+        self
+    }
+
+    /// Inverts input A, which reverses the counting direction.
+    pub fn invert_a(mut self, invert: bool) -> Self {
+        self.invert_a = invert;
+        self
+    }
+
+    /// Inverts input B, which reverses the counting direction.
+    pub fn invert_b(mut self, invert: bool) -> Self {
+        self.invert_b = invert;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            mode: EncoderMode::CountAB,
+            filter: 0,
+            invert_a: false,
+            invert_b: false,
+        }
+    }
+}
+
+/// Direction(s) in which the index pulse resets the counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexDirection {
+    Both = 0b00,
+    Up = 0b01,
+    Down = 0b10,
+}
+
+/// Index configuration
+#[derive(Debug, Clone, Copy)]
+pub struct IndexConfig {
+    direction: IndexDirection,
+    position: u8,
+    first_only: bool,
+    invert: bool,
+    filter: u8,
+}
+
+impl IndexConfig {
+    /// Selects the counting direction(s) in which the index resets the counter.
+    pub fn direction(mut self, direction: IndexDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Selects at which state of inputs A and B (IPOS, `0bBA`) the index resets the counter.
+    pub fn position(mut self, position: u8) -> Self {
+        assert!(position < 4);
+        self.position = position;
+        self
+    }
+
+    /// Only the first index pulse resets the counter.
+    pub fn first_only(mut self, first_only: bool) -> Self {
+        self.first_only = first_only;
+        self
+    }
+
+    /// Inverts the index input.
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+
+    /// Sets the digital filter (ETF) of the index input.
+    pub fn filter(mut self, filter: u8) -> Self {
+        assert!(filter < 16);
+        self.filter = filter;
+        self
+    }
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        IndexConfig {
+            direction: IndexDirection::Both,
+            position: 0b00,
+            first_only: false,
+            invert: false,
+            filter: 0,
+        }
+    }
+}
+
+/// Interrupt events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The counter over- or underflowed
+    Overflow,
+    /// The counter was reset by the index
+    Index,
+    /// The counting direction changed
+    DirectionChange,
+}
+
+/// Hardware quadrature encoder interface peripheral
+pub struct Qei<TIM, PINS> {
+    tim: TIM,
+    pins: PINS,
+    last_count: u32,
+    position: i64,
+}
+
+pub trait QeiExt: Sized {
+    fn qei<PINS, COMP>(self, pins: PINS, config: Config, rcc: &mut Rcc) -> Qei<Self, PINS>
+    where
+        PINS: QeiPins<Self, COMP>;
+}
+
+macro_rules! qei {
+    ($($TIMX:ident: $typ:ty, $ityp:ty,)+) => {
+        $(
+            impl QeiExt for $TIMX {
+                fn qei<PINS, COMP>(self, pins: PINS, config: Config, _rcc: &mut Rcc) -> Qei<Self, PINS>
+                where
+                    PINS: QeiPins<Self, COMP>,
+                {
+                    unsafe {
+                        let rcc_ptr = &(*RCC::ptr());
+                        $TIMX::enable(rcc_ptr);
+                        $TIMX::reset(rcc_ptr);
+                    }
+
+                    // Configure TI1 and TI2 as inputs
+                    self.ccmr1_input().write(|w| unsafe {
+                        w.cc1s()
+                            .bits(0b01)
+                            .ic1f()
+                            .bits(config.filter)
+                            .cc2s()
+                            .bits(0b01)
+                            .ic2f()
+                            .bits(config.filter)
+                    });
+                    self.ccer.write(|w| {
+                        w.cc1p()
+                            .bit(config.invert_a)
+                            .cc2p()
+                            .bit(config.invert_b)
+                    });
+
+                    // Count over the full range of the counter
+                    self.arr.write(|w| unsafe { w.bits(<$typ>::MAX as u32) });
+                    self.smcr.write(|w| unsafe { w.sms().bits(config.mode as u8) });
+                    self.cr1.write(|w| w.cen().set_bit());
+
+                    Qei {
+                        tim: self,
+                        pins,
+                        last_count: 0,
+                        position: 0,
+                    }
+                }
+            }
+
+            impl<PINS> Qei<$TIMX, PINS> {
+                /// Returns the current value of the counter.
+                pub fn count(&self) -> $typ {
+                    self.tim.cnt.read().bits() as $typ
+                }
+
+                /// Returns the current counting direction.
+                pub fn direction(&self) -> Direction {
+                    if self.tim.cr1.read().dir().bit_is_clear() {
+                        Direction::Upcounting
+                    } else {
+                        Direction::Downcounting
+                    }
+                }
+
+                /// Returns the position including the over- and underflows of the counter.
+                ///
+                /// This has to be called at least once per half a counter range of movement to
+                /// keep track of the overflows. A reset of the counter by the index is seen as
+                /// movement as well.
+                pub fn position(&mut self) -> i64 {
+                    let count = self.count();
+                    let delta = count.wrapping_sub(self.last_count as $typ) as $ityp;
+                    self.last_count = count as u32;
+                    self.position += delta as i64;
+                    self.position
+                }
+
+                /// Resets the counter and the position to 0.
+                pub fn reset(&mut self) {
+                    self.tim.cnt.reset();
+                    self.last_count = 0;
+                    self.position = 0;
+                }
+
+                /// Resets the counter on a pulse of the index input (ETR).
+                pub fn enable_index(&mut self, config: IndexConfig) {
+                    // Use the ETR pin as ETR input
+                    self.tim.af1.modify(|_, w| unsafe { w.etrsel().bits(0) });
+                    self.tim.smcr.modify(|_, w| unsafe {
+                        w.etp()
+                            .bit(config.invert)
+                            .etps()
+                            .bits(0)
+                            .etf()
+                            .bits(config.filter)
+                    });
+                    self.tim.ecr.write(|w| unsafe {
+                        w.idir()
+                            .bits(config.direction as u8)
+                            .ipos()
+                            .bits(config.position)
+                            .fidx()
+                            .bit(config.first_only)
+                            .ie()
+                            .set_bit()
+                    });
+                }
+
+                /// Stops resetting the counter on an index pulse.
+                pub fn disable_index(&mut self) {
+                    self.tim.ecr.modify(|_, w| w.ie().clear_bit());
+                }
+
+                /// Starts listening for an `event`
+                pub fn listen(&mut self, event: Event) {
+                    self.tim.dier.modify(|_, w| match event {
+                        Event::Overflow => w.uie().set_bit(),
+                        Event::Index => w.idxie().set_bit(),
+                        Event::DirectionChange => w.dirie().set_bit(),
+                    });
+                }
+
+                /// Stops listening for an `event`
+                pub fn unlisten(&mut self, event: Event) {
+                    self.tim.dier.modify(|_, w| match event {
+                        Event::Overflow => w.uie().clear_bit(),
+                        Event::Index => w.idxie().clear_bit(),
+                        Event::DirectionChange => w.dirie().clear_bit(),
+                    });
+                }
+
+                /// Returns true if the `event` occurred.
+                pub fn is_pending(&self, event: Event) -> bool {
+                    let sr = self.tim.sr.read();
+                    match event {
+                        Event::Overflow => sr.uif().bit_is_set(),
+                        Event::Index => sr.idxf().bit_is_set(),
+                        Event::DirectionChange => sr.dirf().bit_is_set(),
+                    }
+                }
+
+                /// Clears the flag of the `event`.
+                pub fn clear_interrupt(&mut self, event: Event) {
+                    self.tim.sr.write(|w| {
+                        // Flags are cleared by writing 0, writing 1 has no effect
+                        unsafe { w.bits(0xffff_ffff) };
+                        match event {
+                            Event::Overflow => w.uif().clear_bit(),
+                            Event::Index => w.idxf().clear_bit(),
+                            Event::DirectionChange => w.dirf().clear_bit(),
+                        }
+                    });
+                }
+
+                /// Releases the TIM peripheral and the pins
+                pub fn release(self) -> ($TIMX, PINS) {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    (self.tim, self.pins)
+                }
+            }
+
+            impl<PINS> hal_api_old::Qei for Qei<$TIMX, PINS> {
+                type Count = $typ;
+
+                fn count(&self) -> $typ {
+                    self.count()
+                }
+
+                fn direction(&self) -> Direction {
+                    self.direction()
+                }
+            }
+        )+
+    };
+}
+
+qei! {
+    TIM1: u16, i16,
+    TIM2: u32, i32,
+    TIM3: u16, i16,
+    TIM4: u16, i16,
+    TIM8: u16, i16,
+}
+
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+qei! {
+    TIM5: u32, i32,
+}
+
+#[cfg(any(
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+qei! {
+    TIM20: u16, i16,
+}
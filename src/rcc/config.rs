@@ -16,15 +16,20 @@ pub enum Prescaler {
 }
 
 /// System clock mux source
+#[derive(Clone, Copy)]
 pub enum SysClockSrc {
     PLL,
     HSI,
     HSE(Hertz),
 }
 
+/// 48MHz clock source, used by the USB and RNG peripherals
+#[derive(Clone, Copy)]
 pub enum CK48Src {
     OFF,
-    HSI48
+    HSI48,
+    /// PLL Q output, which has to be configured to 48MHz
+    PLLQ,
 }
 
 /// Microcontroller clock output source
@@ -37,6 +42,14 @@ pub enum MCOSrc {
     LSE,
 }
 
+/// RTC clock input source
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RTCSrc {
+    LSE,
+    LSE_BYPASS,
+    LSI,
+}
+
 /// Low-speed clocks output source
 pub enum LSCOSrc {
     LSI,
@@ -331,6 +344,7 @@ impl Default for PllConfig {
 }
 
 /// Clocks configutation
+#[derive(Clone, Copy)]
 pub struct Config {
     pub(crate) sys_mux: SysClockSrc,
     pub(crate) ck48_mux: CK48Src,
@@ -145,6 +145,13 @@ bus! {
     UCPD1 => (APB1_2, 8),
 }
 
+// The WWDG has no reset bit in the RCC
+impl crate::Sealed for crate::stm32::WWDG {}
+impl RccBus for crate::stm32::WWDG {
+    type Bus = APB1_1;
+}
+bus_enable!(WWDG => (APB1_1, 11));
+
 #[cfg(any(
     feature = "stm32g471",
     feature = "stm32g473",
@@ -75,11 +75,65 @@ pub struct Rcc {
     /// Clock configuration
     pub clocks: Clocks,
     pub(crate) rb: RCC,
+    /// Configuration applied by the last `freeze`, used to restore the clocks after Stop mode
+    pub(crate) config: Option<(Config, PowerConfiguration)>,
 }
 
 impl Rcc {
     /// Apply clock configuration
+    ///
+    /// This may be called again at runtime to switch to a different clock and voltage scale
+    /// configuration, e.g. to drop from 170MHz in Range1 boost mode to 26MHz in Range2. Any
+    /// frequencies previously derived from `clocks` have to be recomputed by the caller.
     pub fn freeze(mut self, rcc_cfg: Config, pwr_cfg: PowerConfiguration) -> Self {
+        self.apply_config(rcc_cfg, pwr_cfg);
+        self
+    }
+
+    /// Re-applies the clock configuration of the last `freeze`.
+    ///
+    /// Waking up from Stop mode leaves the device running from HSI16 with the PLL and HSE turned
+    /// off. This brings the clock tree back to the state described by `clocks`.
+    pub fn restore_clocks(&mut self) {
+        if let Some((rcc_cfg, pwr_cfg)) = self.config {
+            self.apply_config(rcc_cfg, pwr_cfg);
+        }
+    }
+
+    fn apply_config(&mut self, rcc_cfg: Config, pwr_cfg: PowerConfiguration) {
+        let present_vos_mode = pwr::current_vos();
+        let target_vos_mode = pwr_cfg.vos();
+
+        // The PLL can not be reconfigured while it drives the system clock, so run from HSI16 in
+        // the meantime. The present wait states are sufficient for this lower frequency.
+        if self.rb.cfgr.read().sws().bits() == 0b11 {
+            self.enable_hsi();
+
+            // Leaving a PLL in Range1 boost mode also has to pass through a halved AHB clock
+            let boost = present_vos_mode == pwr::VoltageScale::Range1 { enable_boost: true };
+            let ahb_psc_bits = self.rb.cfgr.read().hpre().bits();
+            if boost {
+                self.halve_ahb();
+            }
+
+            // SW has no enumerated values on every part, which makes writing its bits unsafe there
+            #[allow(unused_unsafe)]
+            self.rb.cfgr.modify(|_, w| unsafe { w.sw().bits(0b01) });
+            while self.rb.cfgr.read().sws().bits() != 0b01 {}
+
+            if boost {
+                self.restore_ahb(HSI_FREQ, ahb_psc_bits);
+            }
+        }
+
+        // When leaving Range2 the core voltage has to be raised before the PLL and the system
+        // clock are sped up.
+        if present_vos_mode == pwr::VoltageScale::Range2
+            && target_vos_mode != pwr::VoltageScale::Range2
+        {
+            unsafe { pwr::set_vos(target_vos_mode) };
+        }
+
         let pll_clk = self.config_pll(rcc_cfg.pll_cfg);
 
         let (sys_clk, sw_bits) = match rcc_cfg.sys_mux {
@@ -107,6 +161,14 @@ impl Rcc {
                 HSI48_FREQ.Hz()
             }
 
+            CK48Src::PLLQ => {
+                let freq = pll_clk.q.expect("PLL Q output is not configured");
+                // CLK48SEL has no enumerated values on every part, 0b10 selects PLLQ on all of them
+                #[allow(unused_unsafe)]
+                self.rb.ccipr.modify(|_, w| unsafe { w.clk48sel().bits(0b10) });
+                freq
+            }
+
             _ => {0.Hz()}
         };
 
@@ -137,23 +199,44 @@ impl Rcc {
             _ => (sys_freq, 0b000),
         };
 
-        let present_vos_mode = pwr::current_vos();
-        let target_vos_mode = pwr_cfg.vos();
-
         match (present_vos_mode, target_vos_mode) {
             // From VoltageScale::Range1 boost
             (
                 pwr::VoltageScale::Range1 { enable_boost: true },
                 pwr::VoltageScale::Range1 { enable_boost: true },
-            ) => (), // No change
+            ) => self.switch_sysclk(
+                &pwr_cfg,
+                sys_freq,
+                ahb_psc_bits,
+                apb1_psc_bits,
+                apb2_psc_bits,
+                sw_bits,
+            ),
             (
                 pwr::VoltageScale::Range1 { enable_boost: true },
                 pwr::VoltageScale::Range1 {
                     enable_boost: false,
                 },
-            ) => todo!(),
+            ) => {
+                self.range1_boost_to_normal(
+                    &pwr_cfg,
+                    sys_freq,
+                    apb1_psc_bits,
+                    apb2_psc_bits,
+                    sw_bits,
+                    ahb_psc_bits,
+                );
+            }
             (pwr::VoltageScale::Range1 { enable_boost: true }, pwr::VoltageScale::Range2) => {
-                todo!()
+                // Leave boost mode first, the voltage is lowered to Range2 below
+                self.range1_boost_to_normal(
+                    &pwr_cfg,
+                    sys_freq,
+                    apb1_psc_bits,
+                    apb2_psc_bits,
+                    sw_bits,
+                    ahb_psc_bits,
+                );
             }
 
             // From VoltageScale::Range1 normal
@@ -179,41 +262,78 @@ impl Rcc {
                 pwr::VoltageScale::Range1 {
                     enable_boost: false,
                 },
-            ) => (), // No change
+            ) => self.switch_sysclk(
+                &pwr_cfg,
+                sys_freq,
+                ahb_psc_bits,
+                apb1_psc_bits,
+                apb2_psc_bits,
+                sw_bits,
+            ),
             (
                 pwr::VoltageScale::Range1 {
                     enable_boost: false,
                 },
                 pwr::VoltageScale::Range2,
-            ) => todo!(),
+            ) => {
+                // The voltage is lowered to Range2 below, once the system clock has been slowed
+                // down
+                self.switch_sysclk(
+                    &pwr_cfg,
+                    sys_freq,
+                    ahb_psc_bits,
+                    apb1_psc_bits,
+                    apb2_psc_bits,
+                    sw_bits,
+                )
+            }
 
-            // From VoltageScale::Range2
+            // From VoltageScale::Range2, the voltage has already been raised to Range1 normal
+            // above
             (pwr::VoltageScale::Range2, pwr::VoltageScale::Range1 { enable_boost: true }) => {
-                todo!()
+                self.range1_normal_to_boost(
+                    &pwr_cfg,
+                    sys_freq,
+                    apb1_psc_bits,
+                    apb2_psc_bits,
+                    sw_bits,
+                    ahb_psc_bits,
+                );
             }
             (
                 pwr::VoltageScale::Range2,
                 pwr::VoltageScale::Range1 {
                     enable_boost: false,
                 },
-            ) => todo!(),
-            (pwr::VoltageScale::Range2, pwr::VoltageScale::Range2) => (), // No change
+            ) => self.switch_sysclk(
+                &pwr_cfg,
+                sys_freq,
+                ahb_psc_bits,
+                apb1_psc_bits,
+                apb2_psc_bits,
+                sw_bits,
+            ),
+            (pwr::VoltageScale::Range2, pwr::VoltageScale::Range2) => self.switch_sysclk(
+                &pwr_cfg,
+                sys_freq,
+                ahb_psc_bits,
+                apb1_psc_bits,
+                apb2_psc_bits,
+                sw_bits,
+            ),
         }
 
-        Self::configure_wait_states(&pwr_cfg, sys_freq);
-
-        self.rb.cfgr.modify(|_, w| unsafe {
-            w.hpre()
-                .bits(ahb_psc_bits)
-                .ppre1()
-                .bits(apb1_psc_bits)
-                .ppre2()
-                .bits(apb2_psc_bits)
-                .sw()
-                .bits(sw_bits)
-        });
+        // When entering Range2 the core voltage can only be lowered after the system clock has
+        // been slowed down.
+        if present_vos_mode != pwr::VoltageScale::Range2
+            && target_vos_mode == pwr::VoltageScale::Range2
+        {
+            unsafe { pwr::set_vos(target_vos_mode) };
+        }
 
-        while self.rb.cfgr.read().sws().bits() != sw_bits {}
+        // Now that the final clock and voltage are in place, drop any wait states that were only
+        // needed during the transition.
+        Self::configure_wait_states(&pwr_cfg, sys_freq);
 
         // From RM:
         // The timer clock frequencies are automatically defined by hardware. There are two cases:
@@ -230,20 +350,18 @@ impl Rcc {
             _ => apb2_freq * 2,
         };
 
-        Rcc {
-            rb: self.rb,
-            clocks: Clocks {
-                pll_clk,
-                sys_clk,
-                ck48_clk,
-                core_clk: ahb_freq.Hz(),
-                ahb_clk: ahb_freq.Hz(),
-                apb1_clk: apb1_freq.Hz(),
-                apb1_tim_clk: apb1_tim_clk.Hz(),
-                apb2_clk: apb2_freq.Hz(),
-                apb2_tim_clk: apb2_tim_clk.Hz(),
-            },
-        }
+        self.clocks = Clocks {
+            pll_clk,
+            sys_clk,
+            ck48_clk,
+            core_clk: ahb_freq.Hz(),
+            ahb_clk: ahb_freq.Hz(),
+            apb1_clk: apb1_freq.Hz(),
+            apb1_tim_clk: apb1_tim_clk.Hz(),
+            apb2_clk: apb2_freq.Hz(),
+            apb2_tim_clk: apb2_tim_clk.Hz(),
+        };
+        self.config = Some((rcc_cfg, pwr_cfg));
     }
 
     pub fn unlock_rtc(&mut self) {
@@ -252,6 +370,46 @@ impl Rcc {
         pwr.cr1.modify(|_, w| w.dbp().set_bit());
     }
 
+    /// Enables the RTC kernel clock and the RTC register interface.
+    ///
+    /// Selecting a different source than the one currently in use requires a backup domain reset,
+    /// which also clears the RTC calendar and backup registers.
+    pub fn enable_rtc(&mut self, src: RTCSrc) {
+        self.unlock_rtc();
+
+        let rtc_sel = match src {
+            RTCSrc::LSE => {
+                self.enable_lse(false);
+                0b01
+            }
+            RTCSrc::LSE_BYPASS => {
+                self.enable_lse(true);
+                0b01
+            }
+            RTCSrc::LSI => {
+                self.enable_lsi();
+                0b10
+            }
+        };
+
+        let bdcr = self.rb.bdcr.read();
+        if bdcr.rtcsel().bits() != rtc_sel && bdcr.rtcsel().bits() != 0b00 {
+            // The RTC clock source can only be changed after a backup domain reset
+            self.rb.bdcr.modify(|_, w| w.bdrst().set_bit());
+            self.rb.bdcr.modify(|_, w| w.bdrst().clear_bit());
+            if let RTCSrc::LSE | RTCSrc::LSE_BYPASS = src {
+                self.enable_lse(src == RTCSrc::LSE_BYPASS);
+            }
+        }
+
+        // RTCSEL has no enumerated values on every part, which makes writing its bits unsafe there
+        #[allow(unused_unsafe)]
+        self.rb
+            .bdcr
+            .modify(|_, w| unsafe { w.rtcsel().bits(rtc_sel) }.rtcen().set_bit());
+        self.rb.apb1enr1.modify(|_, w| w.rtcapben().set_bit());
+    }
+
     fn config_pll(&self, pll_cfg: PllConfig) -> PLLClocks {
         // Disable PLL
         self.rb.cr.modify(|_, w| w.pllon().clear_bit());
@@ -335,10 +493,33 @@ impl Rcc {
     }
 
     fn configure_wait_states(pwr_cfg: &PowerConfiguration, sys_freq: u32) {
+        let latency = Self::wait_states(pwr_cfg, sys_freq);
+
+        unsafe {
+            // Adjust flash wait states
+            let flash = &(*FLASH::ptr());
+            flash.acr.modify(|_, w| w.latency().bits(latency))
+        }
+    }
+
+    /// Raises the flash wait states to cover the new frequency without lowering them, so they are
+    /// also sufficient for the present frequency during a clock switch.
+    fn raise_wait_states(pwr_cfg: &PowerConfiguration, sys_freq: u32) {
+        let latency = Self::wait_states(pwr_cfg, sys_freq);
+
+        unsafe {
+            let flash = &(*FLASH::ptr());
+            flash
+                .acr
+                .modify(|r, w| w.latency().bits(latency.max(r.latency().bits())))
+        }
+    }
+
+    fn wait_states(pwr_cfg: &PowerConfiguration, sys_freq: u32) -> u8 {
         // Calculate wait states depending on voltage scale and sys_freq
         //
         // See 'Number of wait states according to CPU clock (HCLK) frequency' in RM0440
-        let latency = match pwr_cfg.vos() {
+        match pwr_cfg.vos() {
             pwr::VoltageScale::Range1 { enable_boost: true } => match sys_freq {
                 0..=34_000_000 => 0b0000,
                 34_000_001..=68_000_000 => 0b0001,
@@ -372,13 +553,79 @@ impl Rcc {
                     sys_freq
                 ),
             },
-        };
+        }
+    }
 
-        unsafe {
-            // Adjust flash wait states
-            let flash = &(*FLASH::ptr());
-            flash.acr.modify(|_, w| w.latency().bits(latency))
+    /// Switches to the new system clock and bus prescalers.
+    ///
+    /// In Range1 boost mode a switch to more than 80MHz has to pass through an AHB clock divided
+    /// by 2, like the switches into and out of boost mode.
+    fn switch_sysclk(
+        &mut self,
+        pwr_cfg: &PowerConfiguration,
+        sys_freq: u32,
+        ahb_psc_bits: u8,
+        apb1_psc_bits: u8,
+        apb2_psc_bits: u8,
+        sw_bits: u8,
+    ) {
+        Self::raise_wait_states(pwr_cfg, sys_freq);
+
+        if pwr_cfg.vos() == (pwr::VoltageScale::Range1 { enable_boost: true })
+            && sys_freq > 80_000_000
+        {
+            self.halve_ahb();
+
+            self.rb.cfgr.modify(|_, w| unsafe {
+                w.ppre1()
+                    .bits(apb1_psc_bits)
+                    .ppre2()
+                    .bits(apb2_psc_bits)
+                    .sw()
+                    .bits(sw_bits)
+            });
+
+            while self.rb.cfgr.read().sws().bits() != sw_bits {}
+
+            self.restore_ahb(sys_freq, ahb_psc_bits);
+            return;
         }
+
+        self.rb.cfgr.modify(|_, w| unsafe {
+            w.hpre()
+                .bits(ahb_psc_bits)
+                .ppre1()
+                .bits(apb1_psc_bits)
+                .ppre2()
+                .bits(apb2_psc_bits)
+                .sw()
+                .bits(sw_bits)
+        });
+
+        while self.rb.cfgr.read().sws().bits() != sw_bits {}
+    }
+
+    /// Divides the present AHB clock by 2, required around switches into and out of Range1
+    /// boost mode.
+    fn halve_ahb(&mut self) {
+        let half_apb = (self.rb.cfgr.read().hpre().bits() + 1).clamp(0b1000, 0b1111);
+        self.rb
+            .cfgr
+            .modify(|_r, w| unsafe { w.hpre().bits(half_apb) });
+        while self.rb.cfgr.read().hpre().bits() != half_apb {}
+    }
+
+    /// Waits for at least 1us and then sets the final AHB prescaler.
+    fn restore_ahb(&mut self, sys_freq: u32, ahb_psc_bits: u8) {
+        let us_per_s = 1_000_000;
+        // Number of cycles @ sys_freq for 1us, rounded up, this will
+        // likely end up being 2us since the AHB prescaler is changed
+        let delay_cycles = (sys_freq + us_per_s - 1) / us_per_s;
+        cortex_m::asm::delay(delay_cycles);
+
+        self.rb
+            .cfgr
+            .modify(|_, w| unsafe { w.hpre().bits(ahb_psc_bits) });
     }
 
     fn range1_normal_to_boost(
@@ -394,17 +641,13 @@ impl Rcc {
         // The sequence to switch from Range11 normal mode to Range1 boost mode is:
         // 1. The system clock must be divided by 2 using the AHB prescaler before switching to a
         // higher system frequency.
-        let half_apb = (self.rb.cfgr.read().hpre().bits() + 1).clamp(0b1000, 0b1111);
-        self.rb
-            .cfgr
-            .modify(|_r, w| unsafe { w.hpre().bits(half_apb) });
-        while self.rb.cfgr.read().hpre().bits() != half_apb {}
+        self.halve_ahb();
 
         // 2. Clear the R1MODE bit is in the PWR_CR5 register.
         unsafe { pwr::set_boost(true) };
 
         // 3. Adjust the number of wait states according to the new frequency target in range1 boost mode
-        Self::configure_wait_states(pwr_cfg, sys_freq);
+        Self::raise_wait_states(pwr_cfg, sys_freq);
 
         // 4. Configure and switch to new system frequency.
         self.rb.cfgr.modify(|_, w| unsafe {
@@ -420,15 +663,44 @@ impl Rcc {
 
         // 5. Wait for at least 1us and then reconfigure the AHB prescaler to get the needed HCLK
         // clock frequency.
-        let us_per_s = 1_000_000;
-        // Number of cycles @ sys_freq for 1us, rounded up, this will
-        // likely end up being 2us since the AHB prescaler is changed
-        let delay_cycles = (sys_freq + us_per_s - 1) / us_per_s;
-        cortex_m::asm::delay(delay_cycles);
+        self.restore_ahb(sys_freq, ahb_psc_bits);
+    }
 
-        self.rb
-            .cfgr
-            .modify(|_, w| unsafe { w.hpre().bits(ahb_psc_bits) });
+    fn range1_boost_to_normal(
+        &mut self,
+        pwr_cfg: &PowerConfiguration,
+        sys_freq: u32,
+        apb1_psc_bits: u8,
+        apb2_psc_bits: u8,
+        sw_bits: u8,
+        ahb_psc_bits: u8,
+    ) {
+        // (From RM0440 chapter "Power control (PWR)")
+        // The sequence to switch from Range1 boost mode to Range1 normal mode is:
+        // 1. The system clock must be divided by 2 using the AHB prescaler before switching to a
+        // lower system frequency.
+        self.halve_ahb();
+
+        // 2. Configure and switch to the new system frequency, which has to be within the range1
+        // normal mode limits. The wait states are kept high enough for both frequencies.
+        Self::raise_wait_states(pwr_cfg, sys_freq);
+        self.rb.cfgr.modify(|_, w| unsafe {
+            w.ppre1()
+                .bits(apb1_psc_bits)
+                .ppre2()
+                .bits(apb2_psc_bits)
+                .sw()
+                .bits(sw_bits)
+        });
+
+        while self.rb.cfgr.read().sws().bits() != sw_bits {}
+
+        // 3. Wait for at least 1us and then reconfigure the AHB prescaler to get the needed HCLK
+        // clock frequency.
+        self.restore_ahb(sys_freq, ahb_psc_bits);
+
+        // 4. Set the R1MODE bit in the PWR_CR5 register.
+        unsafe { pwr::set_boost(false) };
     }
 
     pub(crate) fn enable_hsi(&self) {
@@ -440,10 +712,10 @@ impl Rcc {
         self.rb.crrcr.write(|w| w.hsi48on().set_bit());
         while self.rb.crrcr.read().hsi48rdy().bit_is_clear() {}
 
-        self.rb.ccipr.write(|w| w.clk48sel().hsi48());
-        self.rb.apb1smenr1.write(|w| w.usbsmen().clear_bit());
-        self.rb.apb1enr1.write(|w| w.crsen().set_bit());
-        self.rb.apb1enr1.write(|w| w.usben().set_bit());
+        self.rb.ccipr.modify(|_, w| w.clk48sel().hsi48());
+        self.rb.apb1smenr1.modify(|_, w| w.usbsmen().clear_bit());
+        self.rb.apb1enr1.modify(|_, w| w.crsen().set_bit());
+        self.rb.apb1enr1.modify(|_, w| w.usben().set_bit());
     }
 
     pub(crate) fn enable_hse(&self, bypass: bool) {
@@ -535,6 +807,7 @@ impl RccExt for RCC {
         Rcc {
             rb: self,
             clocks: Clocks::default(),
+            config: None,
         }
     }
 
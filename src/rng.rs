@@ -0,0 +1,130 @@
+//! True random number generator
+//!
+//! The RNG is clocked by the 48MHz clock, which has to be selected with
+//! [`Config::ck48_src`](crate::rcc::Config::ck48_src) before the RNG is constrained.
+//!
+//! Besides [`Rng::gen`] and [`Rng::fill`], the `rand_core` traits [`RngCore`] and [`CryptoRng`]
+//! are implemented so the RNG can be used with the wider `rand` ecosystem.
+use core::num::NonZeroU32;
+
+use crate::rcc::{Enable, Rcc, Reset};
+use crate::stm32::RNG;
+
+pub use rand_core::{CryptoRng, RngCore};
+
+/// RNG error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The RNG clock is too slow compared to the AHB clock, or stopped
+    ClockError = 0,
+    /// A faulty sequence was detected in the noise source. The RNG has been recovered, but the
+    /// values read since the previous call may not be random.
+    SeedError = 1,
+}
+
+impl From<ErrorKind> for rand_core::Error {
+    fn from(err: ErrorKind) -> Self {
+        let code = NonZeroU32::new(rand_core::Error::CUSTOM_START + err as u32).unwrap();
+        rand_core::Error::from(code)
+    }
+}
+
+/// Extension trait that constrains the `RNG` peripheral
+pub trait RngExt {
+    fn constrain(self, rcc: &mut Rcc) -> Rng;
+}
+
+impl RngExt for RNG {
+    fn constrain(self, rcc: &mut Rcc) -> Rng {
+        assert!(
+            rcc.clocks.ck48_clk.raw() != 0,
+            "the RNG needs the 48MHz clock, select it with Config::ck48_src"
+        );
+
+        // Enable and reset RNG
+        RNG::enable(&rcc.rb);
+        RNG::reset(&rcc.rb);
+        self.cr.modify(|_, w| w.rngen().set_bit());
+
+        Rng { rb: self }
+    }
+}
+
+/// Constrained RNG peripheral
+pub struct Rng {
+    rb: RNG,
+}
+
+impl Rng {
+    /// Returns a random 32 bit value, blocking until one is available.
+    pub fn gen(&mut self) -> Result<u32, ErrorKind> {
+        loop {
+            let sr = self.rb.sr.read();
+            if sr.seis().bit_is_set() {
+                self.recover_seed_error();
+                return Err(ErrorKind::SeedError);
+            }
+            if sr.ceis().bit_is_set() {
+                // The RNG recovers by itself once the clock is back within range
+                self.rb.sr.modify(|_, w| w.ceis().clear_bit());
+                return Err(ErrorKind::ClockError);
+            }
+            if sr.drdy().bit_is_set() {
+                return Ok(self.rb.dr.read().bits());
+            }
+        }
+    }
+
+    /// Fills `buffer` with random bytes.
+    pub fn fill(&mut self, buffer: &mut [u8]) -> Result<(), ErrorKind> {
+        for chunk in buffer.chunks_mut(4) {
+            let bytes = self.gen()?.to_ne_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(())
+    }
+
+    /// Releases the RNG peripheral, turning it off.
+    pub fn release(self) -> RNG {
+        self.rb.cr.modify(|_, w| w.rngen().clear_bit());
+        self.rb
+    }
+
+    fn recover_seed_error(&mut self) {
+        // See 'Error management' in RM0440
+        self.rb.sr.modify(|_, w| w.seis().clear_bit());
+        // Clear the pipeline of values generated before the error
+        for _ in 0..12 {
+            let _ = self.rb.dr.read();
+        }
+        if self.rb.sr.read().seis().bit_is_set() {
+            // The error is still present, restart the RNG
+            self.rb.cr.modify(|_, w| w.rngen().clear_bit());
+            self.rb.cr.modify(|_, w| w.rngen().set_bit());
+        }
+    }
+}
+
+/// The infallible methods panic when the RNG reports an error, use
+/// [`try_fill_bytes`](RngCore::try_fill_bytes) to handle errors.
+impl RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        self.gen().expect("RNG error")
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        (high << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.fill(dest).expect("RNG error")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill(dest).map_err(rand_core::Error::from)
+    }
+}
+
+impl CryptoRng for Rng {}
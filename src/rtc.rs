@@ -0,0 +1,617 @@
+//! Real Time Clock
+//!
+//! The RTC keeps the calendar (time and date, stored in BCD) running from the LSE or LSI clock,
+//! also while the core is in a low-power mode. On top of the calendar it offers two programmable
+//! alarms (A and B), a periodic wakeup timer, a sub-second counter and smooth digital calibration.
+//!
+//! The calendar is always kept in 24 hour format and only covers the years 2000 to 2099.
+//!
+//! Alarms and the wakeup timer reach the NVIC and wake the device from Stop mode through the EXTI
+//! lines [`exti::Event::RTC_ALARM`] and [`exti::Event::RTC_WAKEUP`], which are configured by
+//! [`Rtc::listen`].
+use crate::exti::{self, ExtiExt};
+use crate::gpio::SignalEdge;
+use crate::rcc::{RTCSrc, Rcc};
+use crate::stm32::{EXTI, RTC};
+use crate::time::{Date, MicroSecond, MilliSecond, MonthDay, Time, WeekDay};
+
+/// LSE oscillator frequency
+pub const LSE_FREQ: u32 = 32_768;
+/// LSI oscillator frequency
+pub const LSI_FREQ: u32 = 32_000;
+
+/// RTC interrupt event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Alarm A matched the calendar
+    AlarmA,
+    /// Alarm B matched the calendar
+    AlarmB,
+    /// Periodic wakeup timer elapsed
+    Wakeup,
+    /// Timestamp event occurred
+    Timestamp,
+}
+
+/// Alarm selection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlarmSel {
+    A,
+    B,
+}
+
+/// Day field of an alarm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlarmDay {
+    /// Match on the day of the month (1-31)
+    Date(MonthDay),
+    /// Match on the day of the week (1 = Monday ... 7 = Sunday)
+    WeekDay(WeekDay),
+}
+
+/// Alarm configuration.
+///
+/// Every field that is left at `None` is masked, meaning it is not taken into account when
+/// comparing the alarm to the calendar. An alarm with all fields masked fires every second.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Alarm {
+    day: Option<AlarmDay>,
+    hours: Option<u32>,
+    minutes: Option<u32>,
+    seconds: Option<u32>,
+    subseconds: u16,
+    subseconds_mask_bits: u8,
+}
+
+impl Alarm {
+    /// Creates an alarm with all fields masked.
+    pub fn new() -> Self {
+        Alarm {
+            day: None,
+            hours: None,
+            minutes: None,
+            seconds: None,
+            subseconds: 0,
+            subseconds_mask_bits: 0,
+        }
+    }
+
+    /// Matches the alarm on the given day of the month or day of the week.
+    pub fn set_day(mut self, day: AlarmDay) -> Self {
+        self.day = Some(day);
+        self
+    }
+
+    /// Matches the alarm on the given hour (0-23).
+    pub fn set_hours(mut self, hours: u32) -> Self {
+        assert!(hours < 24);
+        self.hours = Some(hours);
+        self
+    }
+
+    /// Matches the alarm on the given minute (0-59).
+    pub fn set_minutes(mut self, minutes: u32) -> Self {
+        assert!(minutes < 60);
+        self.minutes = Some(minutes);
+        self
+    }
+
+    /// Matches the alarm on the given second (0-59).
+    pub fn set_seconds(mut self, seconds: u32) -> Self {
+        assert!(seconds < 60);
+        self.seconds = Some(seconds);
+        self
+    }
+
+    /// Matches the `mask_bits` least significant bits of the sub-second counter against
+    /// `subseconds`. With `mask_bits` set to 0 the sub-seconds are not compared.
+    pub fn set_subseconds(mut self, subseconds: u16, mask_bits: u8) -> Self {
+        assert!(mask_bits <= 15);
+        self.subseconds = subseconds;
+        self.subseconds_mask_bits = mask_bits;
+        self
+    }
+}
+
+impl Default for Alarm {
+    fn default() -> Self {
+        Alarm::new()
+    }
+}
+
+impl From<Time> for Alarm {
+    fn from(time: Time) -> Self {
+        Alarm::new()
+            .set_hours(time.hours)
+            .set_minutes(time.minutes)
+            .set_seconds(time.seconds)
+    }
+}
+
+/// Length of the smooth calibration cycle.
+///
+/// Shorter cycles allow faster updates but lower the calibration resolution, see RM0440
+/// 'RTC smooth digital calibration'.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalibrationCycle {
+    /// 32 second cycle, 0.954 ppm resolution
+    Seconds32,
+    /// 16 second cycle, 1.907 ppm resolution
+    Seconds16,
+    /// 8 second cycle, 3.815 ppm resolution
+    Seconds8,
+}
+
+/// Real Time Clock peripheral
+pub struct Rtc {
+    rb: RTC,
+    clk_freq: u32,
+}
+
+/// Extension trait that sets up the `RTC` peripheral
+pub trait RtcExt {
+    fn constrain(self, src: RTCSrc, rcc: &mut Rcc) -> Rtc;
+}
+
+impl RtcExt for RTC {
+    fn constrain(self, src: RTCSrc, rcc: &mut Rcc) -> Rtc {
+        Rtc::new(self, src, rcc)
+    }
+}
+
+impl Rtc {
+    /// Enables the RTC clock from `src` and configures the prescalers for a 1 Hz calendar clock.
+    ///
+    /// The calendar is not reset, so a RTC that kept running from the backup domain keeps its
+    /// time.
+    pub fn new(rtc: RTC, src: RTCSrc, rcc: &mut Rcc) -> Self {
+        rcc.enable_rtc(src);
+
+        let clk_freq = match src {
+            RTCSrc::LSE | RTCSrc::LSE_BYPASS => LSE_FREQ,
+            RTCSrc::LSI => LSI_FREQ,
+        };
+
+        let mut rtc = Rtc { rb: rtc, clk_freq };
+
+        // Maximize the synchronous prescaler for the best sub-second resolution while keeping the
+        // asynchronous prescaler high to save power.
+        let prediv_a = 127;
+        let prediv_s = (clk_freq / (prediv_a + 1)) - 1;
+        rtc.modify(true, |rb| {
+            rb.cr.modify(|_, w| w.fmt().clear_bit());
+            rb.prer.write(|w| unsafe {
+                w.prediv_a()
+                    .bits(prediv_a as u8)
+                    .prediv_s()
+                    .bits(prediv_s as u16)
+            });
+        });
+
+        rtc
+    }
+
+    /// Sets the calendar time. The daylight saving flag is stored in the backup (BKP) bit.
+    pub fn set_time(&mut self, time: &Time) {
+        assert!(time.hours < 24 && time.minutes < 60 && time.seconds < 60);
+        let (ht, hu) = bcd2_encode(time.hours);
+        let (mnt, mnu) = bcd2_encode(time.minutes);
+        let (st, su) = bcd2_encode(time.seconds);
+        self.modify(true, |rb| {
+            rb.tr.write(|w| unsafe {
+                w.pm()
+                    .clear_bit()
+                    .ht()
+                    .bits(ht)
+                    .hu()
+                    .bits(hu)
+                    .mnt()
+                    .bits(mnt)
+                    .mnu()
+                    .bits(mnu)
+                    .st()
+                    .bits(st)
+                    .su()
+                    .bits(su)
+            });
+            rb.cr.modify(|_, w| w.bkp().bit(time.daylight_savings));
+        });
+    }
+
+    /// Sets the calendar date. The day of the week is derived from the date.
+    pub fn set_date(&mut self, date: &Date) {
+        assert!((2000..=2099).contains(&date.year));
+        assert!((1..=12).contains(&date.month));
+        assert!((1..=31).contains(&date.day));
+        let (yt, yu) = bcd2_encode(date.year - 2000);
+        let (mt, mu) = bcd2_encode(date.month);
+        let (dt, du) = bcd2_encode(date.day);
+        let wdu = week_day(date).0 as u8;
+        self.modify(true, |rb| {
+            rb.dr.write(|w| unsafe {
+                w.yt()
+                    .bits(yt)
+                    .yu()
+                    .bits(yu)
+                    .mt()
+                    .bit(mt > 0)
+                    .mu()
+                    .bits(mu)
+                    .dt()
+                    .bits(dt)
+                    .du()
+                    .bits(du)
+                    .wdu()
+                    .bits(wdu)
+            });
+        });
+    }
+
+    /// Sets both the calendar date and time.
+    pub fn set_date_time(&mut self, date: &Date, time: &Time) {
+        self.set_date(date);
+        self.set_time(time);
+    }
+
+    /// Reads the calendar time.
+    pub fn get_time(&self) -> Time {
+        self.wait_for_sync();
+        let tr = self.rb.tr.read();
+        // Reading TR freezes the shadow registers until DR is read
+        let _ = self.rb.dr.read();
+        Time {
+            hours: bcd2_decode(tr.ht().bits(), tr.hu().bits()),
+            minutes: bcd2_decode(tr.mnt().bits(), tr.mnu().bits()),
+            seconds: bcd2_decode(tr.st().bits(), tr.su().bits()),
+            daylight_savings: self.rb.cr.read().bkp().bit_is_set(),
+        }
+    }
+
+    /// Reads the calendar date.
+    pub fn get_date(&self) -> Date {
+        self.wait_for_sync();
+        let dr = self.rb.dr.read();
+        Date {
+            day: bcd2_decode(dr.dt().bits(), dr.du().bits()),
+            month: bcd2_decode(dr.mt().bit() as u8, dr.mu().bits()),
+            year: 2000 + bcd2_decode(dr.yt().bits(), dr.yu().bits()),
+        }
+    }
+
+    /// Reads the calendar date and time from a single consistent snapshot.
+    pub fn get_date_time(&self) -> (Date, Time) {
+        self.wait_for_sync();
+        let tr = self.rb.tr.read();
+        let dr = self.rb.dr.read();
+        (
+            Date {
+                day: bcd2_decode(dr.dt().bits(), dr.du().bits()),
+                month: bcd2_decode(dr.mt().bit() as u8, dr.mu().bits()),
+                year: 2000 + bcd2_decode(dr.yt().bits(), dr.yu().bits()),
+            },
+            Time {
+                hours: bcd2_decode(tr.ht().bits(), tr.hu().bits()),
+                minutes: bcd2_decode(tr.mnt().bits(), tr.mnu().bits()),
+                seconds: bcd2_decode(tr.st().bits(), tr.su().bits()),
+                daylight_savings: self.rb.cr.read().bkp().bit_is_set(),
+            },
+        )
+    }
+
+    /// Reads the day of the week (1 = Monday ... 7 = Sunday).
+    pub fn get_week_day(&self) -> WeekDay {
+        self.wait_for_sync();
+        WeekDay(self.rb.dr.read().wdu().bits() as u32)
+    }
+
+    /// Returns the raw sub-second down counter (SS). It counts down from the synchronous
+    /// prescaler value to 0 every second.
+    pub fn get_subsecond_ticks(&self) -> u32 {
+        let ss = self.rb.ssr.read().ss().bits() as u32;
+        // Reading SSR freezes the shadow registers until DR is read
+        let _ = self.rb.dr.read();
+        ss
+    }
+
+    /// Returns the time elapsed within the current second.
+    pub fn get_subseconds(&self) -> MicroSecond {
+        let prediv_s = self.rb.prer.read().prediv_s().bits() as u32;
+        let ss = self.get_subsecond_ticks();
+        let us = ((prediv_s - ss.min(prediv_s)) as u64 * 1_000_000) / (prediv_s as u64 + 1);
+        MicroSecond::from_ticks(us as u32)
+    }
+
+    /// Programs and enables an alarm.
+    pub fn set_alarm(&mut self, sel: AlarmSel, alarm: impl Into<Alarm>) {
+        let alarm = alarm.into();
+
+        let (dt, du, wdsel) = match alarm.day {
+            Some(AlarmDay::Date(day)) => {
+                let (dt, du) = bcd2_encode(day.0);
+                (dt, du, false)
+            }
+            Some(AlarmDay::WeekDay(day)) => (0, day.0 as u8, true),
+            None => (0, 0, false),
+        };
+        let (ht, hu) = bcd2_encode(alarm.hours.unwrap_or(0));
+        let (mnt, mnu) = bcd2_encode(alarm.minutes.unwrap_or(0));
+        let (st, su) = bcd2_encode(alarm.seconds.unwrap_or(0));
+
+        self.modify(false, |rb| {
+            let (alrmr, alrmssr) = match sel {
+                AlarmSel::A => {
+                    rb.cr.modify(|_, w| w.alrae().clear_bit());
+                    while rb.icsr.read().alrawf().bit_is_clear() {}
+                    (&rb.alrmar, &rb.alrmassr)
+                }
+                AlarmSel::B => {
+                    rb.cr.modify(|_, w| w.alrbe().clear_bit());
+                    while rb.icsr.read().alrbwf().bit_is_clear() {}
+                    (&rb.alrmbr, &rb.alrmbssr)
+                }
+            };
+
+            alrmr.write(|w| unsafe {
+                w.msk4()
+                    .bit(alarm.day.is_none())
+                    .wdsel()
+                    .bit(wdsel)
+                    .dt()
+                    .bits(dt)
+                    .du()
+                    .bits(du)
+                    .msk3()
+                    .bit(alarm.hours.is_none())
+                    .pm()
+                    .clear_bit()
+                    .ht()
+                    .bits(ht)
+                    .hu()
+                    .bits(hu)
+                    .msk2()
+                    .bit(alarm.minutes.is_none())
+                    .mnt()
+                    .bits(mnt)
+                    .mnu()
+                    .bits(mnu)
+                    .msk1()
+                    .bit(alarm.seconds.is_none())
+                    .st()
+                    .bits(st)
+                    .su()
+                    .bits(su)
+            });
+            alrmssr.write(|w| unsafe {
+                w.maskss()
+                    .bits(alarm.subseconds_mask_bits)
+                    .ss()
+                    .bits(alarm.subseconds)
+            });
+
+            match sel {
+                AlarmSel::A => rb.cr.modify(|_, w| w.alrae().set_bit()),
+                AlarmSel::B => rb.cr.modify(|_, w| w.alrbe().set_bit()),
+            }
+        });
+    }
+
+    /// Disables an alarm.
+    pub fn disable_alarm(&mut self, sel: AlarmSel) {
+        self.modify(false, |rb| match sel {
+            AlarmSel::A => rb.cr.modify(|_, w| w.alrae().clear_bit()),
+            AlarmSel::B => rb.cr.modify(|_, w| w.alrbe().clear_bit()),
+        });
+    }
+
+    /// Starts the periodic wakeup timer.
+    ///
+    /// Periods up to 2^20 RTC clock cycles (32 seconds with a 32.768kHz LSE) are timed from the
+    /// RTC clock divided by 2 to 16 with the finest possible resolution, longer periods (up to
+    /// 131072 seconds) are timed in whole seconds from the calendar clock.
+    pub fn set_wakeup_timer(&mut self, period: MilliSecond) {
+        let ms = period.ticks() as u64;
+        assert!(ms > 0);
+
+        // RTCCLK / 2, 4, 8 and 16 map to WUCKSEL 0b011 down to 0b000
+        let (wucksel, wut) = match [(2, 0b011), (4, 0b010), (8, 0b001), (16, 0b000)]
+            .iter()
+            .map(|&(div, sel)| (sel, (ms * (self.clk_freq / div) as u64) / 1000))
+            .find(|&(_, count)| (1..=0x1_0000).contains(&count))
+        {
+            Some((sel, count)) => (sel, count - 1),
+            None => {
+                let secs = ms / 1000;
+                assert!((1..=0x2_0000).contains(&secs));
+                if secs <= 0x1_0000 {
+                    (0b100, secs - 1)
+                } else {
+                    // The 0b11x setting adds 2^16 to the counter value
+                    (0b110, secs - 1 - 0x1_0000)
+                }
+            }
+        };
+
+        self.modify(false, |rb| {
+            rb.cr.modify(|_, w| w.wute().clear_bit());
+            while rb.icsr.read().wutwf().bit_is_clear() {}
+            rb.wutr.write(|w| unsafe { w.wut().bits(wut as u16) });
+            rb.cr
+                .modify(|_, w| unsafe { w.wucksel().bits(wucksel).wute().set_bit() });
+        });
+    }
+
+    /// Stops the periodic wakeup timer.
+    pub fn disable_wakeup_timer(&mut self) {
+        self.modify(false, |rb| {
+            rb.cr.modify(|_, w| w.wute().clear_bit());
+        });
+    }
+
+    /// Applies smooth digital calibration.
+    ///
+    /// Every calibration cycle `mask_pulses` (0-511) RTC clock pulses are masked, and when
+    /// `insert_pulses` is set 512 extra pulses are inserted. This allows a correction range of
+    /// -487.1 ppm to +488.5 ppm.
+    pub fn set_smooth_calibration(
+        &mut self,
+        cycle: CalibrationCycle,
+        insert_pulses: bool,
+        mask_pulses: u16,
+    ) {
+        assert!(mask_pulses < 512);
+        let (calw8, calw16) = match cycle {
+            CalibrationCycle::Seconds32 => (false, false),
+            CalibrationCycle::Seconds16 => (false, true),
+            CalibrationCycle::Seconds8 => (true, false),
+        };
+        self.modify(false, |rb| {
+            while rb.icsr.read().recalpf().bit_is_set() {}
+            rb.calr.write(|w| unsafe {
+                w.calp()
+                    .bit(insert_pulses)
+                    .calw8()
+                    .bit(calw8)
+                    .calw16()
+                    .bit(calw16)
+                    .calm()
+                    .bits(mask_pulses)
+            });
+        });
+    }
+
+    /// Starts listening for an RTC event, both in the RTC and on the matching EXTI line.
+    ///
+    /// The EXTI line is configured for a rising edge, which also makes the event able to wake the
+    /// device from Stop mode.
+    pub fn listen(&mut self, ev: Event, exti: &EXTI) {
+        self.modify(false, |rb| {
+            rb.cr.modify(|_, w| match ev {
+                Event::AlarmA => w.alraie().set_bit(),
+                Event::AlarmB => w.alrbie().set_bit(),
+                Event::Wakeup => w.wutie().set_bit(),
+                Event::Timestamp => w.tsie().set_bit(),
+            })
+        });
+        match ev {
+            Event::AlarmA | Event::AlarmB => {
+                exti.listen(exti::Event::RTC_ALARM, SignalEdge::Rising)
+            }
+            Event::Wakeup => exti.listen(exti::Event::RTC_WAKEUP, SignalEdge::Rising),
+            // Timestamp events share EXTI line 19 with tamper and LSE CSS, which is not modeled
+            // by `exti::Event`
+            Event::Timestamp => {}
+        }
+    }
+
+    /// Stops listening for an RTC event.
+    ///
+    /// The RTC alarm EXTI line is only released once neither alarm is being listened to.
+    pub fn unlisten(&mut self, ev: Event, exti: &EXTI) {
+        self.modify(false, |rb| {
+            rb.cr.modify(|_, w| match ev {
+                Event::AlarmA => w.alraie().clear_bit(),
+                Event::AlarmB => w.alrbie().clear_bit(),
+                Event::Wakeup => w.wutie().clear_bit(),
+                Event::Timestamp => w.tsie().clear_bit(),
+            })
+        });
+        let cr = self.rb.cr.read();
+        match ev {
+            Event::AlarmA | Event::AlarmB => {
+                if cr.alraie().bit_is_clear() && cr.alrbie().bit_is_clear() {
+                    exti.unlisten(exti::Event::RTC_ALARM);
+                }
+            }
+            Event::Wakeup => exti.unlisten(exti::Event::RTC_WAKEUP),
+            Event::Timestamp => {}
+        }
+    }
+
+    /// Returns true if the RTC flag of the event is set.
+    pub fn is_pending(&self, ev: Event) -> bool {
+        let sr = self.rb.sr.read();
+        match ev {
+            Event::AlarmA => sr.alraf().bit_is_set(),
+            Event::AlarmB => sr.alrbf().bit_is_set(),
+            Event::Wakeup => sr.wutf().bit_is_set(),
+            Event::Timestamp => sr.tsf().bit_is_set(),
+        }
+    }
+
+    /// Clears the RTC flag of the event.
+    ///
+    /// The pending bit of the EXTI line has to be cleared separately with
+    /// [`ExtiExt::unpend`].
+    pub fn unpend(&mut self, ev: Event) {
+        self.rb.scr.write(|w| match ev {
+            Event::AlarmA => w.calraf().set_bit(),
+            Event::AlarmB => w.calrbf().set_bit(),
+            Event::Wakeup => w.cwutf().set_bit(),
+            Event::Timestamp => w.ctsf().set_bit(),
+        });
+    }
+
+    /// Releases the RTC peripheral. The RTC keeps running from the backup domain.
+    pub fn release(self) -> RTC {
+        self.rb
+    }
+
+    /// Waits until the calendar shadow registers are synchronized after initialization or a
+    /// wakeup from a low-power mode.
+    fn wait_for_sync(&self) {
+        if self.rb.cr.read().bypshad().bit_is_clear() {
+            while self.rb.icsr.read().rsf().bit_is_clear() {}
+        }
+    }
+
+    /// Disables the RTC write protection and optionally enters initialization mode while
+    /// running `closure`.
+    fn modify<F>(&mut self, init_mode: bool, mut closure: F)
+    where
+        F: FnMut(&mut RTC),
+    {
+        // Disable write protection
+        self.rb.wpr.write(|w| unsafe { w.key().bits(0xCA) });
+        self.rb.wpr.write(|w| unsafe { w.key().bits(0x53) });
+
+        if init_mode && self.rb.icsr.read().initf().bit_is_clear() {
+            self.rb.icsr.modify(|_, w| w.init().set_bit());
+            // Wait for the calendar registers to become writable
+            while self.rb.icsr.read().initf().bit_is_clear() {}
+        }
+
+        closure(&mut self.rb);
+
+        if init_mode {
+            self.rb.icsr.modify(|_, w| w.init().clear_bit().rsf().clear_bit());
+        }
+
+        // Enable write protection
+        self.rb.wpr.write(|w| unsafe { w.key().bits(0xFF) });
+    }
+}
+
+/// Splits a value 0-99 into its BCD tens and units digits.
+fn bcd2_encode(word: u32) -> (u8, u8) {
+    assert!(word < 100);
+    ((word / 10) as u8, (word % 10) as u8)
+}
+
+fn bcd2_decode(tens: u8, units: u8) -> u32 {
+    (tens as u32) * 10 + units as u32
+}
+
+/// Day of the week of a date (1 = Monday ... 7 = Sunday), using Sakamoto's method.
+fn week_day(date: &Date) -> WeekDay {
+    const OFFSETS: [u32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let year = if date.month < 3 {
+        date.year - 1
+    } else {
+        date.year
+    };
+    // 0 = Sunday
+    let day =
+        (year + year / 4 - year / 100 + year / 400 + OFFSETS[date.month as usize - 1] + date.day)
+            % 7;
+    WeekDay(if day == 0 { 7 } else { day })
+}
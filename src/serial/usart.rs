@@ -27,6 +27,15 @@ pub enum Error {
     Parity,
 }
 
+impl embedded_io::Error for Error {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Error::Framing | Error::Noise | Error::Parity => embedded_io::ErrorKind::InvalidData,
+            Error::Overrun => embedded_io::ErrorKind::Other,
+        }
+    }
+}
+
 /// Interrupt event
 pub enum Event {
     /// TXFIFO reaches the threshold
@@ -350,6 +359,114 @@ macro_rules! uart_shared {
         }
 
 
+        impl<Pin> embedded_io::ErrorType for Rx<$USARTX, Pin, NoDMA> {
+            type Error = Error;
+        }
+
+        impl<Pin> embedded_io::Read for Rx<$USARTX, Pin, NoDMA> {
+            /// Blocks until at least one byte has been received, then drains whatever else is
+            /// already waiting in the receiver without blocking again.
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                if buf.is_empty() {
+                    return Ok(0);
+                }
+
+                buf[0] = block!(hal_api_old::serial::Read::read(self))?;
+
+                let usart = unsafe { &(*$USARTX::ptr()) };
+                let mut count = 1;
+                while count < buf.len() {
+                    let isr = usart.isr.read();
+                    // Leave pending errors in place so they are reported by the next call instead
+                    // of discarding the bytes that were already received.
+                    if isr.rxne().bit_is_clear()
+                        || isr.pe().bit_is_set()
+                        || isr.fe().bit_is_set()
+                        || isr.nf().bit_is_set()
+                        || isr.ore().bit_is_set()
+                    {
+                        break;
+                    }
+                    buf[count] = usart.rdr.read().bits() as u8;
+                    count += 1;
+                }
+
+                Ok(count)
+            }
+        }
+
+        impl<Pin> embedded_io::ReadReady for Rx<$USARTX, Pin, NoDMA> {
+            fn read_ready(&mut self) -> Result<bool, Self::Error> {
+                Ok(self.is_rxne())
+            }
+        }
+
+        impl<Pin> embedded_io::ErrorType for Tx<$USARTX, Pin, NoDMA> {
+            type Error = Error;
+        }
+
+        impl<Pin> embedded_io::Write for Tx<$USARTX, Pin, NoDMA> {
+            /// Blocks until at least one byte has been accepted, then keeps writing for as long as
+            /// the transmitter can take more data without blocking.
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                if buf.is_empty() {
+                    return Ok(0);
+                }
+
+                block!(hal_api_old::serial::Write::write(self, buf[0]))?;
+
+                let mut count = 1;
+                while count < buf.len() && self.is_txe() {
+                    block!(hal_api_old::serial::Write::write(self, buf[count]))?;
+                    count += 1;
+                }
+
+                Ok(count)
+            }
+
+            fn flush(&mut self) -> Result<(), Self::Error> {
+                block!(hal_api_old::serial::Write::flush(self))
+            }
+        }
+
+        impl<Pin> embedded_io::WriteReady for Tx<$USARTX, Pin, NoDMA> {
+            fn write_ready(&mut self) -> Result<bool, Self::Error> {
+                Ok(self.is_txe())
+            }
+        }
+
+        impl<TX, RX> embedded_io::ErrorType for Serial<$USARTX, TX, RX> {
+            type Error = Error;
+        }
+
+        impl<TX, RX> embedded_io::Read for Serial<$USARTX, TX, RX> {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                embedded_io::Read::read(&mut self.rx, buf)
+            }
+        }
+
+        impl<TX, RX> embedded_io::ReadReady for Serial<$USARTX, TX, RX> {
+            fn read_ready(&mut self) -> Result<bool, Self::Error> {
+                embedded_io::ReadReady::read_ready(&mut self.rx)
+            }
+        }
+
+        impl<TX, RX> embedded_io::Write for Serial<$USARTX, TX, RX> {
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                embedded_io::Write::write(&mut self.tx, buf)
+            }
+
+            fn flush(&mut self) -> Result<(), Self::Error> {
+                embedded_io::Write::flush(&mut self.tx)
+            }
+        }
+
+        impl<TX, RX> embedded_io::WriteReady for Serial<$USARTX, TX, RX> {
+            fn write_ready(&mut self) -> Result<bool, Self::Error> {
+                embedded_io::WriteReady::write_ready(&mut self.tx)
+            }
+        }
+
         impl<TX, RX> Serial<$USARTX, TX, RX> {
 
             /// Separates the serial struct into separate channel objects for sending (Tx) and
@@ -0,0 +1,473 @@
+//! SMBus / PMBus host
+//!
+//! An [`SMBus`] wraps an I2C controller and implements the SMBus protocols on top of it, with
+//! optional hardware packet error checking (PEC). SMBus requires the SCL low timeout of 25ms to
+//! be enabled with [`i2c::Config::scl_timeout`](crate::i2c::Config::scl_timeout).
+//!
+//! ```rust,ignore
+//!   let i2c = dp.I2C1.i2c(sda, scl, i2c::Config::new(100.kHz()).scl_timeout(25_000.micros()), &mut rcc);
+//!   let mut smbus = i2c.smbus(smbus::Config::new().pec(true), &rcc).with_alert(smba);
+//!   smbus.listen_alert();
+//!
+//!   let vout = smbus.read_word(0x40, 0x8b)?; // PMBus READ_VOUT
+//! ```
+//!
+//! Devices signal the host through the SMBALERT# pin, after which [`SMBus::alert_response`]
+//! returns the address of the device that needs attention.
+
+use core::cmp;
+use core::ops::Deref;
+
+use hal_api::i2c::NoAcknowledgeSource;
+
+use crate::gpio::{gpioa::*, gpiob::*, gpiof::*};
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+use crate::gpio::{gpiod::*, gpiog::*};
+use crate::gpio::{AlternateOD, AF4};
+use crate::i2c::{end_after_nack, software_reset, I2cObj};
+use crate::rcc::{GetBusFreq, Rcc, RccBus};
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+use crate::stm32::I2C4;
+use crate::stm32::i2c1::{isr, RegisterBlock};
+use crate::stm32::{I2C1, I2C2, I2C3};
+use crate::time::{cycles_us, MicroSecond};
+
+/// SMBus alert response address
+const ALERT_RESPONSE_ADDRESS: u8 = 0x0c;
+
+/// SMBus error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Misplaced START or STOP condition
+    Bus,
+    /// Another controller took over the bus
+    ArbitrationLoss,
+    /// The address or a data byte was not acknowledged
+    NoAcknowledge(NoAcknowledgeSource),
+    /// The received PEC byte does not match the calculated one
+    Pec,
+    /// SCL was held low for too long, or the clock was extended for too long in total
+    Timeout,
+    /// The byte count of a block read is zero or does not fit in the buffer
+    BlockLength(u8),
+}
+
+/// SMBus configuration
+#[derive(Default)]
+pub struct Config {
+    pec: bool,
+    clock_extension_timeout: Option<MicroSecond>,
+}
+
+impl Config {
+    /// Creates a configuration without PEC
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a PEC byte to every transaction except quick commands, and checks the PEC byte
+    /// of every read.
+    pub fn pec(mut self, enable: bool) -> Self {
+        self.pec = enable;
+        self
+    }
+
+    /// Enables the cumulative clock extension timeout (tLOW:MEXT), the transaction fails with
+    /// [`Error::Timeout`] when the host stretched the clock for longer than `timeout` in total.
+    pub fn clock_extension_timeout(mut self, timeout: MicroSecond) -> Self {
+        self.clock_extension_timeout = Some(timeout);
+        self
+    }
+}
+
+/// SMBALERT# pin
+pub trait SMBAPin<I2C> {}
+
+/// SMBus host on top of an I2C controller
+pub struct SMBus<I2C, SDA, SCL, SMBA> {
+    i2c: I2cObj<I2C, SDA, SCL>,
+    smba: SMBA,
+    pec: bool,
+}
+
+pub trait SMBusExt<I2C, SDA, SCL> {
+    /// Switches the I2C controller to SMBus host mode
+    fn smbus(self, config: Config, rcc: &Rcc) -> SMBus<I2C, SDA, SCL, ()>;
+}
+
+impl<I2C, SDA, SCL> SMBusExt<I2C, SDA, SCL> for I2cObj<I2C, SDA, SCL>
+where
+    I2C: Deref<Target = RegisterBlock> + RccBus,
+    <I2C as RccBus>::Bus: GetBusFreq,
+{
+    fn smbus(self, config: Config, rcc: &Rcc) -> SMBus<I2C, SDA, SCL, ()> {
+        let i2c = &self.i2c;
+
+        // PECEN can only be changed while the peripheral is disabled
+        i2c.cr1.modify(|_, w| w.pe().clear_bit());
+
+        if let Some(timeout) = config.clock_extension_timeout {
+            // tLOW:MEXT = (TIMEOUTB + 1) * 2048 * tI2CCLK
+            let cycles = cycles_us(timeout, <I2C as RccBus>::Bus::get_frequency(&rcc.clocks));
+            let bits = cmp::min(cycles.div_ceil(2048).saturating_sub(1), 0xfff) as u16;
+            i2c.timeoutr.modify(|_, w| w.timeoutb().bits(bits).texten().set_bit());
+        }
+
+        // Host mode, otherwise ALERTEN would drive SMBA and answer the alert response address
+        i2c.cr1.modify(|_, w| w.smbhen().set_bit().pecen().bit(config.pec).pe().set_bit());
+
+        SMBus {
+            i2c: self,
+            smba: (),
+            pec: config.pec,
+        }
+    }
+}
+
+impl<I2C, SDA, SCL> SMBus<I2C, SDA, SCL, ()>
+where
+    I2C: Deref<Target = RegisterBlock>,
+{
+    /// Enables the SMBALERT# input, see [`listen_alert`](SMBus::listen_alert)
+    pub fn with_alert<SMBA: SMBAPin<I2C>>(self, smba: SMBA) -> SMBus<I2C, SDA, SCL, SMBA> {
+        self.i2c.i2c.cr1.modify(|_, w| w.smbhen().set_bit().alerten().set_bit());
+
+        SMBus {
+            i2c: self.i2c,
+            smba,
+            pec: self.pec,
+        }
+    }
+}
+
+impl<I2C, SDA, SCL, SMBA> SMBus<I2C, SDA, SCL, SMBA>
+where
+    I2C: Deref<Target = RegisterBlock>,
+{
+    /// Waits until `done` returns true for the interrupt and status register, or an error occurs
+    fn wait(&self, done: fn(&isr::R) -> bool, addressed: bool) -> Result<(), Error> {
+        let i2c = &self.i2c.i2c;
+        loop {
+            let isr = i2c.isr.read();
+
+            // Errors are checked first, since a PEC error comes together with the STOP condition
+            if isr.berr().bit_is_set() {
+                i2c.icr.write(|w| w.berrcf().set_bit());
+                return Err(Error::Bus);
+            } else if isr.arlo().bit_is_set() {
+                i2c.icr.write(|w| w.arlocf().set_bit());
+                return Err(Error::ArbitrationLoss);
+            } else if isr.timeout().bit_is_set() {
+                i2c.icr.write(|w| w.timoutcf().set_bit());
+                software_reset(i2c);
+                return Err(Error::Timeout);
+            } else if isr.pecerr().bit_is_set() {
+                // The PEC byte has been NACKed, after which the STOP condition is sent
+                end_after_nack(i2c);
+                i2c.icr.write(|w| w.peccf().set_bit());
+                return Err(Error::Pec);
+            } else if isr.nackf().bit_is_set() {
+                // The STOP condition is sent by hardware after the NACK
+                end_after_nack(i2c);
+                return Err(Error::NoAcknowledge(if addressed {
+                    NoAcknowledgeSource::Data
+                } else {
+                    NoAcknowledgeSource::Address
+                }));
+            } else if done(&isr) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sets up the next transfer of `nbytes` bytes, PEC byte included
+    fn start(&mut self, address: u8, read: bool, nbytes: u8, reload: bool, last: bool, pec: bool) {
+        self.i2c.i2c.cr2.write(|w| {
+            w
+                // Start transfer
+                .start().set_bit()
+                // Set address to transfer to/from
+                .sadd().bits((address << 1) as u16)
+                // Set transfer direction
+                .rd_wrn().bit(read)
+                // Set number of bytes to transfer
+                .nbytes().bits(nbytes)
+                .reload().bit(reload)
+                // Automatic end mode for the last transfer
+                .autoend().bit(last && !reload)
+                // Let the hardware transfer the PEC byte as the last byte
+                .pecbyte().bit(pec)
+        });
+    }
+
+    /// Writes `header` followed by `data`, ending with a STOP condition and PEC byte if `last`,
+    /// or waiting for a repeated START otherwise
+    fn write_phase(&mut self, address: u8, header: &[u8], data: &[u8], last: bool) -> Result<(), Error> {
+        // Wait for any previous address sequence to end automatically.
+        while self.i2c.i2c.cr2.read().start().bit_is_set() {}
+
+        let pec = self.pec && last;
+        let len = header.len() + data.len();
+        assert!(len + pec as usize <= 255);
+        self.start(address, false, (len + pec as usize) as u8, false, last, pec);
+
+        let mut addressed = false;
+        for byte in header.iter().chain(data.iter()) {
+            // Wait until we are allowed to send data
+            // (START has been ACKed or last byte went through)
+            self.wait(|isr| isr.txis().bit_is_set(), addressed)?;
+            addressed = true;
+
+            // Put byte on the wire
+            self.i2c.i2c.txdr.write(|w| w.txdata().bits(*byte));
+        }
+
+        // With all bytes transferred, a NACK can only refer to the last data byte
+        if last {
+            // The PEC byte is sent by the hardware
+            self.wait(|isr| isr.stopf().bit_is_set(), len > 0)?;
+            self.i2c.i2c.icr.write(|w| w.stopcf().set_bit());
+        } else {
+            self.wait(|isr| isr.tc().bit_is_set(), len > 0)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_byte_raw(&mut self) -> Result<u8, Error> {
+        self.wait(|isr| isr.rxne().bit_is_set(), true)?;
+
+        Ok(self.i2c.i2c.rxdr.read().rxdata().bits())
+    }
+
+    /// Reads into `buffer` after a (repeated) START, ending with the PEC byte and a STOP condition
+    fn read_phase(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        let pec = self.pec;
+        assert!(buffer.len() + pec as usize <= 255);
+        self.start(address, true, (buffer.len() + pec as usize) as u8, false, true, pec);
+
+        for byte in buffer.iter_mut() {
+            *byte = self.read_byte_raw()?;
+        }
+        if pec {
+            // The PEC byte is checked by the hardware
+            self.read_byte_raw()?;
+        }
+
+        self.wait(|isr| isr.stopf().bit_is_set(), true)?;
+        self.i2c.i2c.icr.write(|w| w.stopcf().set_bit());
+
+        Ok(())
+    }
+
+    /// Sends only the address with the read/write bit, e.g. to switch a device on or off
+    pub fn quick_command(&mut self, address: u8, read: bool) -> Result<(), Error> {
+        while self.i2c.i2c.cr2.read().start().bit_is_set() {}
+
+        // Quick commands never carry a PEC byte
+        self.start(address, read, 0, false, true, false);
+        self.wait(|isr| isr.stopf().bit_is_set(), false)?;
+        self.i2c.i2c.icr.write(|w| w.stopcf().set_bit());
+
+        Ok(())
+    }
+
+    /// Sends a single byte without command code
+    pub fn send_byte(&mut self, address: u8, byte: u8) -> Result<(), Error> {
+        self.write_phase(address, &[byte], &[], true)
+    }
+
+    /// Receives a single byte without command code
+    pub fn receive_byte(&mut self, address: u8) -> Result<u8, Error> {
+        while self.i2c.i2c.cr2.read().start().bit_is_set() {}
+
+        let mut byte = [0];
+        self.read_phase(address, &mut byte)?;
+
+        Ok(byte[0])
+    }
+
+    /// Writes a byte to the register selected by `command`
+    pub fn write_byte(&mut self, address: u8, command: u8, byte: u8) -> Result<(), Error> {
+        self.write_phase(address, &[command, byte], &[], true)
+    }
+
+    /// Writes a little endian word to the register selected by `command`
+    pub fn write_word(&mut self, address: u8, command: u8, word: u16) -> Result<(), Error> {
+        let [low, high] = word.to_le_bytes();
+        self.write_phase(address, &[command, low, high], &[], true)
+    }
+
+    /// Reads a byte from the register selected by `command`
+    pub fn read_byte(&mut self, address: u8, command: u8) -> Result<u8, Error> {
+        let mut byte = [0];
+        self.write_phase(address, &[command], &[], false)?;
+        self.read_phase(address, &mut byte)?;
+
+        Ok(byte[0])
+    }
+
+    /// Reads a little endian word from the register selected by `command`
+    pub fn read_word(&mut self, address: u8, command: u8) -> Result<u16, Error> {
+        let mut word = [0; 2];
+        self.write_phase(address, &[command], &[], false)?;
+        self.read_phase(address, &mut word)?;
+
+        Ok(u16::from_le_bytes(word))
+    }
+
+    /// Writes a word to the register selected by `command` and reads back the word it returns
+    pub fn process_call(&mut self, address: u8, command: u8, word: u16) -> Result<u16, Error> {
+        let [low, high] = word.to_le_bytes();
+        let mut result = [0; 2];
+        self.write_phase(address, &[command, low, high], &[], false)?;
+        self.read_phase(address, &mut result)?;
+
+        Ok(u16::from_le_bytes(result))
+    }
+
+    /// Writes the byte count followed by `data` to the block selected by `command`, at most 252
+    /// bytes so the whole transfer fits in 255 bytes
+    pub fn block_write(&mut self, address: u8, command: u8, data: &[u8]) -> Result<(), Error> {
+        assert!(!data.is_empty() && data.len() <= 252, "a block is 1 to 252 bytes long");
+        self.write_phase(address, &[command, data.len() as u8], data, true)
+    }
+
+    /// Reads the block selected by `command` into `buffer` and returns its length
+    ///
+    /// A block that does not fit in `buffer` is read completely but only the first bytes are
+    /// stored, and [`Error::BlockLength`] is returned.
+    pub fn block_read(&mut self, address: u8, command: u8, buffer: &mut [u8]) -> Result<usize, Error> {
+        self.write_phase(address, &[command], &[], false)?;
+
+        // Receive the byte count first, the length of the rest of the transfer depends on it
+        let pec = self.pec;
+        self.start(address, true, 1, true, true, false);
+        let count = self.read_byte_raw()?;
+
+        // At least one more byte has to be read, the byte count has already been acknowledged
+        let nbytes = cmp::max(count as usize + pec as usize, 1);
+        self.wait(|isr| isr.tcr().bit_is_set(), true)?;
+        if nbytes > 255 {
+            // The rest of the block doesn't fit in NBYTES, NACK the next byte and end with a STOP
+            self.i2c.i2c.cr2.modify(|_, w| {
+                w.nbytes().bits(1).reload().clear_bit().autoend().set_bit().pecbyte().clear_bit()
+            });
+            self.read_byte_raw()?;
+            self.wait(|isr| isr.stopf().bit_is_set(), true)?;
+            self.i2c.i2c.icr.write(|w| w.stopcf().set_bit());
+            return Err(Error::BlockLength(count));
+        }
+        self.i2c.i2c.cr2.modify(|_, w| {
+            w.nbytes().bits(nbytes as u8).reload().clear_bit().autoend().set_bit().pecbyte().bit(pec)
+        });
+
+        for i in 0..nbytes {
+            let byte = self.read_byte_raw()?;
+            if i < count as usize && i < buffer.len() {
+                buffer[i] = byte;
+            }
+        }
+
+        self.wait(|isr| isr.stopf().bit_is_set(), true)?;
+        self.i2c.i2c.icr.write(|w| w.stopcf().set_bit());
+
+        if count == 0 || count as usize > buffer.len() {
+            return Err(Error::BlockLength(count));
+        }
+
+        Ok(count as usize)
+    }
+
+    /// Reads the address of the device that pulled SMBALERT# low, from the alert response
+    /// address. The device releases SMBALERT# afterwards.
+    pub fn alert_response(&mut self) -> Result<u8, Error> {
+        Ok(self.receive_byte(ALERT_RESPONSE_ADDRESS)? >> 1)
+    }
+
+    /// Enables the error interrupt, which also fires when SMBALERT# is pulled low
+    pub fn listen_alert(&mut self) {
+        self.i2c.i2c.cr1.modify(|_, w| w.errie().set_bit());
+    }
+
+    /// Disables the error interrupt
+    pub fn unlisten_alert(&mut self) {
+        self.i2c.i2c.cr1.modify(|_, w| w.errie().clear_bit());
+    }
+
+    /// Returns true if a device pulled SMBALERT# low
+    pub fn is_alert(&self) -> bool {
+        self.i2c.i2c.isr.read().alert().bit_is_set()
+    }
+
+    /// Clears the alert flag, call [`alert_response`](Self::alert_response) to find the device
+    pub fn clear_alert(&mut self) {
+        self.i2c.i2c.icr.write(|w| w.alertcf().set_bit());
+    }
+
+    /// Switches the peripheral back to plain I2C and releases it with the SMBALERT# pin
+    pub fn release(self) -> (I2cObj<I2C, SDA, SCL>, SMBA) {
+        let i2c = &self.i2c.i2c;
+        i2c.cr1.modify(|_, w| w.pe().clear_bit());
+        i2c.timeoutr.modify(|_, w| w.texten().clear_bit());
+        i2c.cr1.modify(|_, w| {
+            w.smbhen()
+                .clear_bit()
+                .pecen()
+                .clear_bit()
+                .alerten()
+                .clear_bit()
+                .errie()
+                .clear_bit()
+                .pe()
+                .set_bit()
+        });
+
+        (self.i2c, self.smba)
+    }
+}
+
+impl SMBAPin<I2C1> for PB5<AlternateOD<AF4>> {}
+
+impl SMBAPin<I2C2> for PA10<AlternateOD<AF4>> {}
+impl SMBAPin<I2C2> for PB12<AlternateOD<AF4>> {}
+impl SMBAPin<I2C2> for PF2<AlternateOD<AF4>> {}
+
+impl SMBAPin<I2C3> for PB2<AlternateOD<AF4>> {}
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+impl SMBAPin<I2C3> for PG6<AlternateOD<AF4>> {}
+
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+impl SMBAPin<I2C4> for PD11<AlternateOD<AF4>> {}
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+impl SMBAPin<I2C4> for PF13<AlternateOD<AF4>> {}
@@ -24,6 +24,7 @@ use crate::stm32::SPI4;
 use crate::stm32::{RCC, SPI1, SPI2, SPI3};
 use crate::time::Hertz;
 use core::cell::UnsafeCell;
+use core::marker::PhantomData;
 use core::ptr;
 
 
@@ -51,10 +52,56 @@ impl<SPI, SCK, MISO, MOSI> Pins<SPI> for (SCK, MISO, MOSI)
 {
 }
 
+/// Word type of the data frames, which also sets the width of the data register accesses
+pub trait FrameWord: Copy + Default + 'static {
+    /// Smallest frame size in bits that can be used with this word
+    const MIN_BITS: u8;
+    /// Largest frame size in bits that can be used with this word
+    const MAX_BITS: u8;
+
+    #[doc(hidden)]
+    unsafe fn read_dr(dr: *const u32) -> Self;
+    #[doc(hidden)]
+    unsafe fn write_dr(dr: *const u32, word: Self);
+}
+
+macro_rules! frame_word {
+    ($($word:ty: $min:literal..=$max:literal,)+) => {
+        $(
+            impl FrameWord for $word {
+                const MIN_BITS: u8 = $min;
+                const MAX_BITS: u8 = $max;
+
+                #[inline(always)]
+                unsafe fn read_dr(dr: *const u32) -> Self {
+                    // NOTE(read_volatile) the access width decides how many frames are popped
+                    // from the RX FIFO, so only read the size of one word
+                    ptr::read_volatile(dr as *const $word)
+                }
+
+                #[inline(always)]
+                unsafe fn write_dr(dr: *const u32, word: Self) {
+                    // NOTE(write_volatile) the access width decides how many frames are pushed
+                    // into the TX FIFO, so only write the size of one word
+                    let dr = dr as *const UnsafeCell<$word>;
+                    ptr::write_volatile(UnsafeCell::raw_get(dr), word)
+                }
+            }
+        )+
+    };
+}
+
+frame_word!(
+    u8: 4..=8,
+    u16: 9..=16,
+);
+
+/// SPI peripheral. `WORD` is `u8` for frames of 4 to 8 bits and `u16` for frames of 9 to 16 bits.
 #[derive(Debug)]
-pub struct Spi<SPI, PINS> {
+pub struct Spi<SPI, PINS, WORD = u8> {
     spi: SPI,
     pins: PINS,
+    _word: PhantomData<WORD>,
 }
 
 pub trait SpiExt<SPI>: Sized {
@@ -91,6 +138,8 @@ macro_rules! spi {
         )*
 
         impl<PINS: Pins<$SPIX>> Spi<$SPIX, PINS> {
+            /// Creates a master with 8-bit frames. Use [`frame_size`](Self::frame_size) for
+            /// other frame sizes.
             pub fn $spiX<T>(
                 spi: $SPIX,
                 pins: PINS,
@@ -145,27 +194,98 @@ macro_rules! spi {
                         .spe().set_bit()
                 });
 
-                Spi { spi, pins }
+                Spi { spi, pins, _word: PhantomData }
             }
+        }
 
+        impl<PINS, WORD: FrameWord> Spi<$SPIX, PINS, WORD> {
             pub fn release(self) -> ($SPIX, PINS) {
                 (self.spi, self.pins)
             }
 
-            pub fn enable_tx_dma(self) -> Spi<$SPIX, PINS> {
+            /// Switches to frames of `bits` bits, transferred as `W` words.
+            ///
+            /// Panics if `bits` is outside of `W::MIN_BITS..=W::MAX_BITS`.
+            pub fn frame_size<W: FrameWord>(self, bits: u8) -> Spi<$SPIX, PINS, W> {
+                let mut spi = Spi {
+                    spi: self.spi,
+                    pins: self.pins,
+                    _word: PhantomData,
+                };
+                spi.set_frame_size(bits);
+                spi
+            }
+
+            /// Changes the number of bits per frame without changing the word type.
+            ///
+            /// Panics if `bits` is outside of `WORD::MIN_BITS..=WORD::MAX_BITS`.
+            pub fn set_frame_size(&mut self, bits: u8) {
+                assert!((WORD::MIN_BITS..=WORD::MAX_BITS).contains(&bits));
+
+                // DS may only be changed while the peripheral is disabled, so let the
+                // ongoing transfer finish first
+                while self.spi.sr.read().ftlvl().bits() != 0 {}
+                while self.spi.sr.read().bsy().bit_is_set() {}
+                self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+
+                // RXNE is raised per byte for frames up to 8 bits and per half-word otherwise,
+                // matching the width of the data register accesses
+                self.spi.cr2.modify(|_, w| unsafe {
+                    w.ds().bits(bits - 1).frxth().bit(bits <= 8)
+                });
+
+                self.spi.cr1.modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Returns the number of bits per frame
+            pub fn get_frame_size(&self) -> u8 {
+                self.spi.cr2.read().ds().bits() + 1
+            }
+
+            pub fn enable_tx_dma(self) -> Spi<$SPIX, PINS, WORD> {
                 self.spi.cr2.modify(|_, w| w.txdmaen().set_bit());
                 Spi {
                     spi: self.spi,
                     pins: self.pins,
+                    _word: PhantomData,
                 }
             }
 
-            pub fn enable_rx_dma(self) -> Spi<$SPIX, PINS> {
+            pub fn enable_rx_dma(self) -> Spi<$SPIX, PINS, WORD> {
                 self.spi.cr2.modify(|_, w| w.rxdmaen().set_bit());
                 Spi {
                     spi: self.spi,
                     pins: self.pins,
+                    _word: PhantomData,
+                }
+            }
+
+            #[inline(always)]
+            fn dr(&self) -> *const u32 {
+                &self.spi.dr as *const _ as *const u32
+            }
+
+            /// Sends one word and returns the word that was received at the same time
+            fn exchange(&mut self, word: WORD) -> Result<WORD, ErrorKind> {
+                while self.spi.sr.read().txe().bit_is_clear() {
+                    //TODO: Consider adding a timeout here.
+                }
+
+                unsafe { WORD::write_dr(self.dr(), word) };
+
+                // Wait for receive buffer not empty
+                while self.spi.sr.read().rxne().bit_is_clear() {}
+
+                // Check for errors
+                if self.spi.sr.read().ovr().bit_is_set() {
+                    return Err(ErrorKind::Overrun);
                 }
+                if self.spi.sr.read().modf().bit_is_set() {
+                    return Err(ErrorKind::ModeFault);
+                }
+
+                // Read the received data
+                Ok(unsafe { WORD::read_dr(self.dr()) })
             }
         }
 
@@ -179,154 +299,38 @@ macro_rules! spi {
                 }
         }
 
-        impl<PINS> SpiBus<u8> for Spi<$SPIX, PINS> {
-
-            fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        impl<PINS, WORD: FrameWord> SpiBus<WORD> for Spi<$SPIX, PINS, WORD> {
+            fn read(&mut self, words: &mut [WORD]) -> Result<(), Self::Error> {
                 for word in words.iter_mut() {
                     // Write dummy data to generate clock pulses
-                    while self.spi.sr.read().txe().bit_is_clear() {}
-
-                    let dr = &self.spi.dr as *const _ as *const UnsafeCell<u8>;
-                        // NOTE(write_volatile) see note above
-                        unsafe { ptr::write_volatile(UnsafeCell::raw_get(dr), 0x00) };
-
-                    // Wait for receive buffer not empty
-                    while self.spi.sr.read().rxne().bit_is_clear() {}
-
-                    // Check for errors
-                    if self.spi.sr.read().ovr().bit_is_set() {
-                        return Err(ErrorKind::Overrun);
-                    }
-                    if self.spi.sr.read().modf().bit_is_set() {
-                        return Err(ErrorKind::ModeFault);
-                    }
-
-                    // NOTE(read_volatile) read only 1 byte (the svd2rust API only allows
-                    // reading a half-word)
-                    // Read the received data
-                    unsafe{
-                        *word = ptr::read_volatile(&self.spi.dr as *const _ as *const u8)
-                    }
-
+                    *word = self.exchange(WORD::default())?;
                 }
                 Ok(())
             }
 
-            fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
-                 for &word in words.iter() {
-                    while self.spi.sr.read().txe().bit_is_clear() {
-                        //TODO: Consider adding a timeout here.
-                    }
-
-                    let dr = &self.spi.dr as *const _ as *const UnsafeCell<u8>;
-                    // NOTE(write_volatile) see note above
-                    unsafe { ptr::write_volatile(UnsafeCell::raw_get(dr), word) };
-
-                   // Wait for receive buffer not empty
-                    while self.spi.sr.read().rxne().bit_is_clear() {}
-
-                    // Check for errors
-                    if self.spi.sr.read().ovr().bit_is_set() {
-                        return Err(ErrorKind::Overrun);
-                    }
-                    if self.spi.sr.read().modf().bit_is_set() {
-                        return Err(ErrorKind::ModeFault);
-                    }
-
-                    unsafe {
-                        // Read the received data
-                        let _ignored_read_data = ptr::read_volatile(&self.spi.dr as *const _ as *const u8);
-                    }
-
+            fn write(&mut self, words: &[WORD]) -> Result<(), Self::Error> {
+                for &word in words.iter() {
+                    self.exchange(word)?;
                 }
                 Ok(())
             }
 
-            fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
-
-                let mut length = read.len();
-
-                if(write.len() > 0)
-                {
-                    length = write.len();
-                }
+            fn transfer(&mut self, read: &mut [WORD], write: &[WORD]) -> Result<(), Self::Error> {
+                // The shorter buffer is padded with dummy writes or discarded reads
+                let length = read.len().max(write.len());
 
                 for i in 0..length {
-
-                    while self.spi.sr.read().txe().bit_is_clear() {
-                        //TODO: Consider adding a timeout here.
-                    }
-
-                    if(i < length)
-                    {
-                        let dr = &self.spi.dr as *const _ as *const UnsafeCell<u8>;
-                        // NOTE(write_volatile) see note above
-                        unsafe { ptr::write_volatile(UnsafeCell::raw_get(dr), write[i]) };
+                    let word = self.exchange(write.get(i).copied().unwrap_or_default())?;
+                    if let Some(r) = read.get_mut(i) {
+                        *r = word;
                     }
-                    else
-                    {
-                        let dr = &self.spi.dr as *const _ as *const UnsafeCell<u8>;
-                        // NOTE(write_volatile) see note above
-                        unsafe { ptr::write_volatile(UnsafeCell::raw_get(dr), 0x00) };
-                    }
-
-                   // Wait for receive buffer not empty
-                    while self.spi.sr.read().rxne().bit_is_clear() {}
-
-                    // Check for errors
-                    if self.spi.sr.read().ovr().bit_is_set() {
-                        return Err(ErrorKind::Overrun);
-                    }
-                    if self.spi.sr.read().modf().bit_is_set() {
-                        return Err(ErrorKind::ModeFault);
-                    }
-
-                    if(i < length)
-                    {
-                        unsafe {
-                        // Read the received data
-                        read[i] = ptr::read_volatile(&self.spi.dr as *const _ as *const u8)
-                    }
-                    }
-                    else
-                    {
-                        unsafe {
-                        // Read the received data
-                        let _ignored_read_data = ptr::read_volatile(&self.spi.dr as *const _ as *const u8);
-                        }
-                    }
-
-
                 }
                 Ok(())
             }
 
-            fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+            fn transfer_in_place(&mut self, words: &mut [WORD]) -> Result<(), Self::Error> {
                 for word in words.iter_mut() {
-                    while self.spi.sr.read().txe().bit_is_clear() {
-                        //TODO: Consider adding a timeout here.
-                    }
-
-                    let dr = &self.spi.dr as *const _ as *const UnsafeCell<u8>;
-                    // NOTE(write_volatile) see note above
-                    unsafe { ptr::write_volatile(UnsafeCell::raw_get(dr), *word) };
-
-                   // Wait for receive buffer not empty
-                    while self.spi.sr.read().rxne().bit_is_clear() {}
-
-                    // Check for errors
-                    if self.spi.sr.read().ovr().bit_is_set() {
-                        return Err(ErrorKind::Overrun);
-                    }
-                    if self.spi.sr.read().modf().bit_is_set() {
-                        return Err(ErrorKind::ModeFault);
-                    }
-
-                    unsafe {
-                        // Read the received data
-                        *word = ptr::read_volatile(&self.spi.dr as *const _ as *const u8);
-                    }
-
+                    *word = self.exchange(*word)?;
                 }
                 Ok(())
             }
@@ -342,10 +346,10 @@ macro_rules! spi {
         }
 
         //Implementation of old api. This uses a different set of errors as the new API.
-        impl<PINS> hal_api_old::spi::FullDuplex<u8> for Spi<$SPIX, PINS> {
+        impl<PINS, WORD: FrameWord> hal_api_old::spi::FullDuplex<WORD> for Spi<$SPIX, PINS, WORD> {
             type Error = crate::spi_compat::Error;
 
-            fn read(&mut self) -> nb::Result<u8, crate::spi_compat::Error> {
+            fn read(&mut self) -> nb::Result<WORD, crate::spi_compat::Error> {
                 let sr = self.spi.sr.read();
 
                 Err(if sr.ovr().bit_is_set() {
@@ -355,17 +359,13 @@ macro_rules! spi {
                 } else if sr.crcerr().bit_is_set() {
                     nb::Error::Other(crate::spi_compat::Error::Crc)
                 } else if sr.rxne().bit_is_set() {
-                    // NOTE(read_volatile) read only 1 byte (the svd2rust API only allows
-                    // reading a half-word)
-                    return Ok(unsafe {
-                        ptr::read_volatile(&self.spi.dr as *const _ as *const u8)
-                    });
+                    return Ok(unsafe { WORD::read_dr(self.dr()) });
                 } else {
                     nb::Error::WouldBlock
                 })
             }
 
-            fn send(&mut self, byte: u8) -> nb::Result<(), crate::spi_compat::Error> {
+            fn send(&mut self, word: WORD) -> nb::Result<(), crate::spi_compat::Error> {
                 let sr = self.spi.sr.read();
 
                 Err(if sr.ovr().bit_is_set() {
@@ -375,9 +375,7 @@ macro_rules! spi {
                 } else if sr.crcerr().bit_is_set() {
                     nb::Error::Other(crate::spi_compat::Error::Crc)
                 } else if sr.txe().bit_is_set() {
-                    let dr = &self.spi.dr as *const _ as *const UnsafeCell<u8>;
-                    // NOTE(write_volatile) see note above
-                    unsafe { ptr::write_volatile(UnsafeCell::raw_get(dr), byte) };
+                    unsafe { WORD::write_dr(self.dr(), word) };
                     return Ok(());
                 } else {
                     nb::Error::WouldBlock
@@ -385,19 +383,19 @@ macro_rules! spi {
             }
         }
 
-        unsafe impl<Pin> TargetAddress<MemoryToPeripheral> for Spi<$SPIX, Pin> {
+        unsafe impl<Pin, WORD: FrameWord> TargetAddress<MemoryToPeripheral> for Spi<$SPIX, Pin, WORD> {
             #[inline(always)]
             fn address(&self) -> u32 {
                 // unsafe: only the Tx part accesses the Tx register
                 &unsafe { &*<$SPIX>::ptr() }.dr as *const _ as u32
             }
 
-            type MemSize = u8;
+            type MemSize = WORD;
 
             const REQUEST_LINE: Option<u8> = Some($mux as u8);
         }
 
-        impl<PINS> ErrorType for Spi<$SPIX, PINS> {
+        impl<PINS, WORD> ErrorType for Spi<$SPIX, PINS, WORD> {
                 type Error = ErrorKind;
             }
     }
@@ -0,0 +1,459 @@
+//! SPI slave (peripheral) mode with hardware NSS
+//!
+//! An [`SpiSlave`] shifts data in and out on the clock of an external master while its NSS pin
+//! is held low. Words are exchanged through the FIFOs with the [`FullDuplex`] implementation,
+//! which can be driven from the SPI interrupt after calling [`SpiSlave::listen`].
+//!
+//! Messages of which the length is delimited by NSS can be received by DMA with a
+//! [`FrameReceiver`]. The rising edge of NSS is detected through its EXTI line, after which
+//! [`FrameReceiver::poll`] hands out the received words and rearms the DMA stream:
+//!
+//! ```rust,ignore
+//!   let slave = dp.SPI1.spi_slave((sck, miso, mosi), nss, MODE_0, &mut rcc);
+//!   let mut receiver = slave.receive_frames_dma(stream, buf, DmaConfig::default(), &mut syscfg, &mut exti);
+//!
+//!   loop {
+//!       match receiver.poll(|frame| handle(frame)) {
+//!           Ok(()) | Err(nb::Error::WouldBlock) => {}
+//!           Err(nb::Error::Other(_)) => { /* the frame did not fit in the buffer */ }
+//!       }
+//!   }
+//! ```
+//!
+//! [`FullDuplex`]: hal_api_old::spi::FullDuplex
+
+use core::marker::PhantomData;
+
+use embedded_dma::WriteBuffer;
+use hal_api::spi::{ErrorKind, Mode, Phase, Polarity};
+
+use crate::dma::config::DmaConfig;
+use crate::dma::mux::DmaMuxResources;
+use crate::dma::traits::{Stream, TargetAddress};
+use crate::dma::transfer::{MutTransfer, Transfer};
+use crate::dma::PeripheralToMemory;
+use crate::gpio::{gpioa::*, gpiob::*, gpiof::*, Alternate, ExtiPin, SignalEdge, AF5, AF6};
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+use crate::gpio::{gpioe::*, gpiog::*};
+use crate::rcc::{Enable, Rcc, Reset};
+use crate::spi::{FrameWord, Pins};
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+use crate::stm32::SPI4;
+use crate::stm32::{EXTI, RCC, SPI1, SPI2, SPI3};
+use crate::syscfg::SysCfg;
+
+pub trait PinNss<SPI> {}
+
+/// SPI slave interrupt event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The RX FIFO holds at least one frame
+    Rxne,
+    /// The TX FIFO is at most half full
+    Txe,
+    /// Overrun, mode fault or CRC error
+    Error,
+}
+
+/// Fill level of the RX or TX FIFO
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FifoLevel {
+    Empty,
+    Quarter,
+    Half,
+    Full,
+}
+
+impl From<u8> for FifoLevel {
+    fn from(bits: u8) -> Self {
+        match bits {
+            0b00 => FifoLevel::Empty,
+            0b01 => FifoLevel::Quarter,
+            0b10 => FifoLevel::Half,
+            _ => FifoLevel::Full,
+        }
+    }
+}
+
+/// SPI peripheral in slave mode. `WORD` is `u8` for frames of 4 to 8 bits and `u16` for frames
+/// of 9 to 16 bits.
+#[derive(Debug)]
+pub struct SpiSlave<SPI, PINS, NSS, WORD = u8> {
+    spi: SPI,
+    pins: PINS,
+    nss: NSS,
+    _word: PhantomData<WORD>,
+}
+
+pub trait SpiSlaveExt<SPI>: Sized {
+    fn spi_slave<PINS, NSS>(self, pins: PINS, nss: NSS, mode: Mode, rcc: &mut Rcc) -> SpiSlave<SPI, PINS, NSS>
+    where
+        PINS: Pins<SPI>,
+        NSS: PinNss<SPI>;
+}
+
+/// DMA target for the receive data register of an SPI peripheral, used by [`FrameReceiver`]
+pub struct RxDma<SPI, WORD> {
+    _spi: PhantomData<(SPI, WORD)>,
+}
+
+/// Reception of NSS delimited frames by DMA, created by [`SpiSlave::receive_frames_dma`]
+pub struct FrameReceiver<SPI, PINS, NSS, WORD, STREAM, BUF>
+where
+    STREAM: Stream,
+    RxDma<SPI, WORD>: TargetAddress<PeripheralToMemory>,
+{
+    spi: SpiSlave<SPI, PINS, NSS, WORD>,
+    transfer: Transfer<STREAM, RxDma<SPI, WORD>, PeripheralToMemory, BUF, MutTransfer>,
+    len: usize,
+}
+
+macro_rules! spi_slave {
+    ($SPIX:ident, $spiX_slave:ident,
+        nss: [ $($( #[ $pmetanss:meta ] )* $NSS:ty,)+ ],
+        $mux:expr,
+    ) => {
+        $(
+            $( #[ $pmetanss ] )*
+            impl PinNss<$SPIX> for $NSS {}
+        )+
+
+        impl<PINS: Pins<$SPIX>, NSS: PinNss<$SPIX>> SpiSlave<$SPIX, PINS, NSS> {
+            /// Creates a slave with 8-bit frames that is selected by the hardware NSS pin. Use
+            /// [`frame_size`](Self::frame_size) for other frame sizes.
+            pub fn $spiX_slave(spi: $SPIX, pins: PINS, nss: NSS, mode: Mode, _rcc: &mut Rcc) -> Self {
+                // Enable and reset SPI
+                unsafe {
+                    let rcc_ptr = &(*RCC::ptr());
+                    $SPIX::enable(rcc_ptr);
+                    $SPIX::reset(rcc_ptr);
+                }
+
+                spi.cr2.write(|w| unsafe {
+                    w.frxth().set_bit().ds().bits(0b111).ssoe().clear_bit()
+                });
+
+                // The clock comes from the master, so the baud rate is not used
+                spi.cr1.write(|w| {
+                    w
+                        .cpha().bit(mode.phase == Phase::CaptureOnSecondTransition)
+                        .cpol().bit(mode.polarity == Polarity::IdleHigh)
+                        .mstr().clear_bit()
+                        .lsbfirst().clear_bit()
+                        .ssm().clear_bit()
+                        .rxonly().clear_bit()
+                        .bidimode().clear_bit()
+                        .spe().set_bit()
+                });
+
+                SpiSlave { spi, pins, nss, _word: PhantomData }
+            }
+        }
+
+        impl<PINS, NSS, WORD: FrameWord> SpiSlave<$SPIX, PINS, NSS, WORD> {
+            pub fn release(self) -> ($SPIX, PINS, NSS) {
+                (self.spi, self.pins, self.nss)
+            }
+
+            /// Switches to frames of `bits` bits, transferred as `W` words.
+            ///
+            /// Panics if `bits` is outside of `W::MIN_BITS..=W::MAX_BITS`.
+            pub fn frame_size<W: FrameWord>(self, bits: u8) -> SpiSlave<$SPIX, PINS, NSS, W> {
+                let mut spi = SpiSlave {
+                    spi: self.spi,
+                    pins: self.pins,
+                    nss: self.nss,
+                    _word: PhantomData,
+                };
+                spi.set_frame_size(bits);
+                spi
+            }
+
+            /// Changes the number of bits per frame without changing the word type. Should only
+            /// be called while the slave is not selected.
+            ///
+            /// Panics if `bits` is outside of `WORD::MIN_BITS..=WORD::MAX_BITS`.
+            pub fn set_frame_size(&mut self, bits: u8) {
+                assert!((WORD::MIN_BITS..=WORD::MAX_BITS).contains(&bits));
+
+                // DS may only be changed while the peripheral is disabled
+                while self.spi.sr.read().bsy().bit_is_set() {}
+                self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+
+                self.spi.cr2.modify(|_, w| unsafe {
+                    w.ds().bits(bits - 1).frxth().bit(bits <= 8)
+                });
+
+                self.spi.cr1.modify(|_, w| w.spe().set_bit());
+            }
+
+            /// Returns the number of bits per frame
+            pub fn get_frame_size(&self) -> u8 {
+                self.spi.cr2.read().ds().bits() + 1
+            }
+
+            /// Starts listening for an interrupt event
+            pub fn listen(&mut self, event: Event) {
+                self.spi.cr2.modify(|_, w| match event {
+                    Event::Rxne => w.rxneie().set_bit(),
+                    Event::Txe => w.txeie().set_bit(),
+                    Event::Error => w.errie().set_bit(),
+                });
+            }
+
+            /// Stops listening for an interrupt event
+            pub fn unlisten(&mut self, event: Event) {
+                self.spi.cr2.modify(|_, w| match event {
+                    Event::Rxne => w.rxneie().clear_bit(),
+                    Event::Txe => w.txeie().clear_bit(),
+                    Event::Error => w.errie().clear_bit(),
+                });
+            }
+
+            /// Returns the fill level of the RX FIFO
+            pub fn rx_fifo_level(&self) -> FifoLevel {
+                self.spi.sr.read().frlvl().bits().into()
+            }
+
+            /// Returns the fill level of the TX FIFO
+            pub fn tx_fifo_level(&self) -> FifoLevel {
+                self.spi.sr.read().ftlvl().bits().into()
+            }
+
+            /// Returns true while a frame is being shifted or the TX FIFO is not empty
+            pub fn is_busy(&self) -> bool {
+                self.spi.sr.read().bsy().bit_is_set()
+            }
+
+            /// Clears the overrun flag. The frames that were received before the overrun are
+            /// discarded.
+            pub fn clear_overrun(&mut self) {
+                while self.spi.sr.read().frlvl().bits() != 0 {
+                    let _ = unsafe { u8::read_dr(self.dr()) };
+                }
+                // Reading SR after DR clears OVR
+                let _ = self.spi.sr.read();
+            }
+
+            #[inline(always)]
+            fn dr(&self) -> *const u32 {
+                &self.spi.dr as *const _ as *const u32
+            }
+
+            /// Starts receiving frames into `buf` by DMA. A frame ends on the rising edge of NSS,
+            /// which is detected through the EXTI line of the NSS pin. The EXTI interrupt is
+            /// enabled, so [`FrameReceiver::poll`] can be called from its handler.
+            ///
+            /// The memory increment of the DMA configuration is always enabled.
+            pub fn receive_frames_dma<STREAM, BUF>(
+                mut self,
+                stream: STREAM,
+                mut buf: BUF,
+                config: DmaConfig,
+                syscfg: &mut SysCfg,
+                exti: &mut EXTI,
+            ) -> FrameReceiver<$SPIX, PINS, NSS, WORD, STREAM, BUF>
+            where
+                NSS: ExtiPin,
+                STREAM: Stream<Config = DmaConfig>,
+                BUF: WriteBuffer<Word = WORD>,
+            {
+                let (_, len) = unsafe { buf.write_buffer() };
+                assert!(len > 0);
+
+                self.nss.make_interrupt_source(syscfg);
+                self.nss.trigger_on_edge(exti, SignalEdge::Rising);
+                self.nss.clear_interrupt_pending_bit();
+                self.nss.enable_interrupt(exti);
+
+                let mut transfer = Transfer::init(stream, RxDma { _spi: PhantomData }, buf, config.memory_increment(true));
+
+                // The DMA request and stream have to be enabled before the SPI
+                self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+                self.spi.cr2.modify(|_, w| w.rxdmaen().set_bit());
+                transfer.start(|_| {});
+                self.spi.cr1.modify(|_, w| w.spe().set_bit());
+
+                FrameReceiver { spi: self, transfer, len }
+            }
+        }
+
+        impl SpiSlaveExt<$SPIX> for $SPIX {
+            fn spi_slave<PINS, NSS>(self, pins: PINS, nss: NSS, mode: Mode, rcc: &mut Rcc) -> SpiSlave<$SPIX, PINS, NSS>
+            where
+                PINS: Pins<$SPIX>,
+                NSS: PinNss<$SPIX>,
+            {
+                SpiSlave::$spiX_slave(self, pins, nss, mode, rcc)
+            }
+        }
+
+        //Implementation of old api, the new API has no slave traits.
+        impl<PINS, NSS, WORD: FrameWord> hal_api_old::spi::FullDuplex<WORD> for SpiSlave<$SPIX, PINS, NSS, WORD> {
+            type Error = crate::spi_compat::Error;
+
+            fn read(&mut self) -> nb::Result<WORD, crate::spi_compat::Error> {
+                let sr = self.spi.sr.read();
+
+                Err(if sr.ovr().bit_is_set() {
+                    nb::Error::Other(crate::spi_compat::Error::Overrun)
+                } else if sr.modf().bit_is_set() {
+                    nb::Error::Other(crate::spi_compat::Error::ModeFault)
+                } else if sr.crcerr().bit_is_set() {
+                    nb::Error::Other(crate::spi_compat::Error::Crc)
+                } else if sr.rxne().bit_is_set() {
+                    return Ok(unsafe { WORD::read_dr(self.dr()) });
+                } else {
+                    nb::Error::WouldBlock
+                })
+            }
+
+            fn send(&mut self, word: WORD) -> nb::Result<(), crate::spi_compat::Error> {
+                let sr = self.spi.sr.read();
+
+                Err(if sr.ovr().bit_is_set() {
+                    nb::Error::Other(crate::spi_compat::Error::Overrun)
+                } else if sr.modf().bit_is_set() {
+                    nb::Error::Other(crate::spi_compat::Error::ModeFault)
+                } else if sr.crcerr().bit_is_set() {
+                    nb::Error::Other(crate::spi_compat::Error::Crc)
+                } else if sr.txe().bit_is_set() {
+                    unsafe { WORD::write_dr(self.dr(), word) };
+                    return Ok(());
+                } else {
+                    nb::Error::WouldBlock
+                })
+            }
+        }
+
+        impl<PINS, NSS, WORD, STREAM, BUF> FrameReceiver<$SPIX, PINS, NSS, WORD, STREAM, BUF>
+        where
+            NSS: ExtiPin,
+            WORD: FrameWord,
+            STREAM: Stream<Config = DmaConfig>,
+            BUF: WriteBuffer<Word = WORD>,
+        {
+            /// Hands the words of a finished frame to `f` and rearms the DMA stream for the next
+            /// frame. Returns `WouldBlock` while NSS has not been released.
+            ///
+            /// A frame that did not fit in the buffer is discarded and reported as
+            /// [`ErrorKind::Overrun`].
+            pub fn poll<F, T>(&mut self, f: F) -> nb::Result<T, ErrorKind>
+            where
+                F: FnOnce(&[WORD]) -> T,
+                BUF: AsRef<[WORD]>,
+            {
+                if !self.spi.nss.check_interrupt() {
+                    return Err(nb::Error::WouldBlock);
+                }
+                self.spi.nss.clear_interrupt_pending_bit();
+
+                // Let the DMA move the last frames out of the RX FIFO
+                while STREAM::get_number_of_transfers() != 0 && self.spi.spi.sr.read().frlvl().bits() != 0 {}
+                self.transfer.pause(|_| {});
+
+                let received = self.len - STREAM::get_number_of_transfers() as usize;
+                let sr = self.spi.spi.sr.read();
+                let result = if sr.ovr().bit_is_set() || sr.frlvl().bits() != 0 {
+                    self.spi.clear_overrun();
+                    Err(nb::Error::Other(ErrorKind::Overrun))
+                } else {
+                    Ok(f(&self.transfer.buffer().as_ref()[..received]))
+                };
+
+                self.transfer.clear_interrupts();
+                self.transfer.restart(|_| {});
+
+                result
+            }
+
+            /// Stops the reception and returns the slave, the DMA stream and the buffer
+            pub fn free(mut self, exti: &mut EXTI) -> (SpiSlave<$SPIX, PINS, NSS, WORD>, STREAM, BUF) {
+                self.spi.nss.disable_interrupt(exti);
+                self.spi.spi.cr2.modify(|_, w| w.rxdmaen().clear_bit());
+                let (stream, _, buf) = self.transfer.free();
+
+                (self.spi, stream, buf)
+            }
+        }
+
+        unsafe impl<WORD: FrameWord> TargetAddress<PeripheralToMemory> for RxDma<$SPIX, WORD> {
+            #[inline(always)]
+            fn address(&self) -> u32 {
+                &unsafe { &*<$SPIX>::ptr() }.dr as *const _ as u32
+            }
+
+            type MemSize = WORD;
+
+            const REQUEST_LINE: Option<u8> = Some($mux as u8);
+        }
+    };
+}
+
+spi_slave!(
+    SPI1,
+    spi1_slave,
+    nss: [
+        PA4<Alternate<AF5>>,
+        PA15<Alternate<AF5>>,
+        #[cfg(any(
+            feature = "stm32g471",
+            feature = "stm32g473",
+            feature = "stm32g474",
+            feature = "stm32g483",
+            feature = "stm32g484"
+        ))]
+        PG5<Alternate<AF5>>,
+    ],
+    DmaMuxResources::SPI1_RX,
+);
+
+spi_slave!(
+    SPI2,
+    spi2_slave,
+    nss: [
+        PF0<Alternate<AF5>>,
+        PB12<Alternate<AF5>>,
+    ],
+    DmaMuxResources::SPI2_RX,
+);
+
+spi_slave!(
+    SPI3,
+    spi3_slave,
+    nss: [
+        PA4<Alternate<AF6>>,
+        PA15<Alternate<AF6>>,
+    ],
+    DmaMuxResources::SPI3_RX,
+);
+
+#[cfg(any(
+    feature = "stm32g471",
+    feature = "stm32g473",
+    feature = "stm32g474",
+    feature = "stm32g483",
+    feature = "stm32g484"
+))]
+spi_slave!(
+    SPI4,
+    spi4_slave,
+    nss: [
+        PE3<Alternate<AF5>>,
+        PE4<Alternate<AF5>>,
+        PE11<Alternate<AF5>>,
+    ],
+    DmaMuxResources::SPI4_RX,
+);
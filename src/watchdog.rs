@@ -0,0 +1,135 @@
+//! Window Watchdog
+//!
+//! The Window Watchdog peripheral is clocked from PCLK1 and triggers a system reset when its
+//! counter expires, or when it is fed before the start of the window.
+//!
+//! Just before the counter expires the early wakeup interrupt (`WWDG`) can be triggered, which
+//! can be used to save state or to feed the watchdog as a last resort, see
+//! [`WindowWatchdog::listen`].
+//!
+//! Both this and the [`IndependentWatchdog`](crate::independent_watchdog::IndependentWatchdog)
+//! implement the embedded-hal `Watchdog` and `WatchdogEnable` traits.
+use crate::rcc::{Enable, Rcc};
+use crate::stm32::WWDG;
+use crate::time::{Hertz, MicroSecond};
+use fugit::ExtU32;
+
+/// Extension trait that constrains the `WWDG` peripheral
+pub trait WWDGExt {
+    fn constrain(self, rcc: &mut Rcc) -> WindowWatchdog;
+}
+
+impl WWDGExt for WWDG {
+    fn constrain(self, rcc: &mut Rcc) -> WindowWatchdog {
+        WindowWatchdog::new(self, rcc)
+    }
+}
+
+/// The implementation of the hardware WWDG
+pub struct WindowWatchdog {
+    wwdg: WWDG,
+    pclk: Hertz,
+    reload: u8,
+}
+
+impl WindowWatchdog {
+    /// The counter resets the device when it drops below this value
+    const MIN_COUNTER_VALUE: u32 = 0x40;
+    const MAX_COUNTER_VALUE: u32 = 0x7F;
+    /// Fixed divider between PCLK1 and the prescaler
+    const CLOCK_DIVIDER: u32 = 4096;
+    const MAX_PRESCALER_BITS: u8 = 0b111;
+
+    /// Create a new instance
+    pub fn new(wwdg: WWDG, rcc: &mut Rcc) -> Self {
+        WWDG::enable(&rcc.rb);
+
+        Self {
+            wwdg,
+            pclk: rcc.clocks.apb1_clk,
+            reload: Self::MAX_COUNTER_VALUE as u8,
+        }
+    }
+
+    /// Feed the watchdog, resetting the counter
+    pub fn feed(&mut self) {
+        self.wwdg.cr.write(|w| w.t().bits(self.reload));
+    }
+
+    /// Start the watchdog where it must be fed before the max time is over and
+    /// not before the min time has passed
+    ///
+    /// Once started the watchdog can not be stopped, except by a reset.
+    pub fn start_windowed<T: Into<MicroSecond>>(&mut self, min_window_time: T, max_window_time: T) {
+        let min_window_time: MicroSecond = min_window_time.into();
+        let max_window_time: MicroSecond = max_window_time.into();
+        let max_ticks = Self::MAX_COUNTER_VALUE - Self::MIN_COUNTER_VALUE + 1;
+
+        // Find the smallest prescaler for which the max time fits in the counter
+        let (prescaler, tick_us) = (0..=Self::MAX_PRESCALER_BITS)
+            .map(|prescaler| (prescaler, self.tick_us(prescaler)))
+            .find(|(_, tick_us)| max_window_time.to_micros() as u64 <= *tick_us * max_ticks as u64)
+            .expect("WWDG max time is greater than is possible");
+
+        // Calculate the counter values
+        let ticks = ((max_window_time.to_micros() as u64 / tick_us) as u32).clamp(1, max_ticks);
+        let reload = Self::MIN_COUNTER_VALUE + ticks - 1;
+        let min_ticks = (min_window_time.to_micros() as u64).div_ceil(tick_us) as u32;
+        let window = reload
+            .saturating_sub(min_ticks)
+            .max(Self::MIN_COUNTER_VALUE);
+
+        self.reload = reload as u8;
+        self.wwdg
+            .cfr
+            .modify(|_, w| w.wdgtb().bits(prescaler).w().bits(window as u8));
+
+        // Load the counter and start the watchdog
+        self.wwdg
+            .cr
+            .write(|w| w.t().bits(self.reload).wdga().set_bit());
+    }
+
+    /// Start the watchdog with the given max time and no minimal time
+    pub fn start<T: Into<MicroSecond>>(&mut self, max_time: T) {
+        self.start_windowed(0_u32.micros(), max_time.into());
+    }
+
+    /// Enables the early wakeup interrupt, which is triggered one counter tick before the
+    /// watchdog resets the device.
+    ///
+    /// This can only be disabled by a reset.
+    pub fn listen(&mut self) {
+        self.wwdg.cfr.modify(|_, w| w.ewi().set_bit());
+    }
+
+    /// Returns true if the early wakeup interrupt is pending.
+    pub fn is_pending(&self) -> bool {
+        self.wwdg.sr.read().ewif().bit_is_set()
+    }
+
+    /// Clears the early wakeup interrupt flag.
+    pub fn clear_interrupt(&mut self) {
+        self.wwdg.sr.write(|w| w.ewif().clear_bit());
+    }
+
+    /// Duration of one counter tick in microseconds
+    fn tick_us(&self, prescaler: u8) -> u64 {
+        let divider = (Self::CLOCK_DIVIDER << prescaler) as u64;
+        (divider * 1_000_000).div_ceil(self.pclk.raw() as u64)
+    }
+}
+
+impl hal_api_old::watchdog::Watchdog for WindowWatchdog {
+    fn feed(&mut self) {
+        WindowWatchdog::feed(self)
+    }
+}
+
+impl hal_api_old::watchdog::WatchdogEnable for WindowWatchdog {
+    type Time = MicroSecond;
+
+    fn start<T: Into<MicroSecond>>(&mut self, period: T) {
+        WindowWatchdog::start(self, period)
+    }
+}